@@ -1,7 +1,8 @@
 use {
     crate::{
         config::{
-            list::list_config, set::set_config, setup::handle_setup, show::show_config_value,
+            list::list_config, models::storage::CONFLICT_CHOICES, set::set_config,
+            setup::handle_setup, show::show_config_value,
         },
         history::{
             HistoryMode, build_history_json_detail_list, build_history_json_list, clear_history,
@@ -9,8 +10,9 @@ use {
             output_history_json_detail, output_history_json_list, output_history_table,
             parse_since_unix,
         },
-        receiver::start_receiver,
-        sender::start_send,
+        output,
+        receiver::{start_get, start_receiver, start_web_receiver},
+        sender::{start_send, start_serve, start_share, start_watch},
         update::update_portal,
     },
     anyhow::{Context, Result},
@@ -24,26 +26,7 @@ use {
 #[derive(Subcommand)]
 pub enum Commands {
     /// Send a file
-    Send {
-        /// The files or folders to send. If empty, opens the interactive picker.
-        file: Option<Vec<PathBuf>>,
-        /// The IP address of the receiver
-        #[arg(short, long)]
-        address: Option<String>,
-        /// The port the receiver is listening on
-        #[arg(short, long, default_value_t = 7878)]
-        port: u16,
-        /// The username of the receiver
-        /// If omitted, Portal will prompt you for a name.
-        #[arg(short, long, value_name = "USERNAME")]
-        to: Option<String>,
-        /// Send folder recursively
-        #[arg(short, long, value_name = "FOLDER")]
-        recursive: bool,
-        /// Disable gzip compression for tranfer
-        #[arg(long)]
-        no_compress: bool,
-    },
+    Send(SendArgs),
     /// Receive a file
     Receive {
         /// Specify which port to use
@@ -52,7 +35,31 @@ pub enum Commands {
         /// Directory where received files will be saved
         #[arg(short, long, value_name = "PATH")]
         dir: Option<PathBuf>,
+        /// Save received text to a file in the target directory instead of printing it
+        #[arg(long)]
+        save_text: bool,
+        /// Handle items that already exist without asking (default when Portal can't ask:
+        /// storage.on_conflict, or rename)
+        #[arg(long, value_name = "overwrite|rename|skip|merge", value_parser = CONFLICT_CHOICES)]
+        on_conflict: Option<String>,
+        /// Print newline-delimited JSON events on stdout instead of status lines and bars
+        #[arg(long)]
+        json: bool,
+        /// Receive from a browser instead: serve an upload page at a one-time link
+        #[arg(long, conflicts_with = "save_text")]
+        web: bool,
+        /// Show the transfer full-screen: a pinned header, the items and a log pane
+        #[arg(long, conflicts_with_all = ["json", "web"])]
+        tui: bool,
     },
+    /// Host files so other devices can fetch them with `portal get`
+    Serve(ServeArgs),
+    /// Fetch files from a device running `portal serve`
+    Get(GetArgs),
+    /// Offer files to browsers on the network through a download page
+    Share(ShareArgs),
+    /// Watch a folder and send new or changed files as they appear
+    Watch(WatchArgs),
     /// Update portal to latest version
     Update,
     /// Show transfer history and manage saved records
@@ -138,6 +145,171 @@ pub struct HistoryFilterArgs {
     pub since: Option<String>,
 }
 
+#[derive(Args, Debug, Clone)]
+pub struct SendArgs {
+    /// The files or folders to send. If empty (and no --text is given), opens the
+    /// interactive picker.
+    pub file: Option<Vec<PathBuf>>,
+    /// Send a piece of text. Without a value (or with '-'), the text is read from stdin.
+    #[arg(long, value_name = "TEXT", num_args = 0..=1, default_missing_value = "-")]
+    pub text: Option<String>,
+    /// The IP address of the receiver
+    #[arg(short, long)]
+    pub address: Option<String>,
+    /// The port the receiver is listening on
+    #[arg(short, long, default_value_t = 7878)]
+    pub port: u16,
+    /// The username of the receiver. Repeat to send to several receivers at once.
+    /// If omitted, Portal will prompt you for a name.
+    #[arg(short, long, value_name = "USERNAME")]
+    pub to: Vec<String>,
+    /// Seconds to search for the receiver by username (default: 30)
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub discovery_timeout: Option<u64>,
+    /// A note for the receiver; skips the question about adding one
+    #[arg(long, value_name = "TEXT")]
+    pub description: Option<String>,
+    /// Send folder recursively
+    #[arg(short, long, value_name = "FOLDER")]
+    pub recursive: bool,
+    /// Disable gzip compression for tranfer
+    #[arg(long)]
+    pub no_compress: bool,
+    /// Skip files and folders when sending directories
+    #[command(flatten)]
+    pub exclude: ExcludeArgs,
+    /// Retry failed connections and dropped transfers
+    #[command(flatten)]
+    pub retry: RetryArgs,
+    /// Print newline-delimited JSON events on stdout instead of status lines and bars
+    #[arg(long)]
+    pub json: bool,
+    /// Show the transfer full-screen: a pinned header, the items and a log pane
+    #[arg(long, conflicts_with = "json")]
+    pub tui: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ServeArgs {
+    /// The files or folders to offer
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
+    /// Specify which port to use
+    #[arg(short, long)]
+    pub port: Option<u16>,
+    /// Name getters can find this share by (default: your username)
+    #[arg(short, long)]
+    pub name: Option<String>,
+    /// Offer folders recursively
+    #[arg(short, long)]
+    pub recursive: bool,
+    /// Skip files and folders inside offered directories
+    #[command(flatten)]
+    pub exclude: ExcludeArgs,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ShareArgs {
+    /// The files or folders to offer
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
+    /// Specify which port to use
+    #[arg(short, long)]
+    pub port: Option<u16>,
+    /// Offer folders recursively (they download as zips)
+    #[arg(short, long)]
+    pub recursive: bool,
+    /// Skip files and folders inside offered directories
+    #[command(flatten)]
+    pub exclude: ExcludeArgs,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct WatchArgs {
+    /// The folder to watch
+    pub dir: PathBuf,
+    /// The username of the receiver. Repeat to send every batch to several receivers.
+    #[arg(short, long, value_name = "USERNAME", required_unless_present = "address")]
+    pub to: Vec<String>,
+    /// The IP address of the receiver
+    #[arg(short, long)]
+    pub address: Option<String>,
+    /// The port the receiver is listening on
+    #[arg(short, long, default_value_t = 7878)]
+    pub port: u16,
+    /// Seconds without changes before a batch is sent
+    #[arg(long, value_name = "SECS", default_value_t = 2,
+        value_parser = clap::value_parser!(u64).range(1..))]
+    pub debounce: u64,
+    /// Seconds to search for the receiver by username (default: 30)
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub discovery_timeout: Option<u64>,
+    /// Disable gzip compression for tranfer
+    #[arg(long)]
+    pub no_compress: bool,
+    /// Skip files and folders in the watched folder
+    #[command(flatten)]
+    pub exclude: ExcludeArgs,
+    /// Retry failed connections and dropped transfers
+    #[command(flatten)]
+    pub retry: RetryArgs,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct GetArgs {
+    /// The share's name or code, as shown by `portal serve`.
+    /// If omitted (and no --address is given), Portal will prompt for it.
+    pub share: Option<String>,
+    /// The IP address of the host
+    #[arg(short, long)]
+    pub address: Option<String>,
+    /// The port the host is listening on
+    #[arg(short, long, default_value_t = 7878)]
+    pub port: u16,
+    /// Directory where fetched files will be saved
+    #[arg(short, long, value_name = "PATH")]
+    pub dir: Option<PathBuf>,
+    /// Fetch everything the share offers without asking
+    #[arg(long)]
+    pub all: bool,
+    /// Disable gzip compression for the transfer
+    #[arg(long)]
+    pub no_compress: bool,
+    /// Handle items that already exist without asking (default when Portal can't ask:
+    /// storage.on_conflict, or rename)
+    #[arg(long, value_name = "overwrite|rename|skip|merge", value_parser = CONFLICT_CHOICES)]
+    pub on_conflict: Option<String>,
+    /// Seconds to search for the share (default: 30)
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub discovery_timeout: Option<u64>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct RetryArgs {
+    /// Extra attempts after a failed send (default: 3, 0 disables retrying)
+    #[arg(long, value_name = "N")]
+    pub retries: Option<u32>,
+    /// Seconds to wait before the first retry; doubles on each further attempt (default: 1)
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub retry_backoff: Option<u64>,
+    /// Which failures to retry (default: all)
+    #[arg(long, value_name = "connect|all", value_parser = ["connect", "all"])]
+    pub retry_on: Option<String>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ExcludeArgs {
+    /// Skip entries matching a glob when sending folders (repeatable, e.g. --exclude target)
+    #[arg(short = 'x', long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+    /// Read exclude globs from a file, one per line (# starts a comment)
+    #[arg(long, value_name = "FILE")]
+    pub exclude_from: Option<PathBuf>,
+    /// Honour .gitignore and .portalignore files (and skip .git/) when sending folders
+    #[arg(long)]
+    pub respect_gitignore: bool,
+}
+
 fn resolve_history_filters(
     parent: &HistoryFilterArgs,
     child: Option<&HistoryFilterArgs>,
@@ -176,36 +348,87 @@ impl Commands {
     // return Result<()> to catch errors from sender/receiver
     pub async fn execute(&self) -> Result<()> {
         match self {
-            Commands::Send {
-                file,
-                address,
-                port,
-                to,
-                recursive,
-                no_compress,
-            } => {
+            Commands::Send(args) => {
                 info!("Command: SEND initiated");
-                debug!(
-                    "Params: file={:?}, address={:?}, port={}, to={:?}, recursive={}, no_compress={}",
-                    file, address, port, to, recursive, no_compress
-                );
+                debug!("Params: {:?}", args);
+                if args.json {
+                    output::enable_json();
+                }
                 trace!("Delegating to sender::start_send()");
                 // send file or files
-                start_send(&file, &address, &port, &to, &recursive, &no_compress)
+                start_send(args)
                     .await
                     .context("Failed to execute Send command")?;
                 trace!("sender::start_send() completed successfully");
             }
-            Commands::Receive { port, dir } => {
+            Commands::Receive {
+                port,
+                dir,
+                save_text,
+                on_conflict,
+                json,
+                web,
+                tui,
+            } => {
                 info!("Command: RECEIVE initiated");
-                debug!("Params: port={:?}, dir={:?}", port, dir);
+                debug!(
+                    "Params: port={:?}, dir={:?}, save_text={}, on_conflict={:?}, json={}, \
+                     web={}, tui={}",
+                    port, dir, save_text, on_conflict, json, web, tui
+                );
+                if *json {
+                    output::enable_json();
+                }
+                if *web {
+                    trace!("Delegating to receiver::start_web_receiver()");
+                    start_web_receiver(*port, dir, on_conflict.as_deref())
+                        .await
+                        .context("Failed to execute Receive command")?;
+                    return Ok(());
+                }
                 trace!("Delegating to receiver::start_receiver()");
                 // Pass the error up if receiving fails
-                start_receiver(*port, &dir)
+                start_receiver(*port, dir, *save_text, on_conflict.as_deref(), *tui)
                     .await
                     .context("Failed to execute Receive command")?;
                 trace!("receiver::start_receiver() completed successfully");
             }
+            Commands::Serve(args) => {
+                info!("Command: SERVE initiated");
+                debug!("Params: {:?}", args);
+                trace!("Delegating to sender::start_serve()");
+                start_serve(args)
+                    .await
+                    .context("Failed to execute Serve command")?;
+                trace!("sender::start_serve() completed successfully");
+            }
+            Commands::Share(args) => {
+                info!("Command: SHARE initiated");
+                debug!("Params: {:?}", args);
+                trace!("Delegating to sender::start_share()");
+                start_share(args)
+                    .await
+                    .context("Failed to execute Share command")?;
+                trace!("sender::start_share() completed successfully");
+            }
+            Commands::Get(args) => {
+                info!("Command: GET initiated");
+                debug!("Params: {:?}", args);
+                trace!("Delegating to receiver::start_get()");
+                start_get(args)
+                    .await
+                    .context("Failed to execute Get command")?;
+                trace!("receiver::start_get() completed successfully");
+            }
+            Commands::Watch(args) => {
+                info!("Command: WATCH initiated");
+                debug!("Params: {:?}", args);
+                trace!("Delegating to sender::start_watch()");
+                start_watch(args)
+                    .await
+                    .context("Failed to execute Watch command")?;
+                trace!("sender::start_watch() completed successfully");
+            }
             Commands::Update => {
                 info!("Command: UPDATE initiated");
                 trace!("Delegating to update::update_portal()");
@@ -336,7 +559,7 @@ impl Commands {
                     ConfigAction::Set { key, value } => {
                         info!("Config: SET key='{}'", key);
                         trace!("Delegating to config::set::set_config");
-                        set_config(&key, &value)
+                        set_config(key, value)
                             .await
                             .context("Failed to set configuration")?;
                     }
@@ -344,7 +567,7 @@ impl Commands {
                         info!("Config: SHOW key='{}'", key);
                         trace!("Delegating to config::show::show_config_value");
                         // Logic to read and print the a varable value
-                        show_config_value(&key)
+                        show_config_value(key)
                             .await
                             .context("Failed to get variable value")?;
                     }
@@ -1,13 +1,20 @@
+pub mod hooks;
 pub mod network;
+pub mod route;
+pub mod security;
 pub mod storage;
 pub mod user;
 
 use {
     anyhow::{Context, Result, anyhow},
     home::home_dir,
+    hooks::HooksConfig,
     inquire::{CustomType, Text, validator::Validation},
     network::NetworkConfig,
+    pxp::{RetryPolicy, Timeouts},
     rand::random,
+    route::RouteRule,
+    security::SecurityConfig,
     serde::{Deserialize, Serialize},
     std::path::PathBuf,
     storage::StorageConfig,
@@ -21,6 +28,13 @@ pub struct PortalConfig {
     pub user: UserConfig,
     pub network: NetworkConfig,
     pub storage: StorageConfig,
+    #[serde(default)]
+    pub security: SecurityConfig,
+    #[serde(default)]
+    pub hooks: HooksConfig,
+    /// Routing rules for incoming items, written as `[[route]]` tables
+    #[serde(default, rename = "route", skip_serializing_if = "Vec::is_empty")]
+    pub routes: Vec<RouteRule>,
 }
 
 impl PortalConfig {
@@ -36,8 +50,11 @@ impl PortalConfig {
     pub fn new_empty_for_set(key: &str, value: &str) -> Result<Self> {
         let mut cfg = PortalConfig {
             user: UserConfig { username: None },
-            network: NetworkConfig { default_port: None },
-            storage: StorageConfig { download_dir: None },
+            network: NetworkConfig::default(),
+            storage: StorageConfig::default(),
+            security: SecurityConfig::default(),
+            hooks: HooksConfig::default(),
+            routes: Vec::new(),
         };
 
         // Only fill the key being set
@@ -105,10 +122,15 @@ impl PortalConfig {
             },
             network: NetworkConfig {
                 default_port: Some(port),
+                ..Default::default()
             },
             storage: StorageConfig {
                 download_dir: Some(PathBuf::from(dir_string)),
+                ..Default::default()
             },
+            security: SecurityConfig::default(),
+            hooks: HooksConfig::default(),
+            routes: Vec::new(),
         };
 
         debug!("Initial configuration built, attempting to save...");
@@ -119,7 +141,6 @@ impl PortalConfig {
     }
 
     /// Load from ~/.portal/config.toml or create default
-
     pub async fn load_or_return() -> Result<Option<Self>> {
         let dir = Self::get_dir().await?;
         let file_path = dir.join("config.toml");
@@ -169,6 +190,11 @@ impl PortalConfig {
             "user" => self.user.update(field, value),
             "network" => self.network.update(field, value),
             "storage" => self.storage.update(field, value),
+            "security" => self.security.update(field, value),
+            "hooks" => self.hooks.update(field, value),
+            "route" => Err(anyhow!(
+                "Routing rules can't be set here; add [[route]] tables to ~/.portal/config.toml"
+            )),
             _ => Err(anyhow!("Unknown section: '{}'", section)),
         }
     }
@@ -190,10 +216,28 @@ impl PortalConfig {
             "user" => self.user.get_value(field),
             "network" => self.network.get_value(field),
             "storage" => self.storage.get_value(field),
+            "security" => self.security.get_value(field),
+            "hooks" => self.hooks.get_value(field),
             _ => Err(anyhow!("Key '{}' not recognized", key)),
         }
     }
 
+    /// Session time limits from `[network]`, or pxp's defaults when there is no config yet.
+    pub async fn load_timeouts() -> Result<Timeouts> {
+        Ok(Self::load_or_return()
+            .await?
+            .map(|cfg| cfg.network.timeouts())
+            .unwrap_or_default())
+    }
+
+    /// Send retry policy from `[network]`, or pxp's defaults when there is no config yet.
+    pub async fn load_retry_policy() -> Result<RetryPolicy> {
+        match Self::load_or_return().await? {
+            Some(cfg) => cfg.network.retry_policy(),
+            None => Ok(RetryPolicy::default()),
+        }
+    }
+
     /// Save current config to disk
     pub async fn save(&self) -> Result<()> {
         let dir = Self::get_dir().await?;
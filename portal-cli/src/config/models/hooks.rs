@@ -0,0 +1,89 @@
+use {
+    super::network::parse_timeout_secs,
+    anyhow::{Result, anyhow},
+    serde::{Deserialize, Serialize},
+    std::time::Duration,
+    tracing::{debug, trace},
+};
+
+/// Seconds a hook may run before it is killed, when `timeout` isn't set.
+const DEFAULT_HOOK_TIMEOUT: u64 = 60;
+
+/// Shell commands run around transfers. Each gets `PORTAL_*` environment variables and a
+/// JSON description of the transfer on stdin.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct HooksConfig {
+    /// Runs before a send; a non-zero exit cancels it
+    pub pre_send: Option<String>,
+    /// Runs after a send, whether it worked or not
+    pub post_send: Option<String>,
+    /// Runs on received items before they leave staging; may reject some or all of them
+    pub pre_reconcile: Option<String>,
+    /// Runs after a receive, whether it worked or not
+    pub post_receive: Option<String>,
+    /// Seconds a hook may run before it is killed
+    pub timeout: Option<u64>,
+}
+
+impl HooksConfig {
+    pub fn update(&mut self, field: &str, value: &str) -> Result<String> {
+        trace!(
+            "HooksConfig: update field '{}' with value '{}'",
+            field, value
+        );
+        match field {
+            "pre_send" | "post_send" | "pre_reconcile" | "post_receive" => {
+                // An empty value removes the hook.
+                let command = (!value.trim().is_empty()).then(|| value.to_string());
+                match field {
+                    "pre_send" => self.pre_send = command,
+                    "post_send" => self.post_send = command,
+                    "pre_reconcile" => self.pre_reconcile = command,
+                    _ => self.post_receive = command,
+                }
+                debug!("{} hook updated in config: {:?}", field, value);
+                Ok(value.to_string())
+            }
+            "timeout" => {
+                let secs = parse_timeout_secs(value)?;
+                self.timeout = Some(secs);
+                debug!("Hook timeout updated in config: {}s", secs);
+                Ok(secs.to_string())
+            }
+            _ => Err(anyhow!("Unknown field '{}' in [hooks]", field)),
+        }
+    }
+
+    pub fn get_value(&self, field: &str) -> Result<String> {
+        trace!("HooksConfig: get_value for field '{}'", field);
+        match field {
+            "pre_send" | "post_send" | "pre_reconcile" | "post_receive" => {
+                let command = match field {
+                    "pre_send" => &self.pre_send,
+                    "post_send" => &self.post_send,
+                    "pre_reconcile" => &self.pre_reconcile,
+                    _ => &self.post_receive,
+                };
+                let command = command
+                    .clone()
+                    .ok_or_else(|| anyhow!("{} not set", field))?;
+                debug!("Retrieved {} hook from config: {}", field, command);
+                Ok(command)
+            }
+            "timeout" => {
+                let secs = self
+                    .timeout
+                    .map(|s| s.to_string())
+                    .ok_or_else(|| anyhow!("timeout not set"))?;
+                debug!("Retrieved hook timeout from config: {}", secs);
+                Ok(secs)
+            }
+            _ => Err(anyhow!("Unknown field '{}' in [hooks]", field)),
+        }
+    }
+
+    /// How long a hook may run before it is killed.
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout.unwrap_or(DEFAULT_HOOK_TIMEOUT))
+    }
+}
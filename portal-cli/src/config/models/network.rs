@@ -1,12 +1,28 @@
 use {
     anyhow::{Context, Result, anyhow},
+    pxp::{RetryOn, RetryPolicy, Timeouts},
     serde::{Deserialize, Serialize},
+    std::time::Duration,
     tracing::{debug, trace},
 };
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct NetworkConfig {
     pub default_port: Option<u16>,
+    /// Seconds to search for a receiver by username before giving up
+    pub discovery_timeout: Option<u64>,
+    /// Seconds to wait for the TCP connection to the receiver
+    pub connect_timeout: Option<u64>,
+    /// Seconds allowed for the identity proof and manifest exchange
+    pub handshake_timeout: Option<u64>,
+    /// Seconds the data stream may stall before the transfer is aborted
+    pub idle_timeout: Option<u64>,
+    /// Extra attempts after a failed send (0 disables retrying)
+    pub retries: Option<u32>,
+    /// Seconds to wait before the first retry; doubles on each further attempt
+    pub retry_backoff: Option<u64>,
+    /// Which failures are retried: "connect" or "all"
+    pub retry_on: Option<String>,
 }
 
 impl NetworkConfig {
@@ -24,6 +40,37 @@ impl NetworkConfig {
                 debug!("Default port updated in config: {}", port);
                 Ok(port.to_string())
             }
+            "discovery_timeout" | "connect_timeout" | "handshake_timeout" | "idle_timeout" => {
+                let secs = parse_timeout_secs(value)?;
+                match field {
+                    "discovery_timeout" => self.discovery_timeout = Some(secs),
+                    "connect_timeout" => self.connect_timeout = Some(secs),
+                    "handshake_timeout" => self.handshake_timeout = Some(secs),
+                    _ => self.idle_timeout = Some(secs),
+                }
+                debug!("{} updated in config: {}s", field, secs);
+                Ok(secs.to_string())
+            }
+            "retries" => {
+                let retries = value
+                    .parse::<u32>()
+                    .context("Invalid retries: must be a whole number")?;
+                self.retries = Some(retries);
+                debug!("retries updated in config: {}", retries);
+                Ok(retries.to_string())
+            }
+            "retry_backoff" => {
+                let secs = parse_timeout_secs(value)?;
+                self.retry_backoff = Some(secs);
+                debug!("retry_backoff updated in config: {}s", secs);
+                Ok(secs.to_string())
+            }
+            "retry_on" => {
+                parse_retry_on(value)?;
+                self.retry_on = Some(value.to_string());
+                debug!("retry_on updated in config: {}", value);
+                Ok(value.to_string())
+            }
             _ => Err(anyhow!("Unknown field in [network]: {}", field)),
         }
     }
@@ -38,7 +85,100 @@ impl NetworkConfig {
                 debug!("Retrieved default_port from config: {}", p);
                 Ok(p)
             }
+            "discovery_timeout" | "connect_timeout" | "handshake_timeout" | "idle_timeout" => {
+                let value = match field {
+                    "discovery_timeout" => self.discovery_timeout,
+                    "connect_timeout" => self.connect_timeout,
+                    "handshake_timeout" => self.handshake_timeout,
+                    _ => self.idle_timeout,
+                };
+                let secs = value
+                    .map(|s| s.to_string())
+                    .ok_or_else(|| anyhow!("{} not set", field))?;
+                debug!("Retrieved {} from config: {}", field, secs);
+                Ok(secs)
+            }
+            "retries" | "retry_backoff" => {
+                let value = match field {
+                    "retries" => self.retries.map(u64::from),
+                    _ => self.retry_backoff,
+                };
+                let value = value
+                    .map(|v| v.to_string())
+                    .ok_or_else(|| anyhow!("{} not set", field))?;
+                debug!("Retrieved {} from config: {}", field, value);
+                Ok(value)
+            }
+            "retry_on" => {
+                let value = self
+                    .retry_on
+                    .clone()
+                    .ok_or_else(|| anyhow!("retry_on not set"))?;
+                debug!("Retrieved retry_on from config: {}", value);
+                Ok(value)
+            }
             _ => Err(anyhow!("Unknown field '{}' in [network]", field)),
         }
     }
+
+    /// Session time limits, with pxp's defaults filling in anything not configured.
+    pub fn timeouts(&self) -> Timeouts {
+        let defaults = Timeouts::default();
+        Timeouts {
+            discovery: self
+                .discovery_timeout
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.discovery),
+            connect: self
+                .connect_timeout
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.connect),
+            handshake: self
+                .handshake_timeout
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.handshake),
+            idle: self
+                .idle_timeout
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.idle),
+        }
+    }
+
+    /// Retry behaviour for sends, with pxp's defaults filling in anything not configured.
+    pub fn retry_policy(&self) -> Result<RetryPolicy> {
+        let defaults = RetryPolicy::default();
+        Ok(RetryPolicy {
+            retries: self.retries.unwrap_or(defaults.retries),
+            initial_backoff: self
+                .retry_backoff
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.initial_backoff),
+            retry_on: match self.retry_on.as_deref() {
+                Some(value) => parse_retry_on(value)?,
+                None => defaults.retry_on,
+            },
+            ..defaults
+        })
+    }
+}
+
+pub fn parse_retry_on(value: &str) -> Result<RetryOn> {
+    match value {
+        "connect" => Ok(RetryOn::Connect),
+        "all" => Ok(RetryOn::All),
+        _ => Err(anyhow!(
+            "Invalid retry_on '{}': expected 'connect' or 'all'",
+            value
+        )),
+    }
+}
+
+pub(crate) fn parse_timeout_secs(value: &str) -> Result<u64> {
+    let secs = value
+        .parse::<u64>()
+        .context("Invalid timeout: must be a whole number of seconds")?;
+    if secs == 0 {
+        return Err(anyhow!("Invalid timeout: must be at least 1 second"));
+    }
+    Ok(secs)
 }
@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+/// One `[[route]]` rule. An incoming top-level item that matches every condition that is
+/// set is saved in `dir` instead of the download dir. Rules are tried in order and the
+/// first match wins; a rule with no conditions matches everything.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RouteRule {
+    /// Where matching items go. May use {download_dir}, {sender}, {date} (YYYY-MM-DD) and
+    /// {ext}; a leading `~` is the home dir, and relative paths are inside the download dir.
+    pub dir: String,
+    /// Sender usernames; `alice` also matches `alice@portal`
+    pub from: Option<Vec<String>>,
+    /// File extensions, e.g. ["pdf"]. Never matches a folder.
+    pub extensions: Option<Vec<String>>,
+    /// Smallest matching item (a folder's total), e.g. "10MB"
+    pub min_size: Option<String>,
+    /// Largest matching item (a folder's total), e.g. "1GB"
+    pub max_size: Option<String>,
+    /// Matches when the transfer description contains any of these words (any case)
+    pub keywords: Option<Vec<String>>,
+}
@@ -0,0 +1,195 @@
+use {
+    anyhow::{Context, Result, anyhow},
+    pxp::receiver::{AccessRule, ReceivePolicy, SenderFilter},
+    serde::{Deserialize, Serialize},
+    tracing::{debug, trace},
+};
+
+/// Limits on incoming transfers and on who may send them. Unset fields mean "no limit".
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct SecurityConfig {
+    /// Largest transfer accepted, in bytes
+    pub max_total_bytes: Option<u64>,
+    /// Most files (including those inside folders) a transfer may contain
+    pub max_files: Option<u64>,
+    /// Deepest folder nesting accepted; a top-level item is depth 1
+    pub max_depth: Option<usize>,
+    /// Only accept files with these extensions, e.g. ["jpg", "pdf"]
+    pub allowed_extensions: Option<Vec<String>>,
+    /// Never accept files with these extensions, e.g. ["exe", "bat"]
+    pub denied_extensions: Option<Vec<String>>,
+    /// Only accept these MIME types, e.g. ["image/*", "application/pdf"]. Types come from
+    /// the extension and, where the content has a known signature, from the first bytes
+    pub allowed_mime_types: Option<Vec<String>>,
+    /// Never accept these MIME types
+    pub denied_mime_types: Option<Vec<String>>,
+    /// Only accept transfers from these IPs, CIDR ranges or usernames
+    pub allow: Option<Vec<String>>,
+    /// Never accept transfers from these IPs, CIDR ranges or usernames
+    pub deny: Option<Vec<String>>,
+}
+
+impl SecurityConfig {
+    pub fn update(&mut self, field: &str, value: &str) -> Result<String> {
+        trace!(
+            "SecurityConfig: update field '{}' with value '{}'",
+            field, value
+        );
+        match field {
+            "max_total_bytes" => {
+                let bytes = parse_size(value)?;
+                self.max_total_bytes = Some(bytes);
+                debug!("max_total_bytes updated in config: {}", bytes);
+                Ok(bytes.to_string())
+            }
+            "max_files" => {
+                let files = value
+                    .parse::<u64>()
+                    .context("Invalid max_files: must be a whole number")?;
+                self.max_files = Some(files);
+                debug!("max_files updated in config: {}", files);
+                Ok(files.to_string())
+            }
+            "max_depth" => {
+                let depth = value
+                    .parse::<usize>()
+                    .context("Invalid max_depth: must be a whole number")?;
+                if depth == 0 {
+                    return Err(anyhow!("Invalid max_depth: must be at least 1"));
+                }
+                self.max_depth = Some(depth);
+                debug!("max_depth updated in config: {}", depth);
+                Ok(depth.to_string())
+            }
+            "allowed_extensions" | "denied_extensions" | "allowed_mime_types"
+            | "denied_mime_types" => {
+                let list = parse_list(value);
+                let shown = list.join(", ");
+                let list = (!list.is_empty()).then_some(list);
+                match field {
+                    "allowed_extensions" => self.allowed_extensions = list,
+                    "denied_extensions" => self.denied_extensions = list,
+                    "allowed_mime_types" => self.allowed_mime_types = list,
+                    _ => self.denied_mime_types = list,
+                }
+                debug!("{} updated in config: [{}]", field, shown);
+                Ok(shown)
+            }
+            "allow" | "deny" => {
+                let list = parse_list(value);
+                parse_rules(&list)?;
+                let shown = list.join(", ");
+                let list = (!list.is_empty()).then_some(list);
+                if field == "allow" {
+                    self.allow = list;
+                } else {
+                    self.deny = list;
+                }
+                debug!("{} updated in config: [{}]", field, shown);
+                Ok(shown)
+            }
+            _ => Err(anyhow!("Unknown field '{}' in [security]", field)),
+        }
+    }
+
+    pub fn get_value(&self, field: &str) -> Result<String> {
+        trace!("SecurityConfig: get_value for field '{}'", field);
+        match field {
+            "max_total_bytes" | "max_files" => {
+                let value = match field {
+                    "max_total_bytes" => self.max_total_bytes,
+                    _ => self.max_files,
+                };
+                let value = value
+                    .map(|v| v.to_string())
+                    .ok_or_else(|| anyhow!("{} not set", field))?;
+                debug!("Retrieved {} from config: {}", field, value);
+                Ok(value)
+            }
+            "max_depth" => {
+                let depth = self
+                    .max_depth
+                    .map(|d| d.to_string())
+                    .ok_or_else(|| anyhow!("max_depth not set"))?;
+                debug!("Retrieved max_depth from config: {}", depth);
+                Ok(depth)
+            }
+            "allowed_extensions" | "denied_extensions" | "allowed_mime_types"
+            | "denied_mime_types" | "allow" | "deny" => {
+                let list = match field {
+                    "allowed_extensions" => &self.allowed_extensions,
+                    "denied_extensions" => &self.denied_extensions,
+                    "allowed_mime_types" => &self.allowed_mime_types,
+                    "denied_mime_types" => &self.denied_mime_types,
+                    "allow" => &self.allow,
+                    _ => &self.deny,
+                };
+                let value = list
+                    .as_ref()
+                    .map(|l| l.join(", "))
+                    .ok_or_else(|| anyhow!("{} not set", field))?;
+                debug!("Retrieved {} from config: {}", field, value);
+                Ok(value)
+            }
+            _ => Err(anyhow!("Unknown field '{}' in [security]", field)),
+        }
+    }
+
+    /// The receive policy pxp enforces for these settings.
+    pub fn policy(&self) -> ReceivePolicy {
+        ReceivePolicy {
+            max_total_bytes: self.max_total_bytes,
+            max_files: self.max_files,
+            max_depth: self.max_depth,
+            allowed_extensions: self.allowed_extensions.clone().unwrap_or_default(),
+            denied_extensions: self.denied_extensions.clone().unwrap_or_default(),
+            allowed_mime_types: self.allowed_mime_types.clone().unwrap_or_default(),
+            denied_mime_types: self.denied_mime_types.clone().unwrap_or_default(),
+        }
+    }
+
+    /// Which senders may start a transfer, from the `allow` and `deny` lists.
+    pub fn sender_filter(&self) -> Result<SenderFilter> {
+        Ok(SenderFilter {
+            allow: parse_rules(self.allow.as_deref().unwrap_or_default())?,
+            deny: parse_rules(self.deny.as_deref().unwrap_or_default())?,
+        })
+    }
+}
+
+fn parse_rules(values: &[String]) -> Result<Vec<AccessRule>> {
+    values
+        .iter()
+        .map(|v| v.parse::<AccessRule>().map_err(anyhow::Error::from))
+        .collect()
+}
+
+/// Parses a byte count with an optional KB/MB/GB/TB suffix (powers of 1024), e.g. "500MB".
+pub(crate) fn parse_size(value: &str) -> Result<u64> {
+    let upper = value.trim().to_uppercase();
+    let (number, multiplier) = [
+        ("TB", 1u64 << 40),
+        ("GB", 1 << 30),
+        ("MB", 1 << 20),
+        ("KB", 1 << 10),
+        ("B", 1),
+    ]
+    .iter()
+    .find_map(|(suffix, mult)| upper.strip_suffix(suffix).map(|n| (n.trim(), *mult)))
+    .unwrap_or((upper.as_str(), 1));
+    let number = number
+        .parse::<u64>()
+        .context("Invalid size: use a whole number, optionally with KB, MB, GB or TB")?;
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| anyhow!("Invalid size: '{}' is too large", value))
+}
+
+/// Splits a comma-separated list, dropping empty entries. An empty value clears the list.
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
@@ -5,9 +5,16 @@ use {
     tracing::{debug, trace},
 };
 
-#[derive(Serialize, Deserialize, Debug)]
+/// What to do with a name that already exists when Portal can't ask.
+pub const CONFLICT_CHOICES: [&str; 4] = ["overwrite", "rename", "skip", "merge"];
+
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct StorageConfig {
     pub download_dir: Option<PathBuf>,
+    /// How received items that already exist are handled when running non-interactively:
+    /// one of `CONFLICT_CHOICES` (default: rename)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_conflict: Option<String>,
 }
 
 impl StorageConfig {
@@ -23,6 +30,18 @@ impl StorageConfig {
                 debug!("Download directory updated in config: {:?}", path);
                 Ok(path.display().to_string())
             }
+            "on_conflict" => {
+                let choice = value.to_lowercase();
+                if !CONFLICT_CHOICES.contains(&choice.as_str()) {
+                    return Err(anyhow!(
+                        "Invalid on_conflict: use one of {}",
+                        CONFLICT_CHOICES.join(", ")
+                    ));
+                }
+                self.on_conflict = Some(choice.clone());
+                debug!("on_conflict updated in config: {}", choice);
+                Ok(choice)
+            }
             _ => Err(anyhow!("Unknown field '{}' in [storage]", field)),
         }
     }
@@ -38,6 +57,10 @@ impl StorageConfig {
                 debug!("Retrieved download_dir from config: {}", p);
                 Ok(p)
             }
+            "on_conflict" => self
+                .on_conflict
+                .clone()
+                .ok_or_else(|| anyhow!("on_conflict not set")),
             _ => Err(anyhow!("Unknown field '{}' in [storage]", field)),
         }
     }
@@ -1,11 +1,12 @@
 use {
-    crate::config::models::PortalConfig,
+    crate::{config::models::PortalConfig, interactive},
     anyhow::Result,
     inquire::Confirm,
     tracing::{debug, trace},
 };
 
 pub async fn handle_setup() -> Result<()> {
+    interactive::ensure("set each value with 'portal config set <KEY> <VALUE>' instead")?;
     trace!("Determining configuration directory for setup check");
     let path = PortalConfig::get_dir().await?.join("config.toml");
 
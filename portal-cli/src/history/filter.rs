@@ -30,8 +30,8 @@ pub fn filter_history(
     let mut filtered: Vec<TransferHistoryRecord> = records
         .into_iter()
         .filter(|r| {
-            let dir_ok = mode.as_ref().map_or(true, |d| d == &r.mode);
-            let since_ok = since_unix.map_or(true, |s| r.timestamp >= s);
+            let dir_ok = mode.as_ref().is_none_or(|d| d == &r.mode);
+            let since_ok = since_unix.is_none_or(|s| r.timestamp >= s);
             dir_ok && since_ok
         })
         .collect();
@@ -193,11 +193,9 @@ fn strip_ansi(text: &str) -> String {
             }
             continue;
         }
-        if ch == '\x1b' {
-            if let Some('[') = chars.peek() {
-                in_escape = true;
-                continue;
-            }
+        if ch == '\x1b' && chars.peek() == Some(&'[') {
+            in_escape = true;
+            continue;
         }
         out.push(ch);
     }
@@ -255,6 +253,31 @@ pub fn format_history_detail(
     if let Some(err) = record.error.as_deref() {
         lines.push(format!("Error: {}", err));
     }
+    if let Some(attempts) = record.attempts.as_ref() {
+        lines.push(String::new());
+        lines.push(format!("Attempts: {}", attempts.len()));
+        for attempt in attempts {
+            lines.push(format!(
+                "- #{} {} ({}): {}",
+                attempt.attempt,
+                attempt.peer_addr.as_deref().unwrap_or("unknown"),
+                format_duration(attempt.duration_ms),
+                attempt.error.as_deref().unwrap_or("succeeded")
+            ));
+        }
+    }
+    if let Some(hooks) = record.hooks.as_ref() {
+        lines.push(String::new());
+        lines.push("Hooks:".to_string());
+        for hook in hooks {
+            lines.push(format!(
+                "- {} ({}): {}",
+                hook.hook,
+                format_duration(hook.duration_ms),
+                hook.error.as_deref().unwrap_or("succeeded")
+            ));
+        }
+    }
 
     // Item lists (capped unless --items-all)
     let cap = 5usize;
@@ -280,8 +303,12 @@ pub fn format_history_detail(
                 match item.kind {
                     HistoryItemKind::File => "File",
                     HistoryItemKind::Directory => "Directory",
+                    HistoryItemKind::Text => "Text",
                 }
             ));
+            if let Some(path) = &item.path {
+                lines.push(format!("  saved to {}", path));
+            }
         }
         if !items_all && items.len() > cap {
             lines.push(format!("(+{} more)", items.len() - cap));
@@ -304,6 +331,7 @@ pub fn format_history_detail(
                 match item.kind {
                     HistoryItemKind::File => "File",
                     HistoryItemKind::Directory => "Directory",
+                    HistoryItemKind::Text => "Text",
                 }
             ));
         }
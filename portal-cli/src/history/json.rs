@@ -16,6 +16,22 @@ pub struct HistoryJsonItem {
     pub name: String,
     pub size: String,
     pub kind: String,
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistoryJsonAttempt {
+    pub attempt: u32,
+    pub peer_addr: Option<String>,
+    pub duration: String,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistoryJsonHook {
+    pub hook: String,
+    pub duration: String,
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -46,6 +62,8 @@ pub struct HistoryJsonDetail {
     pub receiver_path: Option<String>,
     pub transfer_description: String,
     pub error: Option<String>,
+    pub attempts: Vec<HistoryJsonAttempt>,
+    pub hooks: Vec<HistoryJsonHook>,
     pub items_actual: Vec<HistoryJsonItem>,
     pub items_intended: Vec<HistoryJsonItem>,
 }
@@ -117,6 +135,35 @@ fn build_history_json_detail(record: &TransferHistoryRecord, id: usize) -> Histo
         .as_ref()
         .map(|items| items.iter().map(to_json_item).collect())
         .unwrap_or_default();
+    let attempts = record
+        .attempts
+        .as_ref()
+        .map(|attempts| {
+            attempts
+                .iter()
+                .map(|a| HistoryJsonAttempt {
+                    attempt: a.attempt,
+                    peer_addr: a.peer_addr.clone(),
+                    duration: format_duration(a.duration_ms),
+                    error: a.error.clone(),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let hooks = record
+        .hooks
+        .as_ref()
+        .map(|hooks| {
+            hooks
+                .iter()
+                .map(|h| HistoryJsonHook {
+                    hook: h.hook.clone(),
+                    duration: format_duration(h.duration_ms),
+                    error: h.error.clone(),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
     HistoryJsonDetail {
         id,
         date,
@@ -135,6 +182,8 @@ fn build_history_json_detail(record: &TransferHistoryRecord, id: usize) -> Histo
             .clone()
             .unwrap_or_else(|| "none".to_string()),
         error: record.error.clone(),
+        attempts,
+        hooks,
         items_actual,
         items_intended,
     }
@@ -147,6 +196,8 @@ fn to_json_item(item: &HistoryItem) -> HistoryJsonItem {
         kind: match item.kind {
             HistoryItemKind::File => "File".to_string(),
             HistoryItemKind::Directory => "Directory".to_string(),
+            HistoryItemKind::Text => "Text".to_string(),
         },
+        path: item.path.clone(),
     }
 }
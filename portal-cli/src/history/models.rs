@@ -1,6 +1,7 @@
 use {
-    chrono::Utc,
+    chrono::{DateTime, Utc},
     serde::{Deserialize, Serialize},
+    std::time::SystemTime,
 };
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
@@ -19,6 +20,7 @@ pub enum HistoryStatus {
 pub enum HistoryItemKind {
     File,
     Directory,
+    Text,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,6 +28,26 @@ pub struct HistoryItem {
     pub name: String,
     pub bytes: u64,
     pub kind: HistoryItemKind,
+    /// Where a received item was saved, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+/// One try at a send that was retried. Only kept when a transfer needed more than one attempt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryAttempt {
+    pub attempt: u32,
+    pub peer_addr: Option<String>,
+    pub duration_ms: u64,
+    pub error: Option<String>,
+}
+
+/// One hook command that ran for a transfer, and how it went.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryHook {
+    pub hook: String,
+    pub duration_ms: u64,
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,10 +67,18 @@ pub struct TransferHistoryRecord {
     pub actual_count: u32,
     pub actual_bytes: u64,
     pub actual_items: Option<Vec<HistoryItem>>,
+    #[serde(default)]
+    pub attempts: Option<Vec<HistoryAttempt>>,
+    #[serde(default)]
+    pub hooks: Option<Vec<HistoryHook>>,
 }
 
 impl TransferHistoryRecord {
     pub fn now_unix() -> u64 {
         Utc::now().timestamp().max(0) as u64
     }
+
+    pub fn unix_at(time: SystemTime) -> u64 {
+        DateTime::<Utc>::from(time).timestamp().max(0) as u64
+    }
 }
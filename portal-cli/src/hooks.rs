@@ -0,0 +1,365 @@
+use {
+    crate::{
+        config::models::hooks::HooksConfig,
+        history::{
+            HistoryHook, HistoryItem, HistoryItemKind, HistoryMode, HistoryStatus,
+            TransferHistoryRecord,
+        },
+    },
+    anyhow::{Context, Result, anyhow},
+    pxp::{receiver::StagedTransfer, sender::SendItem},
+    serde::Serialize,
+    std::{
+        process::Stdio,
+        time::{Duration, Instant},
+    },
+    tokio::{io::AsyncWriteExt, process::Command, time::timeout},
+    tracing::{debug, info, trace, warn},
+};
+
+/// The points in a transfer where a hook command can run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HookKind {
+    PreSend,
+    PostSend,
+    PreReconcile,
+    PostReceive,
+}
+
+impl HookKind {
+    /// The key under `[hooks]`, also passed to the command as `PORTAL_HOOK`.
+    pub fn name(self) -> &'static str {
+        match self {
+            HookKind::PreSend => "pre_send",
+            HookKind::PostSend => "post_send",
+            HookKind::PreReconcile => "pre_reconcile",
+            HookKind::PostReceive => "post_receive",
+        }
+    }
+}
+
+/// What a hook is told about the transfer. Sent as JSON on the command's stdin; the main
+/// fields are also set as `PORTAL_*` environment variables for simple scripts.
+#[derive(Debug, Serialize)]
+pub struct HookPayload {
+    pub hook: &'static str,
+    pub mode: &'static str,
+    /// "success" or "failed"; only set once the transfer is over
+    pub status: Option<&'static str>,
+    pub error: Option<String>,
+    /// Only set when the transfer has exactly one known peer username
+    pub peer_username: Option<String>,
+    /// Every username the transfer is with; empty in direct (address) mode
+    pub peers: Vec<String>,
+    pub peer_addr: Option<String>,
+    pub description: Option<String>,
+    pub target_dir: Option<String>,
+    pub total_bytes: u64,
+    pub items: Vec<HookItem>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HookItem {
+    pub name: String,
+    pub bytes: u64,
+    pub kind: &'static str,
+    /// Where the item is on disk: the source before a send, the staged copy for
+    /// pre_reconcile and the final location after a receive
+    pub path: Option<String>,
+}
+
+impl HookPayload {
+    /// A send about to start towards the `targets` usernames, or `address` in direct mode.
+    pub fn before_send(
+        targets: &[String],
+        address: Option<&str>,
+        description: Option<&str>,
+        items: &[SendItem],
+    ) -> Self {
+        Self {
+            hook: HookKind::PreSend.name(),
+            mode: "send",
+            status: None,
+            error: None,
+            peer_username: match targets {
+                [username] => Some(username.clone()),
+                _ => None,
+            },
+            peers: targets.to_vec(),
+            peer_addr: address.map(str::to_string),
+            description: description.map(str::to_string),
+            target_dir: None,
+            total_bytes: items.iter().map(SendItem::bytes).sum(),
+            items: items
+                .iter()
+                .map(|item| HookItem {
+                    name: item.name().to_string(),
+                    bytes: item.bytes(),
+                    kind: match item {
+                        SendItem::File { .. } => "file",
+                        SendItem::Directory(_) => "directory",
+                        SendItem::Text { .. } => "text",
+                    },
+                    path: match item {
+                        SendItem::File { path, .. } => Some(path.display().to_string()),
+                        SendItem::Directory(dir) => Some(dir.root.display().to_string()),
+                        SendItem::Text { .. } => None,
+                    },
+                })
+                .collect(),
+        }
+    }
+
+    /// Received files and folders still in staging, before anything is moved into place.
+    pub fn before_reconcile(
+        peer_username: Option<&str>,
+        peer_addr: Option<&str>,
+        description: Option<&str>,
+        staged: &StagedTransfer,
+    ) -> Self {
+        Self {
+            hook: HookKind::PreReconcile.name(),
+            mode: "receive",
+            status: None,
+            error: None,
+            peer_username: peer_username.map(str::to_string),
+            peers: peer_username.into_iter().map(str::to_string).collect(),
+            peer_addr: peer_addr.map(str::to_string),
+            description: description.map(str::to_string),
+            target_dir: Some(staged.target_dir.display().to_string()),
+            total_bytes: staged.items.iter().map(|item| item.bytes).sum(),
+            items: staged
+                .items
+                .iter()
+                .map(|item| HookItem {
+                    name: item.name.clone(),
+                    bytes: item.bytes,
+                    kind: if item.is_dir { "directory" } else { "file" },
+                    path: Some(item.staged_path.display().to_string()),
+                })
+                .collect(),
+        }
+    }
+
+    /// A finished transfer, as it is about to be written to history.
+    pub fn after(kind: HookKind, record: &TransferHistoryRecord) -> Self {
+        let items = record
+            .actual_items
+            .as_ref()
+            .filter(|items| !items.is_empty())
+            .or(record.intended_items.as_ref());
+        Self {
+            hook: kind.name(),
+            mode: match record.mode {
+                HistoryMode::Send => "send",
+                HistoryMode::Receive => "receive",
+            },
+            status: Some(match record.status {
+                HistoryStatus::Success => "success",
+                HistoryStatus::Failed => "failed",
+            }),
+            error: record.error.clone(),
+            peer_username: record.peer_username.clone(),
+            peers: record.peer_username.iter().cloned().collect(),
+            peer_addr: record.peer_addr.clone(),
+            description: record.description.clone(),
+            target_dir: record.receiver_path.clone(),
+            total_bytes: record.actual_bytes,
+            items: items
+                .map(|items| items.iter().map(HookItem::from_history).collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn env(&self) -> Vec<(&'static str, String)> {
+        let paths: Vec<&str> = self
+            .items
+            .iter()
+            .filter_map(|item| item.path.as_deref())
+            .collect();
+        let mut env = vec![
+            ("PORTAL_HOOK", self.hook.to_string()),
+            ("PORTAL_MODE", self.mode.to_string()),
+            ("PORTAL_ITEM_COUNT", self.items.len().to_string()),
+            ("PORTAL_TOTAL_BYTES", self.total_bytes.to_string()),
+            ("PORTAL_PATHS", paths.join("\n")),
+            ("PORTAL_PEERS", self.peers.join("\n")),
+        ];
+        let optional = [
+            ("PORTAL_STATUS", self.status.map(str::to_string)),
+            ("PORTAL_ERROR", self.error.clone()),
+            ("PORTAL_PEER", self.peer_username.clone()),
+            ("PORTAL_PEER_ADDR", self.peer_addr.clone()),
+            ("PORTAL_DESCRIPTION", self.description.clone()),
+            ("PORTAL_TARGET_DIR", self.target_dir.clone()),
+        ];
+        env.extend(
+            optional
+                .into_iter()
+                .filter_map(|(key, value)| value.map(|value| (key, value))),
+        );
+        env
+    }
+}
+
+impl HookItem {
+    fn from_history(item: &HistoryItem) -> Self {
+        Self {
+            name: item.name.clone(),
+            bytes: item.bytes,
+            kind: match item.kind {
+                HistoryItemKind::File => "file",
+                HistoryItemKind::Directory => "directory",
+                HistoryItemKind::Text => "text",
+            },
+            path: item.path.clone(),
+        }
+    }
+}
+
+/// How one hook command went.
+#[derive(Debug)]
+pub struct HookRun {
+    pub kind: HookKind,
+    pub duration: Duration,
+    /// What the command printed, used by pre_reconcile to name rejected items
+    pub stdout: String,
+    /// Why the hook failed: it couldn't start, timed out or exited non-zero
+    pub error: Option<String>,
+}
+
+impl HookRun {
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+
+    pub fn to_history(&self) -> HistoryHook {
+        HistoryHook {
+            hook: self.kind.name().to_string(),
+            duration_ms: self.duration.as_millis() as u64,
+            error: self.error.clone(),
+        }
+    }
+}
+
+/// The `[hooks]` commands configured for this device.
+#[derive(Debug, Default, Clone)]
+pub struct Hooks {
+    config: HooksConfig,
+}
+
+impl Hooks {
+    pub fn new(config: &HooksConfig) -> Self {
+        Self {
+            config: config.clone(),
+        }
+    }
+
+    fn command(&self, kind: HookKind) -> Option<&str> {
+        match kind {
+            HookKind::PreSend => self.config.pre_send.as_deref(),
+            HookKind::PostSend => self.config.post_send.as_deref(),
+            HookKind::PreReconcile => self.config.pre_reconcile.as_deref(),
+            HookKind::PostReceive => self.config.post_receive.as_deref(),
+        }
+    }
+
+    pub fn is_set(&self, kind: HookKind) -> bool {
+        self.command(kind).is_some()
+    }
+
+    /// Runs the hook `payload` is meant for, if one is configured. A failure is logged and
+    /// printed but never returned as an error; callers decide what it means.
+    pub async fn run(&self, kind: HookKind, payload: &HookPayload) -> Option<HookRun> {
+        let command = self.command(kind)?;
+        info!("Running {} hook: {}", kind.name(), command);
+        let start = Instant::now();
+        let result = execute(command, payload, self.config.timeout()).await;
+        let run = match result {
+            Ok(stdout) => {
+                debug!("{} hook finished in {:?}", kind.name(), start.elapsed());
+                HookRun {
+                    kind,
+                    duration: start.elapsed(),
+                    stdout,
+                    error: None,
+                }
+            }
+            Err(e) => {
+                warn!("{} hook failed: {:#}", kind.name(), e);
+                say!("Portal: The {} hook failed: {:#}", kind.name(), e);
+                HookRun {
+                    kind,
+                    duration: start.elapsed(),
+                    stdout: String::new(),
+                    error: Some(format!("{:#}", e)),
+                }
+            }
+        };
+        Some(run)
+    }
+
+    /// Runs the post_send or post_receive hook for a finished transfer and notes how it
+    /// went in the transfer's history record.
+    pub async fn after_transfer(&self, kind: HookKind, record: &mut TransferHistoryRecord) {
+        let payload = HookPayload::after(kind, record);
+        if let Some(run) = self.run(kind, &payload).await {
+            record.hooks.get_or_insert_with(Vec::new).push(run.to_history());
+        }
+    }
+}
+
+/// Runs `command` through the system shell with the payload on stdin, and returns its
+/// stdout. The command is killed if it outlives `limit`.
+async fn execute(command: &str, payload: &HookPayload, limit: Duration) -> Result<String> {
+    let json = serde_json::to_vec(payload).context("Failed to serialize hook payload")?;
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C");
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c");
+        cmd
+    };
+    cmd.arg(command)
+        .envs(payload.env())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let mut child = cmd.spawn().context("Failed to start the hook")?;
+
+    // Written from a task so a hook that never reads stdin can't block us; dropping the
+    // handle afterwards closes its stdin.
+    if let Some(mut stdin) = child.stdin.take() {
+        tokio::spawn(async move {
+            if let Err(e) = stdin.write_all(&json).await {
+                trace!("Hook did not read its payload: {}", e);
+            }
+        });
+    }
+
+    let output = match timeout(limit, child.wait_with_output()).await {
+        Ok(output) => output.context("Failed to run the hook")?,
+        Err(_) => return Err(anyhow!("Timed out after {}s", limit.as_secs())),
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    for line in stdout.lines() {
+        debug!("hook stdout: {}", line);
+    }
+    for line in stderr.lines() {
+        debug!("hook stderr: {}", line);
+    }
+    if !output.status.success() {
+        let detail = stderr
+            .lines()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+            .map(|line| format!(": {}", line.trim()))
+            .unwrap_or_default();
+        return Err(anyhow!("Hook exited with {}{}", output.status, detail));
+    }
+    Ok(stdout)
+}
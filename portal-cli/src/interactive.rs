@@ -0,0 +1,46 @@
+use {
+    anyhow::{Result, anyhow},
+    inquire::Confirm,
+    std::{
+        io::IsTerminal,
+        sync::atomic::{AtomicBool, Ordering},
+    },
+    tracing::debug,
+};
+
+static INTERACTIVE: AtomicBool = AtomicBool::new(true);
+
+/// Decides once, at startup, whether Portal may prompt: not with `--non-interactive`
+/// (`--yes`), and not when stdin isn't a terminal, e.g. under cron or in CI.
+pub fn init(non_interactive: bool) {
+    let interactive = !non_interactive && std::io::stdin().is_terminal();
+    INTERACTIVE.store(interactive, Ordering::Relaxed);
+    debug!("Interactive prompts enabled: {}", interactive);
+}
+
+pub fn is_interactive() -> bool {
+    INTERACTIVE.load(Ordering::Relaxed)
+}
+
+/// Fails unless Portal may prompt, for questions that have no sensible default. `hint`
+/// says how to answer up front instead.
+pub fn ensure(hint: &str) -> Result<()> {
+    if is_interactive() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "Portal can't ask for this when running non-interactively; {}",
+            hint
+        ))
+    }
+}
+
+/// Asks a yes/no question, or takes `default` as the answer without asking when running
+/// non-interactively.
+pub fn confirm(message: &str, default: bool) -> Result<bool> {
+    if !is_interactive() {
+        debug!("Answering {:?} with the default: {}", message, default);
+        return Ok(default);
+    }
+    Ok(Confirm::new(message).with_default(default).prompt()?)
+}
@@ -1,5 +1,5 @@
 use {
-    crate::config::models::PortalConfig,
+    crate::{config::models::PortalConfig, tui},
     chrono::Local,
     std::{env, fs::create_dir_all},
     tracing::{debug, trace},
@@ -42,7 +42,16 @@ pub async fn init(verbose: bool, quiet: bool) -> WorkerGuard {
     tracing_subscriber::registry()
         .with(
             fmt::layer()
-                .with_writer(std::io::stderr)
+                .with_writer(tui::stderr)
+                .without_time()
+                .with_target(false)
+                .with_filter(terminal_level),
+        )
+        // Takes over from the layer above while `--tui` is showing.
+        .with(
+            fmt::layer()
+                .with_writer(tui::pane)
+                .with_ansi(false)
                 .without_time()
                 .with_target(false)
                 .with_filter(terminal_level),
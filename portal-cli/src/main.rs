@@ -6,6 +6,8 @@ use {
 };
 
 // link files
+#[macro_use]
+mod output;
 mod commands;
 mod config {
     pub mod list;
@@ -18,9 +20,13 @@ mod receiver;
 mod select;
 mod sender;
 mod update;
+mod web;
 mod history;
+mod hooks;
+mod interactive;
 mod logger;
 mod progress;
+mod tui;
 
 // 1. Defining the Map (The Struct)
 #[derive(Parser)]
@@ -34,6 +40,10 @@ struct Cli {
     /// Suppress non-error log output
     #[arg(short, long, conflicts_with = "verbose")]
     quiet: bool,
+    /// Never prompt: take each question's default answer, or fail when it has none.
+    /// Implied when stdin isn't a terminal
+    #[arg(short = 'y', long, visible_alias = "yes", global = true)]
+    non_interactive: bool,
 
     #[command(subcommand)]
     command: Commands,
@@ -49,6 +59,8 @@ async fn main() {
     let _log_guard = logger::init(cli.verbose, cli.quiet).await;
     trace!("Logger guard initialized");
 
+    interactive::init(cli.non_interactive);
+
     info!("Initializing Portal v{}", env!("CARGO_PKG_VERSION"));
 
     trace!(
@@ -57,6 +69,7 @@ async fn main() {
     );
     if let Err(e) = cli.command.execute().await {
         error!("Portal Error: {:#}", e);
+        output::error(&e);
         // Exit with a non-zero code to tell the OS that the program failed
         exit(1);
     }
@@ -0,0 +1,429 @@
+use {
+    crate::history::{HistoryItemKind, HistoryMode, HistoryStatus, TransferHistoryRecord},
+    pxp::{
+        ConflictAction, EventSink, ItemKind, PxpError, TransferEvent, TransferWarning,
+        metadata::GlobalTransferManifest,
+    },
+    serde::Serialize,
+    std::{
+        io::Write,
+        sync::{
+            Mutex,
+            atomic::{AtomicBool, Ordering},
+        },
+        time::{Duration, Instant},
+    },
+    tracing::warn,
+};
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// How often a `progress` event is written for the item being transferred.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Switches send and receive to `--json` output: stdout carries only NDJSON events, and
+/// status lines move to stderr.
+pub fn enable_json() {
+    JSON.store(true, Ordering::Relaxed);
+}
+
+pub fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Prints a status line to stdout, or to stderr with `--json` so stdout stays parseable.
+/// With `--tui` the line goes to the log pane instead.
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if $crate::tui::is_active() {
+            $crate::tui::print(format!($($arg)*));
+        } else if $crate::output::is_json() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// One line of `--json` output. Events are only ever added, and each line carries its
+/// kind in `event`, so consumers should skip kinds they don't know.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum JsonEvent {
+    /// The receiver is waiting for a sender.
+    Listening {
+        username: String,
+        ip: Option<String>,
+        port: u16,
+    },
+    Attempt {
+        peer: String,
+        attempt: u32,
+        max_attempts: u32,
+    },
+    Discovering {
+        peer: String,
+    },
+    Discovered {
+        peer: String,
+        host: String,
+        port: u16,
+        mode: String,
+        device_name: Option<String>,
+        app_version: Option<String>,
+        free_space: Option<u64>,
+    },
+    Connecting {
+        peer: String,
+        host: String,
+        port: u16,
+    },
+    /// `verified` is only set by a sender that checked the receiver's identity.
+    Connected {
+        peer: String,
+        host: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        verified: Option<bool>,
+    },
+    AwaitingAcceptance {
+        peer: String,
+    },
+    Accepted {
+        peer: String,
+    },
+    Manifest {
+        files: u32,
+        directories: u32,
+        bytes: u64,
+        description: Option<String>,
+        sender: Option<String>,
+    },
+    ItemStarted {
+        index: usize,
+        total: usize,
+        name: String,
+        kind: &'static str,
+        bytes: u64,
+    },
+    /// `bytes` of the current item's `total` have been transferred.
+    Progress {
+        name: String,
+        bytes: u64,
+        total: u64,
+    },
+    ItemDone {
+        name: String,
+        kind: &'static str,
+        verified: bool,
+    },
+    EmptyFolder {
+        name: String,
+    },
+    Renamed {
+        original: String,
+        renamed: String,
+    },
+    Conflict {
+        name: String,
+        action: &'static str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        rename_to: Option<String>,
+    },
+    AttemptFailed {
+        peer: String,
+        attempt: u32,
+        max_attempts: u32,
+        error: String,
+        retry_in_ms: u64,
+    },
+    /// A received text item, when it isn't saved to a file.
+    Text {
+        name: String,
+        text: String,
+    },
+    /// How a transfer ended, one per history record (so one per receiver of a fan-out send).
+    Summary {
+        mode: &'static str,
+        status: &'static str,
+        peer_username: Option<String>,
+        peer_addr: Option<String>,
+        path: Option<String>,
+        description: Option<String>,
+        duration_ms: u64,
+        expected_items: u32,
+        expected_bytes: u64,
+        items: Vec<JsonItem>,
+        bytes: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        code: Option<&'static str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    /// The command failed. `code` is stable; `message` is meant for people.
+    Error {
+        code: &'static str,
+        message: String,
+    },
+}
+
+#[derive(Debug, Serialize)]
+pub struct JsonItem {
+    pub name: String,
+    pub kind: &'static str,
+    pub bytes: u64,
+    pub path: Option<String>,
+}
+
+impl JsonEvent {
+    /// Writes the event as one line on stdout.
+    pub fn print(&self) {
+        match serde_json::to_string(self) {
+            Ok(line) => {
+                let mut stdout = std::io::stdout().lock();
+                let _ = writeln!(stdout, "{}", line);
+                let _ = stdout.flush();
+            }
+            Err(e) => warn!("Failed to serialize JSON event: {}", e),
+        }
+    }
+
+    pub fn manifest(manifest: &GlobalTransferManifest) -> Self {
+        JsonEvent::Manifest {
+            files: manifest.total_files,
+            directories: manifest.total_directories,
+            bytes: manifest.total_bytes,
+            description: manifest.description.clone(),
+            sender: manifest.sender_username.clone(),
+        }
+    }
+}
+
+/// Prints `event` when running with `--json`.
+pub fn emit(event: JsonEvent) {
+    if is_json() {
+        event.print();
+    }
+}
+
+/// Prints the summary of a transfer that is about to be recorded in history. `code` is
+/// the error code of a failed transfer, when known.
+pub fn summary(record: &TransferHistoryRecord, code: Option<&'static str>) {
+    if !is_json() {
+        return;
+    }
+    let items = record
+        .actual_items
+        .iter()
+        .flatten()
+        .map(|item| JsonItem {
+            name: item.name.clone(),
+            kind: match item.kind {
+                HistoryItemKind::File => "file",
+                HistoryItemKind::Directory => "directory",
+                HistoryItemKind::Text => "text",
+            },
+            bytes: item.bytes,
+            path: item.path.clone(),
+        })
+        .collect();
+    let failed = matches!(record.status, HistoryStatus::Failed);
+    JsonEvent::Summary {
+        mode: match record.mode {
+            HistoryMode::Send => "send",
+            HistoryMode::Receive => "receive",
+        },
+        status: if failed { "failed" } else { "success" },
+        peer_username: record.peer_username.clone(),
+        peer_addr: record.peer_addr.clone(),
+        path: record.receiver_path.clone(),
+        description: record.description.clone(),
+        duration_ms: record.duration_ms,
+        expected_items: record.intended_count,
+        expected_bytes: record.intended_bytes,
+        items,
+        bytes: record.actual_bytes,
+        code: if failed { code.or(Some("other")) } else { None },
+        error: record.error.clone(),
+    }
+    .print();
+}
+
+/// Prints the `error` event a failed command ends with.
+pub fn error(error: &anyhow::Error) {
+    emit(JsonEvent::Error {
+        code: error_code(error),
+        message: format!("{:#}", error),
+    });
+}
+
+/// The stable code of the pxp error behind `error`, or `other` for failures outside pxp
+/// (bad arguments, missing config and the like).
+pub fn error_code(error: &anyhow::Error) -> &'static str {
+    error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<PxpError>())
+        .map_or("other", PxpError::code)
+}
+
+fn kind_name(kind: ItemKind) -> &'static str {
+    match kind {
+        ItemKind::File => "file",
+        ItemKind::Directory => "directory",
+        ItemKind::Text => "text",
+    }
+}
+
+fn action_name(action: &ConflictAction) -> &'static str {
+    match action {
+        ConflictAction::Overwrite => "overwrite",
+        ConflictAction::OverwriteAll => "overwrite_all",
+        ConflictAction::Rename => "rename",
+        ConflictAction::RenameAll => "rename_all",
+        ConflictAction::Skip => "skip",
+        ConflictAction::SkipAll => "skip_all",
+        ConflictAction::KeepNewer => "keep_newer",
+        ConflictAction::KeepLarger => "keep_larger",
+        ConflictAction::SkipIfIdentical => "skip_if_identical",
+        ConflictAction::RenameTo(_) => "rename_to",
+        ConflictAction::MergeDirectories => "merge_directories",
+        ConflictAction::ReplaceDirectory => "replace_directory",
+    }
+}
+
+/// Turns a session's events into `--json` lines, in place of the progress bars.
+#[derive(Default)]
+pub struct JsonEvents {
+    item: Mutex<Option<ItemProgress>>,
+}
+
+/// The item being transferred, for throttled `progress` events.
+struct ItemProgress {
+    name: String,
+    bytes: u64,
+    total: u64,
+    reported: Instant,
+}
+
+impl EventSink for JsonEvents {
+    fn emit(&self, event: TransferEvent) {
+        let event = match event {
+            TransferEvent::AttemptStarted {
+                peer,
+                attempt,
+                max_attempts,
+            } => JsonEvent::Attempt {
+                peer,
+                attempt,
+                max_attempts,
+            },
+            TransferEvent::Discovering { peer } => JsonEvent::Discovering { peer },
+            TransferEvent::Discovered { peer, receiver } => JsonEvent::Discovered {
+                peer,
+                host: receiver.ip,
+                port: receiver.beacon.port,
+                mode: receiver.mode.to_string(),
+                device_name: receiver.beacon.device_name,
+                app_version: receiver.beacon.app_version,
+                free_space: receiver.beacon.free_space,
+            },
+            TransferEvent::Connecting { peer, host, port } => {
+                JsonEvent::Connecting { peer, host, port }
+            }
+            TransferEvent::Connected {
+                peer,
+                host,
+                verified,
+            } => JsonEvent::Connected {
+                peer,
+                host,
+                verified: Some(verified),
+            },
+            TransferEvent::AwaitingAcceptance { peer } => JsonEvent::AwaitingAcceptance { peer },
+            TransferEvent::Accepted { peer } => JsonEvent::Accepted { peer },
+            TransferEvent::ItemStarted {
+                index,
+                total,
+                name,
+                kind,
+                bytes,
+            } => {
+                if let Ok(mut item) = self.item.lock() {
+                    *item = Some(ItemProgress {
+                        name: name.clone(),
+                        bytes: 0,
+                        total: bytes,
+                        reported: Instant::now(),
+                    });
+                }
+                JsonEvent::ItemStarted {
+                    index,
+                    total,
+                    name,
+                    kind: kind_name(kind),
+                    bytes,
+                }
+            }
+            TransferEvent::Progress { bytes } => {
+                let Ok(mut item) = self.item.lock() else {
+                    return;
+                };
+                let Some(item) = item.as_mut() else {
+                    return;
+                };
+                item.bytes += bytes;
+                if item.reported.elapsed() < PROGRESS_INTERVAL {
+                    return;
+                }
+                item.reported = Instant::now();
+                JsonEvent::Progress {
+                    name: item.name.clone(),
+                    bytes: item.bytes,
+                    total: item.total,
+                }
+            }
+            TransferEvent::ItemFinished {
+                name,
+                kind,
+                verified,
+            } => {
+                if let Ok(mut item) = self.item.lock() {
+                    *item = None;
+                }
+                JsonEvent::ItemDone {
+                    name,
+                    kind: kind_name(kind),
+                    verified,
+                }
+            }
+            TransferEvent::Warning(TransferWarning::EmptyDirectory { name }) => {
+                JsonEvent::EmptyFolder { name }
+            }
+            TransferEvent::Warning(TransferWarning::Renamed { original, renamed }) => {
+                JsonEvent::Renamed { original, renamed }
+            }
+            TransferEvent::Conflict { name, action } => JsonEvent::Conflict {
+                name,
+                action: action_name(&action),
+                rename_to: match action {
+                    ConflictAction::RenameTo(to) => Some(to),
+                    _ => None,
+                },
+            },
+            TransferEvent::AttemptFailed {
+                peer,
+                attempt,
+                max_attempts,
+                error,
+                retry_in,
+            } => JsonEvent::AttemptFailed {
+                peer,
+                attempt,
+                max_attempts,
+                error,
+                retry_in_ms: retry_in.as_millis() as u64,
+            },
+            _ => return,
+        };
+        event.print();
+    }
+}
@@ -1,7 +1,10 @@
+use crate::tui;
 use anyhow::Result;
-use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use pxp::{EventSink, ItemKind, TransferEvent, TransferWarning};
 use std::{
     io::{Read, Write},
+    sync::{Arc, Mutex},
     time::Duration,
 };
 use tracing::debug;
@@ -11,6 +14,10 @@ pub struct ProgressManager {
     mp: MultiProgress,
     top: ProgressBar,
     side: Side,
+    attempt: Option<(u32, u32)>,
+    peers: Option<usize>,
+    /// Bar of the item being transferred right now.
+    item_bar: Arc<Mutex<Option<ProgressBar>>>,
 }
 
 // Which side of the transfer this manager is used for.
@@ -21,12 +28,19 @@ pub enum Side {
 }
 
 impl Side {
-    fn verb(self) -> &'static str {
+    pub fn verb(self) -> &'static str {
         match self {
             Side::Sender => "Sending",
             Side::Receiver => "Receiving",
         }
     }
+
+    pub fn done(self) -> &'static str {
+        match self {
+            Side::Sender => "sent",
+            Side::Receiver => "received",
+        }
+    }
 }
 
 impl ProgressManager {
@@ -36,32 +50,66 @@ impl ProgressManager {
 
     pub fn new_with_side(side: Side) -> Self {
         debug!("Progress UI initialized: {:?}", side);
-        let mp = MultiProgress::with_draw_target(ProgressDrawTarget::stderr_with_hz(10));
+        // `--tui` draws its own progress from the same events.
+        let target = if tui::is_active() {
+            ProgressDrawTarget::hidden()
+        } else {
+            ProgressDrawTarget::stderr_with_hz(10)
+        };
+        let mp = MultiProgress::with_draw_target(target);
         let top = mp.add(ProgressBar::new(0));
         let style = ProgressStyle::with_template("{msg} [{bar:40.green/white}] {pos}/{len}")
             .unwrap_or_else(|_| ProgressStyle::default_bar())
             .progress_chars("━╾─");
         top.set_style(style);
         top.set_message(format!("Portal: {}", side.verb()));
-        Self { mp, top, side }
+        Self {
+            mp,
+            top,
+            side,
+            attempt: None,
+            peers: None,
+            item_bar: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Tags the header with the current attempt, for sends that are being retried.
+    pub fn with_attempt(mut self, attempt: u32, max_attempts: u32) -> Self {
+        if attempt > 1 {
+            self.attempt = Some((attempt, max_attempts));
+        }
+        self
+    }
+
+    /// Tags the header with the number of receivers a fan-out send is streaming to.
+    pub fn with_peers(mut self, peers: usize) -> Self {
+        if peers > 1 {
+            self.peers = Some(peers);
+        }
+        self
+    }
+
+    fn header(&self, current: usize, total: usize) -> String {
+        let mut base = format!("Portal: {} item {} of {}", self.side.verb(), current, total);
+        if let Some(peers) = self.peers {
+            base = format!("{} to {} receivers", base, peers);
+        }
+        match self.attempt {
+            Some((attempt, max)) => format!("{} (attempt {} of {})", base, attempt, max),
+            None => base,
+        }
     }
 
     pub fn set_total_items(&self, total: usize) {
         debug!("Progress UI total items set to {}", total);
         self.top.set_length(total as u64);
-        self.top
-            .set_message(format!("Portal: {} item 0 of {}", self.side.verb(), total));
+        self.top.set_message(self.header(0, total));
     }
 
     pub fn set_current_item(&self, current: usize, total: usize) {
         debug!("Progress UI current item: {} of {}", current, total);
         self.top.set_position(current as u64);
-        self.top.set_message(format!(
-            "Portal: {} item {} of {}",
-            self.side.verb(),
-            current,
-            total
-        ));
+        self.top.set_message(self.header(current, total));
     }
 
     pub fn create_file_bar(&self, filename: &str, total_bytes: u64) -> ProgressBar {
@@ -70,7 +118,8 @@ impl ProgressManager {
             filename, total_bytes
         );
         let total = if total_bytes == 0 { 1 } else { total_bytes };
-        let pb = ProgressBar::new(total);
+        // Drawn only through `mp` once added, so it is hidden along with it under `--tui`.
+        let pb = ProgressBar::with_draw_target(Some(total), ProgressDrawTarget::hidden());
         let sty = ProgressStyle::with_template(
             "{msg} {bar:40.cyan/blue} {percent:>3}% | {bytes_per_sec} | {eta}",
         )
@@ -84,6 +133,14 @@ impl ProgressManager {
         self.mp.add(pb)
     }
 
+    /// Makes `bar` the current item's bar and hands back the one it replaces.
+    fn replace_item_bar(&self, bar: Option<ProgressBar>) -> Option<ProgressBar> {
+        match self.item_bar.lock() {
+            Ok(mut current) => std::mem::replace(&mut *current, bar),
+            Err(_) => None,
+        }
+    }
+
     pub fn println<S: AsRef<str>>(&self, msg: S) {
         let _ = self.mp.println(msg);
     }
@@ -153,63 +210,145 @@ pub fn stream_download_with_spinner<R: Read, W: Write>(
     Ok(downloaded)
 }
 
-/// PXP Trait Implementations 
-//
-// We want to keep the core `pxp` engine completely free of terminal-specific code (no println, no indicatif).
-// To do that, the engine defines abstract traits `ItemProgress` and `TransferProgress`.
-// Here in the CLI, we implement those traits using our terminal progress bar manager (`indicatif`).
-// This acts as a bridge: `pxp` handles the raw data bytes, calls these hooks, and our adapters update the terminal screen!
-use pxp::{ItemProgress, TransferProgress};
-use tokio::io::{AsyncRead, AsyncWrite};
-
-/// An adapter that wraps a standard `indicatif` ProgressBar to implement the core's `ItemProgress` trait.
-pub struct IndicatifItemProgress {
+/// Spinner shown while directories are scanned before the manifest is built, so a send
+/// of a huge tree doesn't look frozen before the first byte goes out.
+#[derive(Clone)]
+pub struct ScanSpinner {
     pb: ProgressBar,
 }
 
-impl ItemProgress for IndicatifItemProgress {
-    // When the core starts reading/writing a file, it calls these wrapping functions.
-    // We use indicatif's built-in wrapper streams so that as the core reads/writes bytes,
-    // the progress bar updates automatically without any manual byte counting in the engine.
-    fn wrap_read(
-        &self,
-        reader: Box<dyn AsyncRead + Unpin + Send>,
-    ) -> Box<dyn AsyncRead + Unpin + Send> {
-        Box::new(self.pb.wrap_async_read(reader))
+impl ScanSpinner {
+    pub fn new() -> Self {
+        let pb = ProgressBar::with_draw_target(None, ProgressDrawTarget::stderr_with_hz(10));
+        let style = ProgressStyle::with_template("{spinner:.cyan} {msg}")
+            .unwrap_or_else(|_| ProgressStyle::default_spinner());
+        pb.set_style(style);
+        pb.enable_steady_tick(Duration::from_millis(120));
+        Self { pb }
     }
 
-    fn wrap_write(
-        &self,
-        writer: Box<dyn AsyncWrite + Unpin + Send>,
-    ) -> Box<dyn AsyncWrite + Unpin + Send> {
-        Box::new(self.pb.wrap_async_write(writer))
+    pub fn finish(&self) {
+        self.pb.finish_and_clear();
     }
+}
 
-    // Called when the transfer of a single item is finished. We clean up the bar from the terminal.
-    fn finish_and_clear(&self) {
-        self.pb.finish_and_clear();
+impl pxp::sender::ScanProgress for ScanSpinner {
+    fn scanned(&self, dirname: &str, files: u64, bytes: u64) {
+        self.pb.set_message(format!(
+            "Portal: Scanning '{}'... {} file(s), {}",
+            dirname,
+            files,
+            HumanBytes(bytes)
+        ));
+    }
+
+    fn finished(&self, dirname: &str, files: u64, bytes: u64) {
+        debug!(
+            "Scan of '{}' finished: {} file(s), {} bytes",
+            dirname, files, bytes
+        );
+        self.pb.println(format!(
+            "Portal: Scanned '{}': {} file(s), {}",
+            dirname,
+            files,
+            HumanBytes(bytes)
+        ));
     }
 }
 
-impl TransferProgress for ProgressManager {
-    // The core calls these to set overall transfer progress (e.g. "Sending file 2 of 5").
-    fn set_total_items(&self, total: usize) {
-        ProgressManager::set_total_items(self, total);
+/// Holds whichever progress UI is showing right now. A pxp session is handed its event
+/// sink once, up front, while bars only appear once data moves (and again on every retry),
+/// so the session gets a slot and item events go to what is in it.
+#[derive(Clone, Default)]
+pub struct ProgressSlot {
+    current: Arc<Mutex<Option<ProgressManager>>>,
+}
+
+impl ProgressSlot {
+    /// Shows `prog`, clearing whatever was shown before.
+    pub fn show(&self, prog: ProgressManager) {
+        let old = match self.current.lock() {
+            Ok(mut current) => current.replace(prog),
+            Err(_) => None,
+        };
+        if let Some(old) = old {
+            old.finish();
+        }
     }
 
-    fn set_current_item(&self, current: usize, total: usize) {
-        ProgressManager::set_current_item(self, current, total);
+    /// Clears the progress UI, if one is showing.
+    pub fn finish(&self) {
+        let current = match self.current.lock() {
+            Ok(mut current) => current.take(),
+            Err(_) => None,
+        };
+        if let Some(prog) = current {
+            prog.finish();
+        }
     }
+}
 
-    // When the core starts a new item, it requests an `ItemProgress` tracker from us.
-    // We create a fresh file progress bar and wrap it in our adapter.
-    fn create_item_progress(&self, name: &str, total_bytes: u64) -> Box<dyn ItemProgress> {
-        let pb = self.create_file_bar(name, total_bytes);
-        Box::new(IndicatifItemProgress { pb })
+impl EventSink for ProgressSlot {
+    fn emit(&self, event: TransferEvent) {
+        if let Ok(current) = self.current.lock()
+            && let Some(prog) = current.as_ref()
+        {
+            prog.emit(event);
+        }
     }
+}
 
-    // Lets the core print text status messages cleanly without breaking the active progress bar layouts.
-    fn println(&self, msg: &str) {
-        ProgressManager::println(self, msg);
+/// PXP event rendering
+//
+// The core `pxp` engine is completely free of terminal-specific code (no println, no
+// indicatif): it only reports typed `TransferEvent`s. Here in the CLI we turn those into
+// progress bars and status lines, so `pxp` handles the raw data bytes and our manager
+// updates the terminal screen.
+impl EventSink for ProgressManager {
+    fn emit(&self, event: TransferEvent) {
+        match event {
+            TransferEvent::ItemStarted {
+                index,
+                total,
+                name,
+                bytes,
+                ..
+            } => {
+                self.set_current_item(index, total);
+                let bar = self.create_file_bar(&name, bytes);
+                if let Some(old) = self.replace_item_bar(Some(bar)) {
+                    old.finish_and_clear();
+                }
+            }
+            TransferEvent::Progress { bytes } => {
+                if let Ok(current) = self.item_bar.lock()
+                    && let Some(bar) = current.as_ref()
+                {
+                    bar.inc(bytes);
+                }
+            }
+            TransferEvent::ItemFinished { name, kind, .. } => {
+                if let Some(bar) = self.replace_item_bar(None) {
+                    bar.finish_and_clear();
+                }
+                let done = self.side.done();
+                match kind {
+                    ItemKind::Text => self.println(format!("Portal: Text {} successfully!", done)),
+                    ItemKind::Directory => {
+                        self.println(format!("Portal: Directory '{}' {} successfully!", name, done))
+                    }
+                    _ => self.println(format!("Portal: File '{}' {} successfully!", name, done)),
+                }
+            }
+            TransferEvent::Warning(TransferWarning::EmptyDirectory { name }) => {
+                self.println(format!(
+                    "Portal: Note: Directory '{}' is empty. Sending structure only.",
+                    name
+                ));
+            }
+            // Renames are listed together once the transfer is over, and conflicts are
+            // prompted for by the CLI's own resolver.
+            other => debug!("Transfer event: {:?}", other),
+        }
     }
 }
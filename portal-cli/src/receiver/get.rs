@@ -0,0 +1,178 @@
+use {
+    super::{
+        CliConflictResolver, ReceiveJob, ReceiveSettings, get_dir::get_target_dir, route::Routes,
+    },
+    crate::{
+        commands::GetArgs, config::models::PortalConfig, hooks::Hooks, interactive,
+        progress::ProgressSlot,
+    },
+    anyhow::{Context, Result},
+    indicatif::HumanBytes,
+    inquire::{MultiSelect, Text},
+    pxp::{
+        ReceiveSession,
+        metadata::{PullRequest, ShareListing, TransferItem},
+    },
+    std::time::Duration,
+    tracing::{debug, info, trace, warn},
+};
+
+/// Finds a share, lists what it offers, and receives the items the user picks exactly as a
+/// pushed transfer would be received: same limits, staging, conflict handling and history.
+pub async fn start_get(args: &GetArgs) -> Result<()> {
+    info!("Portal: Initializing fetch...");
+    let mut job = ReceiveJob::new();
+
+    let result: Result<()> = async {
+        let full_cfg = PortalConfig::load_all()
+            .await
+            .context("Failed to load user config")?;
+        let mut timeouts = full_cfg.network.timeouts();
+        if let Some(secs) = args.discovery_timeout {
+            timeouts.discovery = Duration::from_secs(secs);
+        }
+        debug!("Session timeouts: {:?}", timeouts);
+        let policy = full_cfg.security.policy();
+        debug!("Receive policy: {:?}", policy);
+        let routes = Routes::new(&full_cfg.routes)?;
+        job.hooks = Hooks::new(&full_cfg.hooks);
+
+        let target_dir = get_target_dir(&args.dir).await?;
+        info!("Target directory for saving: {:?}", target_dir);
+
+        // --- Locate the share ---
+        let (host_ip, node_id, host_port) = match &args.address {
+            Some(address) => {
+                info!("Using manual IP address override: {}", address);
+                (address.clone(), None, args.port)
+            }
+            None => {
+                let query = match &args.share {
+                    Some(share) => share.clone(),
+                    None => {
+                        interactive::ensure("pass the share name or code, or --address <IP>")?;
+                        Text::new("Portal: Enter the share name or code:")
+                            .prompt()
+                            .context("Failed to get share name")?
+                    }
+                };
+                println!("Portal: Searching for share...: {}", query);
+                let found =
+                    pxp::receiver::discover_share(&query, args.port, timeouts.discovery).await?;
+                println!(
+                    "Portal: Share found via {} discovery at {}",
+                    found.mode, found.ip
+                );
+                (found.ip, Some(found.beacon.node_id), found.beacon.port)
+            }
+        };
+        job.peer_addr = Some(host_ip.clone());
+
+        println!("Portal: Connecting to {}:{}...", host_ip, host_port);
+        let (mut stream, listing) = pxp::receiver::connect_to_share(
+            &host_ip,
+            host_port,
+            node_id.as_deref(),
+            &timeouts,
+        )
+        .await?;
+        job.peer_username = listing.host_username.clone();
+        if node_id.is_none() {
+            println!(
+                "Portal: Connected to {} (Manual mode: Identity check skipped).",
+                host_ip
+            );
+        }
+        println!(
+            "Portal: '{}' offers {} item(s)",
+            listing.share_name,
+            listing.items.len()
+        );
+
+        // --- Choose ---
+        let Some(chosen) = choose_items(&listing, args.all)? else {
+            println!("Portal: Nothing selected; fetch cancelled.");
+            return Ok(());
+        };
+        let request = PullRequest {
+            username: full_cfg.user.username.clone(),
+            items: chosen,
+            compressed: !args.no_compress,
+        };
+        let manifest = pxp::receiver::request_items(&mut stream, &request, &timeouts).await?;
+
+        let progress = ProgressSlot::default();
+        let session = ReceiveSession::builder()
+            .target_dir(&target_dir)
+            .policy(policy)
+            .timeouts(timeouts)
+            .resolver(CliConflictResolver::new(
+                args.on_conflict.as_deref(),
+                full_cfg.storage.on_conflict.as_deref(),
+            )?)
+            .progress(progress.clone())
+            .build()?;
+        let incoming = session.incoming(
+            stream,
+            manifest,
+            job.peer_addr.clone(),
+            job.peer_username.clone(),
+        );
+
+        let settings = ReceiveSettings {
+            target_dir,
+            routes,
+            save_text: false,
+            progress,
+        };
+        job.receive(incoming, &settings).await
+    }
+    .await;
+
+    if let Err(ref e) = result {
+        job.record_failure(e).await;
+    }
+
+    result
+}
+
+/// Indices of the items to fetch: everything with `--all` or when only one item is offered,
+/// otherwise whatever the user ticks. `None` when nothing was picked.
+fn choose_items(listing: &ShareListing, all: bool) -> Result<Option<Vec<u32>>> {
+    let everything: Vec<u32> = (0..listing.items.len() as u32).collect();
+    if all || listing.items.len() == 1 {
+        for item in &listing.items {
+            println!("Portal:   {}", describe_item(item));
+        }
+        return Ok((!everything.is_empty()).then_some(everything));
+    }
+
+    interactive::ensure("pass --all to fetch everything")?;
+    let options: Vec<String> = listing.items.iter().map(describe_item).collect();
+    match MultiSelect::new(
+        "Select items to fetch (Space to toggle, Enter to confirm):",
+        options,
+    )
+    .raw_prompt()
+    {
+        Ok(choices) => {
+            trace!("User picked items {:?}", choices);
+            let picked: Vec<u32> = choices.iter().map(|c| c.index as u32).collect();
+            Ok((!picked.is_empty()).then_some(picked))
+        }
+        Err(e) => {
+            warn!("Item selection cancelled: {}", e);
+            Ok(None)
+        }
+    }
+}
+
+fn describe_item(item: &TransferItem) -> String {
+    match item {
+        TransferItem::File(meta) => format!("{} ({})", meta.filename, HumanBytes(meta.file_size)),
+        TransferItem::Directory(meta) => {
+            format!("{}/ ({})", meta.dirname, HumanBytes(meta.total_size))
+        }
+        TransferItem::Text(meta) => format!("text ({})", HumanBytes(meta.byte_len)),
+    }
+}
@@ -1,5 +1,5 @@
 use {
-    crate::config::models::PortalConfig,
+    crate::{config::models::PortalConfig, interactive},
     anyhow::{Context, Result, anyhow},
     home::home_dir,
     inquire::Text,
@@ -16,13 +16,16 @@ pub async fn get_target_dir(dir: &Option<PathBuf>) -> Result<PathBuf> {
     } else if let Some(cfg) = PortalConfig::load_or_return().await? {
         if let Some(d) = &cfg.storage.download_dir {
             trace!("Target directory source: Configuration file");
-            println!("Portal: Using directory from config: {}", d.display());
+            say!("Portal: Using directory from config: {}", d.display());
             d.clone()
         } else {
             trace!(
                 "Target directory source: Config exists but download_dir is empty. Prompting user..."
             );
-            println!("Portal: Config exists but download directory not set.");
+            say!("Portal: Config exists but download directory not set.");
+            interactive::ensure(
+                "pass --dir <PATH> or run 'portal config set storage.download_dir <PATH>'",
+            )?;
             let default_path = home_dir()
                 .ok_or_else(|| anyhow!("Could not find home directory"))?
                 .join("Downloads")
@@ -39,6 +42,7 @@ pub async fn get_target_dir(dir: &Option<PathBuf>) -> Result<PathBuf> {
         }
     } else {
         trace!("Target directory source: No config found. Prompting user...");
+        interactive::ensure("pass --dir <PATH> or run 'portal config setup' first")?;
         let default_path = home_dir()
             .ok_or_else(|| anyhow!("Could not find home directory"))?
             .join("Downloads")
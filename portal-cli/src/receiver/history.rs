@@ -1,5 +1,6 @@
 use crate::history::{HistoryItem, HistoryMode, HistoryStatus, TransferHistoryRecord};
 
+#[allow(clippy::too_many_arguments)]
 pub fn build_receive_history_record(
     timestamp: u64,
     duration_ms: u64,
@@ -30,5 +31,7 @@ pub fn build_receive_history_record(
         actual_count,
         actual_bytes,
         actual_items,
+        attempts: None,
+        hooks: None,
     }
 }
@@ -1,23 +1,47 @@
+mod get;
 mod get_dir;
 mod history;
+mod route;
+mod web;
+
+pub use get::start_get;
+pub use web::start_web_receiver;
 
 use {
     crate::{
-        config::models::PortalConfig,
+        config::models::{PortalConfig, storage::CONFLICT_CHOICES},
         history::{
-            HistoryItem, HistoryItemKind, HistoryStatus, TransferHistoryRecord, append_record,
+            HistoryHook, HistoryItem, HistoryItemKind, HistoryStatus, TransferHistoryRecord,
+            append_record,
         },
-        progress::{ProgressManager, Side},
+        hooks::{HookKind, HookPayload, Hooks},
+        interactive,
+        output::{self, JsonEvent, JsonEvents},
+        progress::{ProgressManager, ProgressSlot, Side},
+        tui,
     },
     anyhow::{Context, Result, anyhow},
+    chrono::{DateTime, Local},
     get_dir::get_target_dir,
     history::build_receive_history_record,
-    inquire::Select,
+    route::Routes,
+    indicatif::HumanBytes,
+    inquire::{Select, Text},
+    gethostname::gethostname,
     pxp::{
-        ConflictAction, ConflictResolver,
-        metadata::ReceiveSummary,
+        BoxFuture, ConflictAction, ConflictInfo, ConflictResolver, ConflictSide, EventSink,
+        ItemRouter, PxpError, ReceiveSession,
+        discovery::DeviceInfo,
+        metadata::{ReceiveSummary, RejectReason},
+        receiver::{PlacedItem, StagedTransfer},
+        session::{IncomingTransfer, ReceivedTransfer},
+    },
+    std::{
+        collections::HashSet,
+        path::{Path, PathBuf},
+        sync::Arc,
+        time::Instant,
     },
-    std::{path::PathBuf, time::Instant},
     tracing::{debug, error, info, trace, warn},
 };
 
@@ -26,67 +50,265 @@ use {
 /// If a file we are trying to receive already exists, the core library will call this method
 /// to figure out what to do. Since the core doesn't know about TTYs or user prompts,
 /// we handle the CLI interaction here and return the resolved action back to the core.
-struct CliConflictResolver;
+/// When Portal can't prompt, or `--on-conflict` was given, every conflict gets the same
+/// fixed answer instead.
+enum CliConflictResolver {
+    Prompt,
+    /// One of the `--on-conflict` choices
+    Fixed(OnConflict),
+}
+
+/// A fixed answer to every conflict, parsed from `--on-conflict` or `storage.on_conflict`.
+#[derive(Debug, Clone, Copy)]
+enum OnConflict {
+    Overwrite,
+    Rename,
+    Skip,
+    /// Folders are merged; anything else is renamed
+    Merge,
+}
+
+impl OnConflict {
+    fn parse(choice: &str) -> Result<Self> {
+        match choice {
+            "overwrite" => Ok(Self::Overwrite),
+            "rename" => Ok(Self::Rename),
+            "skip" => Ok(Self::Skip),
+            "merge" => Ok(Self::Merge),
+            _ => Err(anyhow!(
+                "Unknown conflict choice '{}': use one of {}",
+                choice,
+                CONFLICT_CHOICES.join(", ")
+            )),
+        }
+    }
+}
+
+impl CliConflictResolver {
+    /// `--on-conflict` wins; otherwise the user is asked when possible, and when not,
+    /// `storage.on_conflict` applies, or renaming as the safe default.
+    fn new(flag: Option<&str>, configured: Option<&str>) -> Result<Self> {
+        let flag = flag.map(OnConflict::parse).transpose()?;
+        let configured = configured
+            .map(OnConflict::parse)
+            .transpose()
+            .context("Invalid storage.on_conflict in config.toml")?;
+        let choice = match flag {
+            Some(choice) => choice,
+            None if interactive::is_interactive() => return Ok(Self::Prompt),
+            None => configured.unwrap_or(OnConflict::Rename),
+        };
+        debug!("Conflicts will be resolved with: {:?}", choice);
+        Ok(Self::Fixed(choice))
+    }
+}
 
 impl ConflictResolver for CliConflictResolver {
-    fn resolve(&self, item_name: &str) -> std::result::Result<ConflictAction, pxp::PxpError> {
-        let options = vec![
-            "Overwrite",
-            "Overwrite All",
-            "Rename",
-            "Rename All",
-            "Skip",
-            "Skip All",
-        ];
-        // We prompt the user interactively on the terminal using `inquire`.
-        // If the prompt fails (e.g., TTY disconnected or Ctrl-C), we map the error to our typed PxpError.
-        let ans = Select::new(&format!("Portal: '{}' exists. Action?", item_name), options)
+    fn resolve<'a>(
+        &'a self,
+        conflict: &'a ConflictInfo,
+    ) -> BoxFuture<'a, std::result::Result<ConflictAction, PxpError>> {
+        let choice = match self {
+            Self::Prompt => None,
+            Self::Fixed(choice) => Some(*choice),
+        };
+        let both_dirs = conflict.existing.is_dir && conflict.incoming.is_dir;
+        let conflict = conflict.clone();
+        Box::pin(async move {
+            match choice {
+                Some(OnConflict::Overwrite) => Ok(ConflictAction::Overwrite),
+                Some(OnConflict::Skip) => Ok(ConflictAction::Skip),
+                // Folders are merged; a file in both keeps both copies.
+                Some(OnConflict::Merge) if both_dirs => Ok(ConflictAction::MergeDirectories),
+                Some(OnConflict::Rename | OnConflict::Merge) => Ok(ConflictAction::Rename),
+                None if tui::is_active() => ask_conflict_inline(&conflict).await,
+                // inquire blocks on the terminal, so the prompt runs off the async workers.
+                None => tokio::task::spawn_blocking(move || prompt_conflict(&conflict))
+                    .await
+                    .map_err(|e| PxpError::ConflictResolution(e.to_string()))?,
+            }
+        })
+    }
+}
+
+const RENAME_TO: &str = "Rename to...";
+const MERGE_HELP: &str = "Overwrite keeps files that are only in your folder";
+
+/// Asks what to do about one conflict, showing both copies so the choice is an informed one.
+fn prompt_conflict(conflict: &ConflictInfo) -> std::result::Result<ConflictAction, PxpError> {
+    say!("Portal:   Existing: {}", describe_side(&conflict.existing));
+    say!("Portal:   Received: {}", describe_side(&conflict.incoming));
+
+    let both_dirs = conflict.existing.is_dir && conflict.incoming.is_dir;
+    // We prompt the user interactively on the terminal using `inquire`.
+    // If the prompt fails (e.g., TTY disconnected or Ctrl-C), we map the error to our typed PxpError.
+    let message = format!("Portal: '{}' exists. Action?", conflict.name);
+    let mut select = Select::new(&message, conflict_choices(both_dirs));
+    if both_dirs {
+        select = select.with_help_message(MERGE_HELP);
+    }
+    let ans = select
+        .prompt()
+        .map_err(|e| PxpError::ConflictResolution(e.to_string()))?;
+
+    match ans {
+        RENAME_TO => Text::new("Portal: Save the received copy as:")
             .prompt()
-            .map_err(|e| pxp::PxpError::ConflictResolution(e.to_string()))?;
-
-        // Translate the user's choice string into the corresponding core Action enum.
-        match ans {
-            "Overwrite" => Ok(ConflictAction::Overwrite),
-            "Overwrite All" => Ok(ConflictAction::OverwriteAll),
-            "Rename" => Ok(ConflictAction::Rename),
-            "Rename All" => Ok(ConflictAction::RenameAll),
-            "Skip" => Ok(ConflictAction::Skip),
-            "Skip All" => Ok(ConflictAction::SkipAll),
-            _ => unreachable!(),
+            .map(ConflictAction::RenameTo)
+            .map_err(|e| PxpError::ConflictResolution(e.to_string())),
+        other => Ok(conflict_action(other)),
+    }
+}
+
+/// The same question as `prompt_conflict`, as a dialog in the `--tui` view.
+async fn ask_conflict_inline(
+    conflict: &ConflictInfo,
+) -> std::result::Result<ConflictAction, PxpError> {
+    let both_dirs = conflict.existing.is_dir && conflict.incoming.is_dir;
+    let mut details = vec![
+        format!("Existing: {}", describe_side(&conflict.existing)),
+        format!("Received: {}", describe_side(&conflict.incoming)),
+    ];
+    if both_dirs {
+        details.push(MERGE_HELP.to_string());
+    }
+    let cancelled = || PxpError::ConflictResolution("the question was dismissed".into());
+    let title = format!("'{}' exists. Action?", conflict.name);
+    let ans = tui::choose(title, details, &conflict_choices(both_dirs))
+        .await
+        .ok_or_else(cancelled)?;
+    match ans {
+        RENAME_TO => tui::ask_text("Save the received copy as".into())
+            .await
+            .map(ConflictAction::RenameTo)
+            .ok_or_else(cancelled),
+        other => Ok(conflict_action(other)),
+    }
+}
+
+/// What can be done about a conflict; merging and replacing only apply to two folders.
+fn conflict_choices(both_dirs: bool) -> Vec<&'static str> {
+    let mut options = vec![
+        "Overwrite",
+        "Overwrite All",
+        "Rename",
+        "Rename All",
+        RENAME_TO,
+        "Keep newer",
+        "Keep larger",
+        "Skip",
+        "Skip All",
+        "Skip if identical",
+    ];
+    if both_dirs {
+        options.extend(["Merge, asking about each file", "Replace folder"]);
+    }
+    options
+}
+
+/// Translates a chosen option into the corresponding core action. `RENAME_TO` needs a
+/// name as well, so callers ask for it themselves.
+fn conflict_action(choice: &str) -> ConflictAction {
+    match choice {
+        "Overwrite" => ConflictAction::Overwrite,
+        "Overwrite All" => ConflictAction::OverwriteAll,
+        "Rename" => ConflictAction::Rename,
+        "Rename All" => ConflictAction::RenameAll,
+        "Keep newer" => ConflictAction::KeepNewer,
+        "Keep larger" => ConflictAction::KeepLarger,
+        "Skip" => ConflictAction::Skip,
+        "Skip All" => ConflictAction::SkipAll,
+        "Skip if identical" => ConflictAction::SkipIfIdentical,
+        "Merge, asking about each file" => ConflictAction::MergeDirectories,
+        "Replace folder" => ConflictAction::ReplaceDirectory,
+        _ => unreachable!(),
+    }
+}
+
+/// Size and modification time of one copy of a conflicting item.
+fn describe_side(side: &ConflictSide) -> String {
+    let kind = if side.is_dir { "folder, " } else { "" };
+    match side.modified {
+        Some(modified) => format!(
+            "{}{}, modified {}",
+            kind,
+            HumanBytes(side.bytes),
+            DateTime::<Local>::from(modified).format("%Y-%m-%d %H:%M")
+        ),
+        None => format!("{}{}", kind, HumanBytes(side.bytes)),
+    }
+}
+
+/// Lets the transfer through if it fits the `[security]` policy and the target filesystem
+/// can hold it. A policy violation is always refused. When space is short, the user may
+/// still choose to receive (e.g. after freeing space elsewhere); otherwise the sender is
+/// told why and the receive fails with `InsufficientSpace`.
+async fn answer_manifest(
+    incoming: IncomingTransfer,
+    target_dir: &Path,
+) -> Result<IncomingTransfer> {
+    if let Err(e) = incoming.check_policy() {
+        let reason = match &e {
+            PxpError::Security(reason) => reason.clone(),
+            other => other.to_string(),
+        };
+        incoming.reject(RejectReason::Policy(reason)).await;
+        say!("Portal: Transfer declined by your security settings.");
+        return Err(e.into());
+    }
+
+    if let Err(PxpError::InsufficientSpace { needed, available }) = incoming.check_space() {
+        let question = format!(
+            "This transfer needs {} but only {} is free in '{}'. Receive anyway?",
+            HumanBytes(needed),
+            HumanBytes(available),
+            target_dir.display()
+        );
+        let receive_anyway = if tui::is_active() && interactive::is_interactive() {
+            tui::confirm(question, false).await
+        } else {
+            interactive::confirm(&format!("Portal: {}", question), false).unwrap_or(false)
+        };
+
+        if !receive_anyway {
+            info!("Transfer rejected: not enough free space");
+            let reason = RejectReason::InsufficientSpace { needed, available };
+            incoming.reject(reason).await;
+            say!("Portal: Transfer declined: not enough free space.");
+            return Err(PxpError::InsufficientSpace { needed, available }.into());
         }
+        warn!("Receiving despite low free space, at the user's request");
+    }
+
+    Ok(incoming)
+}
+
+/// Lists the incoming names that were changed to be safe on this system.
+fn report_renames(summary: &ReceiveSummary) {
+    if summary.renamed.is_empty() {
+        return;
+    }
+    say!(
+        "Portal: {} name(s) were changed to be valid on this device:",
+        summary.renamed.len()
+    );
+    for entry in &summary.renamed {
+        say!("Portal:   '{}' -> '{}'", entry.original, entry.renamed);
     }
 }
 
-pub async fn start_receiver(port: Option<u16>, dir: &Option<PathBuf>) -> Result<()> {
+pub async fn start_receiver(
+    port: Option<u16>,
+    dir: &Option<PathBuf>,
+    save_text: bool,
+    on_conflict: Option<&str>,
+    tui: bool,
+) -> Result<()> {
     info!("Portal: Initializing receiver systems...");
-    let mut peer_addr: Option<String> = None;
-    let mut peer_username: Option<String> = None;
-    let mut start_ts_unix = 0u64;
-    let mut start_instant = Instant::now();
-    let mut expected_items: Option<u32> = None;
-    let mut expected_bytes: u64 = 0;
-
-    let mut partial_summary: Option<ReceiveSummary> = None;
+    let mut job = ReceiveJob::new();
+
     let result: Result<()> = async {
-        // --- Resolve port ---
-        let n_port = if let Some(port) = port {
-            trace!("Port source: CLI argument");
-            debug!("Portal: Overriding config port with CLI port: {}", port);
-            port
-        } else if let Some(cfg) = PortalConfig::load_or_return().await? {
-            if let Some(p) = cfg.network.default_port {
-                trace!("Port source: User Configuration");
-                debug!("Portal: Port not given, using config port: {}", p);
-                p
-            } else {
-                error!("Port missing in both CLI and config");
-                return Err(anyhow!("No port provided and config has no port set"));
-            }
-        } else {
-            trace!("Port source: No configuration found");
-            error!("No port configuration found");
-            return Err(anyhow!("No port provided and no config found"));
-        };
+        let n_port = resolve_port(port).await?;
 
         // --- Display local IP ---
         let my_ip = pxp::receiver::local_ip::get_local_ip().await;
@@ -111,101 +333,315 @@ pub async fn start_receiver(port: Option<u16>, dir: &Option<PathBuf>) -> Result<
 
         // --- Print listening info ---
         if let Some(ip) = &my_ip {
-            println!("Portal: Creating wormhole at {}", ip);
+            say!("Portal: Creating wormhole at {}", ip);
         } else {
-            println!("Portal: Creating wormhole on port {}.", n_port);
-            println!("Portal: Tip: To connect manually, find this device's local IP:");
-            println!("Portal:   Windows: ipconfig");
-            println!("Portal:   macOS/Linux/Android: ifconfig or ip addr");
-            println!("Portal: Then run from the sender:");
-            println!(
+            say!("Portal: Creating wormhole on port {}.", n_port);
+            say!("Portal: Tip: To connect manually, find this device's local IP:");
+            say!("Portal:   Windows: ipconfig");
+            say!("Portal:   macOS/Linux/Android: ifconfig or ip addr");
+            say!("Portal: Then run from the sender:");
+            say!(
                 "Portal:   portal send --address <receiver-ip> --port {} <file-or-folder>",
                 n_port
             );
         }
-        println!("Portal: Wormhole open for {:?}", username);
+        say!("Portal: Wormhole open for {:?}", username);
+        output::emit(JsonEvent::Listening {
+            username: username.clone(),
+            ip: my_ip.clone(),
+            port: n_port,
+        });
+
+        let timeouts = full_cfg.network.timeouts();
+        debug!("Session timeouts: {:?}", timeouts);
+        let policy = full_cfg.security.policy();
+        debug!("Receive policy: {:?}", policy);
+        let filter = full_cfg
+            .security
+            .sender_filter()
+            .context("Invalid [security] allow/deny list")?;
+        debug!("Sender filter: {:?}", filter);
+        let routes = Routes::new(&full_cfg.routes)?;
+        job.hooks = Hooks::new(&full_cfg.hooks);
+
+        // Resolved before waiting so any prompt happens up front, and so the beacon can
+        // advertise the free space where files will land.
+        let target_dir = get_target_dir(dir).await?;
+        info!("Target directory for saving: {:?}", target_dir);
+
+        let device = DeviceInfo {
+            device_name: gethostname().into_string().ok(),
+            app_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            free_space: pxp::receiver::available_space(&target_dir),
+        };
+        debug!("Beacon device info: {:?}", device);
+
+        // Started once nothing is left to ask on the plain terminal.
+        let _screen = if tui {
+            Some(tui::start(Side::Receiver)?)
+        } else {
+            None
+        };
+        let progress = ProgressSlot::default();
+        let events: Arc<dyn EventSink> = if output::is_json() {
+            Arc::new(JsonEvents::default())
+        } else {
+            Arc::new(progress.clone())
+        };
+        let session = ReceiveSession::builder()
+            .username(username)
+            .port(n_port)
+            .device(device)
+            .sender_filter(filter)
+            .target_dir(&target_dir)
+            .policy(policy)
+            .timeouts(timeouts)
+            .resolver(CliConflictResolver::new(
+                on_conflict,
+                full_cfg.storage.on_conflict.as_deref(),
+            )?)
+            .progress(tui::events(events))
+            .build()?;
 
         // --- Core handshake ---
-        let handshake = pxp::receiver::handshake::accept_and_read_manifest(n_port, username).await?;
-        let socket = handshake.socket;
-        peer_addr = handshake.peer_addr;
-        peer_username = handshake.peer_username.clone();
+        // The beacon keeps advertising this receiver as busy until the transfer is over.
+        let incoming = session.listen().await?;
+        job.peer_addr = incoming.peer_addr().map(str::to_string);
+        job.peer_username = incoming.peer_username().map(str::to_string);
+        if let Some(peer) = job.peer_username.as_ref().or(job.peer_addr.as_ref()) {
+            tui::set_peer(peer);
+        }
 
-        println!("Portal: Connection established with {}!", peer_addr.as_deref().unwrap_or("unknown"));
-        println!("Portal: Connected to sender");
-        println!("Portal: Waiting for incoming files...");
+        say!(
+            "Portal: Connection established with {}!",
+            job.peer_addr.as_deref().unwrap_or("unknown")
+        );
+        output::emit(JsonEvent::Connected {
+            peer: job
+                .peer_username
+                .clone()
+                .or_else(|| job.peer_addr.clone())
+                .unwrap_or_default(),
+            host: job.peer_addr.clone().unwrap_or_default(),
+            verified: None,
+        });
+        say!("Portal: Connected to sender");
+        say!("Portal: Waiting for incoming files...");
 
-        start_ts_unix = TransferHistoryRecord::now_unix();
-        start_instant = Instant::now();
+        let settings = ReceiveSettings {
+            target_dir,
+            routes,
+            save_text,
+            progress,
+        };
+        job.receive(incoming, &settings).await
+    }
+    .await;
 
-        let global_manifest = handshake.manifest;
+    if let Err(ref e) = result {
+        job.record_failure(e).await;
+    }
+
+    result
+}
 
-        let total_directories = &global_manifest.total_directories;
-        let total_files = global_manifest.total_files;
+/// The port given with `--port`, or the configured default.
+async fn resolve_port(port: Option<u16>) -> Result<u16> {
+    if let Some(port) = port {
+        trace!("Port source: CLI argument");
+        debug!("Portal: Overriding config port with CLI port: {}", port);
+        Ok(port)
+    } else if let Some(cfg) = PortalConfig::load_or_return().await? {
+        if let Some(p) = cfg.network.default_port {
+            trace!("Port source: User Configuration");
+            debug!("Portal: Port not given, using config port: {}", p);
+            Ok(p)
+        } else {
+            error!("Port missing in both CLI and config");
+            Err(anyhow!("No port provided and config has no port set"))
+        }
+    } else {
+        trace!("Port source: No configuration found");
+        error!("No port configuration found");
+        Err(anyhow!("No port provided and no config found"))
+    }
+}
+
+/// What the CLI does with a transfer on top of the pxp session, settled before it starts.
+struct ReceiveSettings {
+    target_dir: PathBuf,
+    routes: Routes,
+    /// Save text items into the target dir instead of printing them.
+    save_text: bool,
+    /// Where the session's progress events are shown
+    progress: ProgressSlot,
+}
+
+/// What a receive got to before it ended, so a failure can still be recorded in history.
+struct ReceiveJob {
+    peer_addr: Option<String>,
+    peer_username: Option<String>,
+    start_ts_unix: u64,
+    start_instant: Instant,
+    expected_items: Option<u32>,
+    expected_bytes: u64,
+    partial_summary: Option<ReceiveSummary>,
+    hooks: Hooks,
+    /// Hooks that ran before the transfer was recorded
+    hook_runs: Vec<HistoryHook>,
+}
+
+impl ReceiveJob {
+    fn new() -> Self {
+        Self {
+            peer_addr: None,
+            peer_username: None,
+            start_ts_unix: 0,
+            start_instant: Instant::now(),
+            expected_items: None,
+            expected_bytes: 0,
+            partial_summary: None,
+            hooks: Hooks::default(),
+            hook_runs: Vec::new(),
+        }
+    }
+
+    /// Answers the manifest, receives the stream into the target dir, routes items and
+    /// resolves conflicts, and records the transfer. Used both when a sender pushes to us
+    /// and when we fetch from a share. Text items are printed unless `save_text` is set.
+    async fn receive(
+        &mut self,
+        incoming: IncomingTransfer,
+        settings: &ReceiveSettings,
+    ) -> Result<()> {
+        let target_dir = settings.target_dir.as_path();
+        self.start_ts_unix = TransferHistoryRecord::now_unix();
+        self.start_instant = Instant::now();
+
+        let global_manifest = incoming.manifest().clone();
         let description = global_manifest.description.clone();
-        if let Some(name) = &peer_username {
+        if let Some(name) = &self.peer_username {
             info!("Sender username received in manifest: {}", name);
         } else {
             warn!("No sender username provided in manifest");
         }
-        expected_bytes = global_manifest.total_bytes;
-        let compressed = global_manifest.compressed;
-        if compressed {
+        self.expected_bytes = global_manifest.total_bytes;
+        if global_manifest.compressed {
             info!("Incoming transfer is gzip-compressed");
         } else {
             info!("Incoming transfer is not compressed");
         }
 
-        let total_items = total_files + total_directories;
-        expected_items = Some(total_items);
+        let total_items = global_manifest.total_files + global_manifest.total_directories;
+        self.expected_items = Some(total_items);
+        tui::set_totals(total_items as usize, global_manifest.total_bytes);
 
-        println!("Portal: Incoming transfer - {} item(s)", total_items);
+        say!("Portal: Incoming transfer - {} item(s)", total_items);
+        output::emit(JsonEvent::manifest(&global_manifest));
 
         if let Some(desc) = &description {
-            println!("Portal: Sender left a note: \"{}\"", desc);
+            say!("Portal: Sender left a note: \"{}\"", desc);
             info!("Transfer Note: {}", desc);
         } else {
             info!("Transfer has no description.");
         }
 
-        let target_dir = get_target_dir(&dir).await?;
-        info!("Target directory for saving: {:?}", target_dir);
+        // --- Pre-flight checks, answered over the wire before any data is sent ---
+        let incoming = answer_manifest(incoming, target_dir).await?;
+
+        if !output::is_json() {
+            let prog = ProgressManager::new_with_side(Side::Receiver);
+            debug!("Progress UI created for receiver");
+            prog.set_total_items(total_items as usize);
+            trace!("Progress UI initialized with total_items={}", total_items);
+            settings.progress.show(prog);
+        }
 
-        let prog = ProgressManager::new_with_side(Side::Receiver);
-        debug!("Progress UI created for receiver");
-        prog.set_total_items(total_items as usize);
-        trace!("Progress UI initialized with total_items={}", total_items);
-
-        let (stream_result, staged, summary) = pxp::receiver::stream::receive_stream(
-            socket,
-            compressed,
-            &target_dir,
-            total_items,
-            Some(&prog as &dyn pxp::TransferProgress),
-        )
-        .await;
+        let received = incoming.accept().await;
         // Stop the progress UI before any conflict prompts so the terminal stays clean.
-        prog.finish();
+        settings.progress.finish();
+        self.place(received?, settings).await
+    }
+
+    /// Moves a transfer that has landed in staging into place: screens it, runs the
+    /// pre_reconcile hook, routes items and resolves conflicts, then records it. Used for
+    /// pushed, fetched and uploaded transfers alike.
+    async fn place(
+        &mut self,
+        mut received: ReceivedTransfer,
+        settings: &ReceiveSettings,
+    ) -> Result<()> {
+        let target_dir = settings.target_dir.as_path();
+        let global_manifest = received.manifest().clone();
+        let description = global_manifest.description.clone();
+
+        // A policy violation keeps nothing, not even the items that passed before it.
+        if let Some(PxpError::Security(_)) = received.stream_error() {
+            let outcome = received.discard().await;
+            say!("Portal: Transfer blocked by your security settings; nothing was saved.");
+            self.partial_summary = Some(outcome.summary);
+            if let Some(e) = outcome.stream_error {
+                return Err(e.into());
+            }
+            return Err(anyhow!("Transfer blocked by your security settings"));
+        }
 
         // Resolve any filename collisions now that the stream is done. This runs on success
         // AND on a cut connection, so whatever was already staged still lands in the target
         // dir (same crash-safety as the old per-item finalize behavior).
-        let conflict_resolver = CliConflictResolver;
-        if let Err(e) =
-            pxp::receiver::receive_item::reconcile(&staged, Some(&conflict_resolver as &dyn ConflictResolver))
-                .await
-        {
-            partial_summary = Some(summary);
+        let rejected = self
+            .run_pre_reconcile(&received.staged, description.as_deref())
+            .await;
+        let order: Vec<String> = received
+            .staged
+            .items
+            .iter()
+            .map(|item| item.name.clone())
+            .collect();
+        for name in order.iter().filter(|name| rejected.contains(*name)) {
+            say!("Portal: '{}' was rejected by the pre_reconcile hook", name);
+            received.reject_item(name);
+        }
+
+        let router = settings.routes.for_transfer(
+            target_dir,
+            global_manifest.sender_username.as_deref(),
+            description.as_deref(),
+        );
+        let outcome = received
+            .reconcile((!settings.routes.is_empty()).then_some(&router as &dyn ItemRouter))
+            .await;
+        if let Some(e) = outcome.reconcile_error {
+            self.partial_summary = Some(outcome.summary);
             return Err(e.into());
         }
+        for item in &outcome.placed {
+            if let Some(dir) = item.path.as_deref().and_then(Path::parent)
+                && dir != target_dir
+            {
+                say!("Portal: '{}' saved to '{}'", item.name, dir.display());
+            }
+        }
 
-        if let Err(e) = stream_result {
-            println!(
+        let summary = outcome.summary;
+        report_renames(&summary);
+        // Text that made it before a cut connection is still shown (or saved).
+        let saved_texts = match deliver_texts(&summary, settings, &router).await {
+            Ok(saved) => saved,
+            Err(e) => {
+                self.partial_summary = Some(summary);
+                return Err(e);
+            }
+        };
+        let locations = item_locations(&summary, &outcome.placed, &saved_texts);
+
+        if let Some(e) = outcome.stream_error {
+            say!(
                 "Portal: Transfer interrupted; recovered {} item(s) to '{}'",
-                staged.items.len(),
+                order.len() - rejected.len(),
                 target_dir.display()
             );
-            partial_summary = Some(summary);
+            self.partial_summary = Some(summary);
             return Err(e.into());
         }
 
@@ -213,45 +649,37 @@ pub async fn start_receiver(port: Option<u16>, dir: &Option<PathBuf>) -> Result<
             "SUCCESS: Transfer completed. Saved to {}",
             target_dir.display()
         );
-        println!(
-            "Portal: All item(s) have been received successfully! Saved to '{}'",
-            target_dir.display()
-        );
-
-        // Convert core summary items to CLI history items
-        let history_items: Vec<HistoryItem> = summary
-            .items
-            .iter()
-            .map(|item| HistoryItem {
-                name: item.name.clone(),
-                bytes: item.bytes,
-                kind: if item.is_directory {
-                    HistoryItemKind::Directory
-                } else {
-                    HistoryItemKind::File
-                },
-            })
-            .collect();
+        if settings.save_text || summary.items.iter().any(|item| item.text.is_none()) {
+            say!(
+                "Portal: All item(s) have been received successfully! Saved to '{}'",
+                target_dir.display()
+            );
+        } else {
+            say!("Portal: All item(s) have been received successfully!");
+        }
 
-        let duration_ms = start_instant.elapsed().as_millis() as u64;
+        let history_items = to_history_items(&summary, &locations);
+        let duration_ms = self.start_instant.elapsed().as_millis() as u64;
         debug!(
             "Preparing successful receive history record (duration: {}ms)",
             duration_ms
         );
-        let record = build_receive_history_record(
-            start_ts_unix,
+        let mut record = build_receive_history_record(
+            self.start_ts_unix,
             duration_ms,
             HistoryStatus::Success,
-            peer_addr.clone(),
-            peer_username.clone(),
+            self.peer_addr.clone(),
+            self.peer_username.clone(),
             Some(target_dir.display().to_string()),
             description.clone(),
-            expected_items.unwrap_or(history_items.len() as u32),
-            expected_bytes,
+            self.expected_items.unwrap_or(history_items.len() as u32),
+            self.expected_bytes,
             history_items.len() as u32,
             summary.total_bytes,
             Some(history_items),
         );
+        self.run_post_receive(&mut record).await;
+        output::summary(&record, None);
         if let Err(e) = append_record(&record).await {
             error!("Failed to append history record: {:#}", e);
         } else {
@@ -261,41 +689,72 @@ pub async fn start_receiver(port: Option<u16>, dir: &Option<PathBuf>) -> Result<
 
         Ok(())
     }
-    .await;
 
-    if let Err(ref e) = result {
-        let duration_ms = start_instant.elapsed().as_millis() as u64;
+    /// Runs the pre_reconcile hook on the staged files and folders and returns the names of
+    /// those it rejects: the ones it prints, one per line, or all of them if it fails.
+    async fn run_pre_reconcile(
+        &mut self,
+        staged: &StagedTransfer,
+        description: Option<&str>,
+    ) -> HashSet<String> {
+        if staged.items.is_empty() || !self.hooks.is_set(HookKind::PreReconcile) {
+            return HashSet::new();
+        }
+        let payload = HookPayload::before_reconcile(
+            self.peer_username.as_deref(),
+            self.peer_addr.as_deref(),
+            description,
+            staged,
+        );
+        let Some(run) = self.hooks.run(HookKind::PreReconcile, &payload).await else {
+            return HashSet::new();
+        };
+        self.hook_runs.push(run.to_history());
+        let names: HashSet<String> = staged.items.iter().map(|item| item.name.clone()).collect();
+        if !run.succeeded() {
+            return names;
+        }
+        let mut rejected = HashSet::new();
+        for line in run.stdout.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            if names.contains(line) {
+                rejected.insert(line.to_string());
+            } else {
+                warn!("pre_reconcile hook rejected an unknown item: {:?}", line);
+            }
+        }
+        debug!("pre_reconcile hook rejected {} item(s)", rejected.len());
+        rejected
+    }
+
+    /// Notes the hooks that already ran in `record`, then runs post_receive with it.
+    async fn run_post_receive(&self, record: &mut TransferHistoryRecord) {
+        record.hooks = (!self.hook_runs.is_empty()).then(|| self.hook_runs.clone());
+        self.hooks.after_transfer(HookKind::PostReceive, record).await;
+    }
+
+    /// Appends the history record for a receive that ended with `error`.
+    async fn record_failure(mut self, error: &anyhow::Error) {
+        let duration_ms = self.start_instant.elapsed().as_millis() as u64;
         debug!(
             "Preparing failed receive history record (duration: {}ms)",
             duration_ms
         );
-        let summary = partial_summary.unwrap_or(ReceiveSummary {
+        let summary = self.partial_summary.take().unwrap_or(ReceiveSummary {
             items: Vec::new(),
             total_bytes: 0,
+            renamed: Vec::new(),
         });
-        let history_items: Vec<HistoryItem> = summary
-            .items
-            .iter()
-            .map(|item| HistoryItem {
-                name: item.name.clone(),
-                bytes: item.bytes,
-                kind: if item.is_directory {
-                    HistoryItemKind::Directory
-                } else {
-                    HistoryItemKind::File
-                },
-            })
-            .collect();
+        let history_items = to_history_items(&summary, &[]);
         let mut record = build_receive_history_record(
-            start_ts_unix,
+            self.start_ts_unix,
             duration_ms,
             HistoryStatus::Failed,
-            peer_addr,
-            peer_username,
+            self.peer_addr.clone(),
+            self.peer_username.clone(),
             None,
             None,
-            expected_items.unwrap_or(0),
-            expected_bytes,
+            self.expected_items.unwrap_or(0),
+            self.expected_bytes,
             history_items.len() as u32,
             summary.total_bytes,
             if history_items.is_empty() {
@@ -304,7 +763,9 @@ pub async fn start_receiver(port: Option<u16>, dir: &Option<PathBuf>) -> Result<
                 Some(history_items)
             },
         );
-        record.error = Some(format!("{:#}", e));
+        record.error = Some(format!("{:#}", error));
+        self.run_post_receive(&mut record).await;
+        output::summary(&record, Some(output::error_code(error)));
         if let Err(err) = append_record(&record).await {
             error!("Failed to append failed history record: {:#}", err);
         } else {
@@ -312,6 +773,90 @@ pub async fn start_receiver(port: Option<u16>, dir: &Option<PathBuf>) -> Result<
             trace!("Appended failed record details: {:?}", record);
         }
     }
+}
 
-    result
+/// Prints every received text item to stdout (as a `text` event with `--json`), or with
+/// `save_text` writes each one into the target dir (or where a routing rule sends it),
+/// never replacing an existing file. Returns where each text was saved, in order.
+async fn deliver_texts(
+    summary: &ReceiveSummary,
+    settings: &ReceiveSettings,
+    router: &dyn ItemRouter,
+) -> Result<Vec<Option<PathBuf>>> {
+    let mut saved = Vec::new();
+    for item in &summary.items {
+        let Some(text) = &item.text else {
+            continue;
+        };
+        if settings.save_text {
+            let dir = router
+                .route(&item.name, item.bytes, false)
+                .unwrap_or_else(|| settings.target_dir.clone());
+            let path = pxp::receiver::save_text(&dir, &item.name, text)
+                .await
+                .context("Failed to save received text")?;
+            say!("Portal: Text saved to '{}'", path.display());
+            saved.push(Some(path));
+        } else if output::is_json() {
+            output::emit(JsonEvent::Text {
+                name: item.name.clone(),
+                text: text.clone(),
+            });
+            saved.push(None);
+        } else {
+            say!("Portal: Received text:");
+            say!("{}", text);
+            saved.push(None);
+        }
+    }
+    Ok(saved)
+}
+
+/// Where each summary item ended up, in summary order. Staged items are placed in stream
+/// order and texts in their own, so both can be matched up with the summary by position.
+fn item_locations(
+    summary: &ReceiveSummary,
+    placed: &[PlacedItem],
+    saved_texts: &[Option<PathBuf>],
+) -> Vec<Option<PathBuf>> {
+    let mut placed = placed.iter();
+    let mut texts = saved_texts.iter();
+    summary
+        .items
+        .iter()
+        .map(|item| {
+            let location = if item.text.is_some() {
+                texts.next().cloned()
+            } else {
+                placed.next().map(|p| p.path.clone())
+            };
+            location.flatten()
+        })
+        .collect()
+}
+
+/// Converts core summary items to CLI history items, with the final location of each item
+/// when it is known.
+fn to_history_items(summary: &ReceiveSummary, locations: &[Option<PathBuf>]) -> Vec<HistoryItem> {
+    summary
+        .items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| HistoryItem {
+            name: item.name.clone(),
+            bytes: item.bytes,
+            path: locations
+                .get(i)
+                .cloned()
+                .flatten()
+                .map(|p| p.display().to_string()),
+            kind: if item.is_directory {
+                HistoryItemKind::Directory
+            } else if item.text.is_some() {
+                HistoryItemKind::Text
+            } else {
+                HistoryItemKind::File
+            },
+        })
+        .collect()
 }
@@ -0,0 +1,190 @@
+use {
+    crate::config::models::{route::RouteRule, security::parse_size},
+    anyhow::{Context, Result},
+    chrono::Local,
+    home::home_dir,
+    pxp::ItemRouter,
+    std::path::{Path, PathBuf},
+    tracing::{debug, trace},
+};
+
+/// The `[[route]]` rules from config, checked once so a typo fails before anything is
+/// received.
+#[derive(Debug, Default)]
+pub struct Routes {
+    rules: Vec<Route>,
+}
+
+#[derive(Debug)]
+struct Route {
+    dir: String,
+    from: Vec<String>,
+    extensions: Vec<String>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    keywords: Vec<String>,
+}
+
+/// The rules applied to one transfer, which knows who sent it and why.
+pub struct TransferRouter<'a> {
+    routes: &'a Routes,
+    download_dir: &'a Path,
+    sender: Option<&'a str>,
+    description: String,
+    date: String,
+}
+
+impl Routes {
+    pub fn new(rules: &[RouteRule]) -> Result<Self> {
+        let rules = rules
+            .iter()
+            .enumerate()
+            .map(|(i, rule)| {
+                let size = |value: &Option<String>, field: &str| {
+                    value
+                        .as_deref()
+                        .map(parse_size)
+                        .transpose()
+                        .with_context(|| format!("Invalid {} in [[route]] rule {}", field, i + 1))
+                };
+                let lower = |list: &Option<Vec<String>>| -> Vec<String> {
+                    list.iter().flatten().map(|s| s.to_lowercase()).collect()
+                };
+                Ok(Route {
+                    dir: rule.dir.clone(),
+                    from: lower(&rule.from)
+                        .iter()
+                        .map(|name| strip_suffix(name).to_string())
+                        .collect(),
+                    extensions: lower(&rule.extensions)
+                        .iter()
+                        .map(|ext| ext.trim_start_matches('.').to_string())
+                        .collect(),
+                    min_size: size(&rule.min_size, "min_size")?,
+                    max_size: size(&rule.max_size, "max_size")?,
+                    keywords: lower(&rule.keywords),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        debug!("Loaded {} routing rule(s)", rules.len());
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The router for one transfer from `sender` with `description`, where unrouted items
+    /// go to `download_dir`.
+    pub fn for_transfer<'a>(
+        &'a self,
+        download_dir: &'a Path,
+        sender: Option<&'a str>,
+        description: Option<&str>,
+    ) -> TransferRouter<'a> {
+        TransferRouter {
+            routes: self,
+            download_dir,
+            sender,
+            description: description.unwrap_or_default().to_lowercase(),
+            date: Local::now().format("%Y-%m-%d").to_string(),
+        }
+    }
+}
+
+impl TransferRouter<'_> {
+    fn matches(&self, rule: &Route, ext: Option<&str>, bytes: u64) -> bool {
+        if !rule.from.is_empty() {
+            let Some(sender) = self.sender else {
+                return false;
+            };
+            let sender = strip_suffix(&sender.to_lowercase()).to_string();
+            if !rule.from.contains(&sender) {
+                return false;
+            }
+        }
+        if !rule.extensions.is_empty()
+            && !ext.is_some_and(|ext| rule.extensions.iter().any(|e| e == ext))
+        {
+            return false;
+        }
+        if rule.min_size.is_some_and(|min| bytes < min)
+            || rule.max_size.is_some_and(|max| bytes > max)
+        {
+            return false;
+        }
+        rule.keywords.is_empty()
+            || rule
+                .keywords
+                .iter()
+                .any(|word| self.description.contains(word.as_str()))
+    }
+
+    /// Fills in a rule's `dir` template for one item.
+    fn expand(&self, template: &str, ext: Option<&str>) -> PathBuf {
+        let sender = self.sender.map(strip_suffix).unwrap_or("unknown");
+        let expanded = template
+            .replace("{download_dir}", &self.download_dir.to_string_lossy())
+            .replace("{sender}", &path_safe(sender))
+            .replace("{date}", &self.date)
+            .replace("{ext}", &path_safe(ext.unwrap_or("none")));
+        let path = match expanded.strip_prefix('~') {
+            Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+                match home_dir() {
+                    Some(home) => home.join(rest.trim_start_matches(['/', '\\'])),
+                    None => PathBuf::from(&expanded),
+                }
+            }
+            _ => PathBuf::from(&expanded),
+        };
+        if path.is_absolute() {
+            path
+        } else {
+            self.download_dir.join(path)
+        }
+    }
+}
+
+impl ItemRouter for TransferRouter<'_> {
+    fn route(&self, item_name: &str, bytes: u64, is_dir: bool) -> Option<PathBuf> {
+        let ext = if is_dir {
+            None
+        } else {
+            Path::new(item_name)
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+        };
+        let rule = self
+            .routes
+            .rules
+            .iter()
+            .find(|rule| self.matches(rule, ext.as_deref(), bytes))?;
+        let dir = self.expand(&rule.dir, ext.as_deref());
+        trace!("Rule {:?} routes '{}' to {:?}", rule, item_name, dir);
+        Some(dir)
+    }
+}
+
+fn strip_suffix(name: &str) -> &str {
+    name.strip_suffix("@portal").unwrap_or(name)
+}
+
+/// Makes a value chosen by the sender (its username, a file extension) safe to use as one
+/// path component, so it can't climb out of the rule's directory.
+fn path_safe(value: &str) -> String {
+    let safe: String = value
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if safe.is_empty() || safe.chars().all(|c| c == '.') {
+        "unknown".to_string()
+    } else {
+        safe
+    }
+}
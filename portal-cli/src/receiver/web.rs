@@ -0,0 +1,287 @@
+use {
+    super::{
+        CliConflictResolver, ReceiveJob, ReceiveSettings, get_dir::get_target_dir, resolve_port,
+        route::Routes,
+    },
+    crate::{
+        config::models::PortalConfig,
+        history::TransferHistoryRecord,
+        hooks::Hooks,
+        output::{self, JsonEvents},
+        progress::{ProgressManager, ProgressSlot, Side},
+        web,
+    },
+    anyhow::{Context, Result, anyhow},
+    axum::{
+        Router,
+        extract::{
+            ConnectInfo, DefaultBodyLimit, Multipart, Path, State, multipart::MultipartError,
+        },
+        http::{HeaderMap, StatusCode, header::CONTENT_LENGTH},
+        response::{IntoResponse, Response},
+        routing::get,
+    },
+    gethostname::gethostname,
+    pxp::{EventSink, PxpError, ReceiveSession, receiver::SenderFilter, session::Upload},
+    std::{
+        net::SocketAddr,
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicBool, Ordering},
+        },
+        time::Instant,
+    },
+    tokio::sync::Notify,
+    tokio_stream::StreamExt,
+    tokio_util::io::StreamReader,
+    tracing::{debug, info, warn},
+};
+
+/// Room in a capped upload request for the multipart headers around the files.
+const FORM_OVERHEAD: u64 = 1024 * 1024;
+
+/// Everything the upload page's handlers share. The link is good for one upload, like a
+/// `portal receive` is good for one transfer; the server stops once it is in.
+struct WebReceiver {
+    token: String,
+    /// Who the page says files are going to
+    name: String,
+    session: ReceiveSession,
+    settings: ReceiveSettings,
+    filter: SenderFilter,
+    hooks: Hooks,
+    used: AtomicBool,
+    /// How the upload went, once it is over
+    result: Mutex<Option<Result<()>>>,
+    finished: Notify,
+}
+
+/// Serves an upload page until a browser has sent files through it. The files go through
+/// the same staging, routing, conflict handling and history as a pushed transfer.
+pub async fn start_web_receiver(
+    port: Option<u16>,
+    dir: &Option<std::path::PathBuf>,
+    on_conflict: Option<&str>,
+) -> Result<()> {
+    info!("Portal: Initializing web receiver...");
+    let n_port = resolve_port(port).await?;
+    let full_cfg = PortalConfig::load_all()
+        .await
+        .context("Failed to load user config")?;
+    let policy = full_cfg.security.policy();
+    debug!("Receive policy: {:?}", policy);
+    // The files are checked against the policy as they come in; this caps the whole request.
+    let body_limit = match policy.max_total_bytes {
+        Some(max) => DefaultBodyLimit::max(
+            usize::try_from(max.saturating_add(FORM_OVERHEAD)).unwrap_or(usize::MAX),
+        ),
+        None => DefaultBodyLimit::disable(),
+    };
+    let filter = full_cfg
+        .security
+        .sender_filter()
+        .context("Invalid [security] allow/deny list")?;
+    let routes = Routes::new(&full_cfg.routes)?;
+    let target_dir = get_target_dir(dir).await?;
+    info!("Target directory for saving: {:?}", target_dir);
+
+    let progress = ProgressSlot::default();
+    let events: Arc<dyn EventSink> = if output::is_json() {
+        Arc::new(JsonEvents::default())
+    } else {
+        Arc::new(progress.clone())
+    };
+    let session = ReceiveSession::builder()
+        .target_dir(&target_dir)
+        .policy(policy)
+        .resolver(CliConflictResolver::new(
+            on_conflict,
+            full_cfg.storage.on_conflict.as_deref(),
+        )?)
+        .progress(events)
+        .build()?;
+
+    let name = full_cfg
+        .user
+        .username
+        .clone()
+        .or_else(|| gethostname().into_string().ok())
+        .unwrap_or_else(|| "this device".into());
+    let token = web::new_token();
+    let listener = web::bind(n_port).await?;
+    let state = Arc::new(WebReceiver {
+        token,
+        name,
+        session,
+        settings: ReceiveSettings {
+            target_dir,
+            routes,
+            save_text: false,
+            progress,
+        },
+        filter,
+        hooks: Hooks::new(&full_cfg.hooks),
+        used: AtomicBool::new(false),
+        result: Mutex::new(None),
+        finished: Notify::new(),
+    });
+    let app = Router::new()
+        .route("/{token}", get(upload_page).post(take_upload))
+        .layer(body_limit)
+        .with_state(state.clone());
+
+    web::announce(n_port, &state.token).await;
+    say!("Portal: Waiting for an upload. Press Ctrl+C to stop.");
+
+    // In-flight responses are still written after the upload ends, so the browser is
+    // told how it went before the server goes away.
+    let finished = state.clone();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move { finished.finished.notified().await })
+    .await?;
+
+    let result = match state.result.lock() {
+        Ok(mut result) => result.take(),
+        Err(_) => None,
+    };
+    result.unwrap_or_else(|| Err(anyhow!("The web server stopped before anything was uploaded")))
+}
+
+async fn upload_page(
+    State(state): State<Arc<WebReceiver>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(token): Path<String>,
+) -> Response {
+    if !web::token_matches(&token, &state.token) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    if !state.filter.permits(addr.ip(), None) {
+        return (StatusCode::FORBIDDEN, "This device may not send files here.").into_response();
+    }
+    if state.used.load(Ordering::SeqCst) {
+        return (StatusCode::GONE, "This link has already been used.").into_response();
+    }
+    let body = web::UPLOAD_PAGE.replace("{{name}}", &web::escape(&state.name));
+    web::page("upload", &body).into_response()
+}
+
+async fn take_upload(
+    State(state): State<Arc<WebReceiver>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(token): Path<String>,
+    headers: HeaderMap,
+    multipart: Multipart,
+) -> Response {
+    if !web::token_matches(&token, &state.token) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    if !state.filter.permits(addr.ip(), None) {
+        return (StatusCode::FORBIDDEN, "This device may not send files here.").into_response();
+    }
+    if state.used.swap(true, Ordering::SeqCst) {
+        return (StatusCode::GONE, "This link has already been used.").into_response();
+    }
+    say!("Portal: Upload started from {}", addr.ip());
+
+    let mut job = ReceiveJob::new();
+    job.hooks = state.hooks.clone();
+    job.peer_addr = Some(addr.ip().to_string());
+    let content_length = headers
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok());
+    let result = receive_upload(&state, &mut job, content_length, multipart).await;
+
+    let response = match &result {
+        Ok(files) => (
+            StatusCode::OK,
+            format!("Uploaded {} file(s) to {}. Thank you!", files, state.name),
+        ),
+        Err(e) => {
+            warn!("Upload from {} failed: {:#}", addr.ip(), e);
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("Upload failed: {:#}", e))
+        }
+    };
+    let result = match result {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            job.record_failure(&e).await;
+            Err(e)
+        }
+    };
+    if let Ok(mut slot) = state.result.lock() {
+        *slot = Some(result);
+    }
+    state.finished.notify_one();
+    response.into_response()
+}
+
+/// Stages the files of one upload form and places them. Returns how many files came in.
+async fn receive_upload(
+    state: &WebReceiver,
+    job: &mut ReceiveJob,
+    content_length: Option<u64>,
+    multipart: Multipart,
+) -> Result<usize> {
+    job.start_ts_unix = TransferHistoryRecord::now_unix();
+    job.start_instant = Instant::now();
+    let settings = &state.settings;
+    if let Some(bytes) = content_length {
+        pxp::receiver::check_free_space(&settings.target_dir, bytes)?;
+    }
+
+    if !output::is_json() {
+        settings
+            .progress
+            .show(ProgressManager::new_with_side(Side::Receiver));
+    }
+    let mut upload = state.session.upload(job.peer_addr.clone()).await;
+    let result = read_form(&mut upload, multipart).await;
+    settings.progress.finish();
+
+    let received = upload.finish(result);
+    let files = received.staged.items.len();
+    job.expected_items = Some(files as u32);
+    job.expected_bytes = received.manifest().total_bytes;
+    if files == 0 && received.stream_error().is_none() {
+        return Err(anyhow!("No files were uploaded"));
+    }
+    job.place(received, settings).await?;
+    Ok(files)
+}
+
+/// Stages every file in the form. A `sizes` field sent ahead of the files (the upload
+/// page adds one) lets each be checked before it arrives.
+async fn read_form(upload: &mut Upload<'_>, mut multipart: Multipart) -> pxp::Result<()> {
+    let mut sizes: Vec<u64> = Vec::new();
+    let mut index = 0;
+    while let Some(field) = multipart.next_field().await.map_err(form_error)? {
+        match field.name() {
+            Some("sizes") => {
+                let text = field.text().await.map_err(form_error)?;
+                sizes = text.split(',').filter_map(|s| s.trim().parse().ok()).collect();
+                upload.expect(sizes.len());
+            }
+            Some("files") => {
+                // An empty file input still sends a field, with no name.
+                let Some(name) = field.file_name().filter(|n| !n.is_empty()) else {
+                    continue;
+                };
+                let name = name.to_string();
+                let size = sizes.get(index).copied();
+                index += 1;
+                let chunks = field.map(|chunk| chunk.map_err(std::io::Error::other));
+                upload.add_file(&name, size, StreamReader::new(chunks)).await?;
+            }
+            other => debug!("Ignoring form field {:?}", other),
+        }
+    }
+    Ok(())
+}
+
+fn form_error(e: MultipartError) -> PxpError {
+    PxpError::Io(std::io::Error::other(e))
+}
@@ -0,0 +1,33 @@
+use {
+    crate::commands::ExcludeArgs,
+    anyhow::{Context, Result},
+    pxp::sender::PathFilter,
+    tokio::fs::read_to_string,
+    tracing::{debug, trace},
+};
+
+/// Turns the `--exclude`, `--exclude-from` and `--respect-gitignore` flags into the core's
+/// path filter. Patterns from the file are appended after the ones given on the command line.
+pub async fn build_path_filter(args: &ExcludeArgs) -> Result<PathFilter> {
+    let mut patterns = args.exclude.clone();
+
+    if let Some(path) = &args.exclude_from {
+        trace!("Reading exclude patterns from {:?}", path);
+        let content = read_to_string(path)
+            .await
+            .with_context(|| format!("Failed to read exclude file '{}'", path.display()))?;
+        patterns.extend(
+            content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(String::from),
+        );
+    }
+
+    debug!(
+        "Exclude patterns resolved: {:?}, respect_gitignore={}",
+        patterns, args.respect_gitignore
+    );
+    PathFilter::new(patterns, args.respect_gitignore).context("Invalid exclude settings")
+}
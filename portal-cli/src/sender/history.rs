@@ -1,5 +1,20 @@
-use crate::history::{HistoryItem, HistoryMode, HistoryStatus, TransferHistoryRecord};
+use {
+    crate::{
+        history::{
+            HistoryAttempt, HistoryHook, HistoryItem, HistoryMode, HistoryStatus,
+            TransferHistoryRecord, append_record,
+        },
+        hooks::{HookKind, Hooks},
+        output,
+    },
+    pxp::{
+        PxpError,
+        session::{PeerOutcome, SendTarget},
+    },
+    tracing::{info, trace, warn},
+};
 
+#[allow(clippy::too_many_arguments)]
 pub fn build_history_record(
     timestamp: u64,
     duration_ms: u64,
@@ -36,5 +51,69 @@ pub fn build_history_record(
         } else {
             Some(actual_items)
         },
+        attempts: None,
+        hooks: None,
+    }
+}
+
+/// What a send is recorded with, shared by the record of every receiver it went to.
+pub struct SendRecordContext<'a> {
+    pub description: Option<String>,
+    pub intended_items: &'a [HistoryItem],
+    pub intended_bytes: u64,
+    pub hooks: &'a Hooks,
+    /// Hooks that already ran for the whole send (pre_send), noted in every record
+    pub hook_runs: &'a [HistoryHook],
+}
+
+/// Runs post_send for one receiver's outcome and appends its history record.
+pub async fn record_peer(peer: &PeerOutcome, context: &SendRecordContext<'_>) {
+    let (status, sent_items, sent_bytes) = match peer.error {
+        None => (
+            HistoryStatus::Success,
+            context.intended_items.to_vec(),
+            context.intended_bytes,
+        ),
+        Some(_) => (HistoryStatus::Failed, Vec::new(), 0),
+    };
+    let peer_username = match &peer.target {
+        SendTarget::User(username) => Some(username.clone()),
+        SendTarget::Address { .. } => None,
+    };
+    let mut record = build_history_record(
+        TransferHistoryRecord::unix_at(peer.started_at),
+        peer.duration.as_millis() as u64,
+        status,
+        peer.host.clone(),
+        peer_username,
+        context.description.clone(),
+        context.intended_items.to_vec(),
+        context.intended_bytes,
+        sent_items,
+        sent_bytes,
+    );
+    record.error = peer.error.as_ref().map(|e| e.with_causes());
+    record.attempts = (peer.attempts.len() > 1).then(|| {
+        peer.attempts
+            .iter()
+            .map(|attempt| HistoryAttempt {
+                attempt: attempt.attempt,
+                peer_addr: attempt.host.clone(),
+                duration_ms: attempt.duration.as_millis() as u64,
+                error: attempt.error.clone(),
+            })
+            .collect()
+    });
+    record.hooks = (!context.hook_runs.is_empty()).then(|| context.hook_runs.to_vec());
+    context
+        .hooks
+        .after_transfer(HookKind::PostSend, &mut record)
+        .await;
+    output::summary(&record, peer.error.as_ref().map(PxpError::code));
+    if let Err(e) = append_record(&record).await {
+        warn!("Failed to append history record for '{}': {:#}", peer.target, e);
+    } else {
+        info!("Successfully appended transfer history record.");
+        trace!("Appended record for '{}': {:?}", peer.target, record);
     }
 }
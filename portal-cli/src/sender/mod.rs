@@ -1,44 +1,87 @@
+mod exclude;
 mod history;
+mod report;
+mod retry;
+mod serve;
+mod share;
+mod watch;
+
+pub use serve::start_serve;
+pub use share::start_share;
+pub use watch::start_watch;
 
 use {
     crate::{
+        commands::SendArgs,
         config::models::PortalConfig,
         history::{
-            HistoryItem, HistoryItemKind, HistoryStatus, TransferHistoryRecord, append_record,
+            HistoryHook, HistoryItem, HistoryItemKind, HistoryStatus, TransferHistoryRecord,
+            append_record,
         },
-        progress::ProgressManager,
+        hooks::{HookKind, HookPayload, Hooks},
+        interactive,
+        output::{self, JsonEvent, JsonEvents},
+        progress::{ScanSpinner, Side},
         select::select_files_to_send,
+        tui,
     },
     anyhow::{Context, Result, anyhow},
-    history::build_history_record,
-    inquire::{Confirm, Text},
-    pxp::metadata::TransferItem,
-    std::{path::PathBuf, time::Instant},
-    tokio::net::TcpStream,
+    exclude::build_path_filter,
+    history::{SendRecordContext, build_history_record, record_peer},
+    inquire::Text,
+    pxp::{
+        EventSink, RetryPolicy, SendSession, Timeouts,
+        sender::{PathFilter, SendItem},
+        session::SendOutcome,
+    },
+    report::SendReport,
+    retry::build_retry_policy,
+    std::{
+        io::{IsTerminal, Read},
+        path::PathBuf,
+        sync::Arc,
+        time::{Duration, Instant},
+    },
     tracing::{debug, error, info, trace, warn},
 };
 
-pub async fn start_send(
-    file: &Option<Vec<PathBuf>>,
-    addr: &Option<String>,
-    port: &u16,
-    to: &Option<String>,
-    recursive: &bool,
-    no_compress: &bool,
-) -> Result<()> {
-    let mut peer_addr: Option<String> = None;
+/// Everything a send needs once its inputs are settled: what to send, where, and how.
+pub struct SendJob {
+    /// Receivers to find by username; empty when sending to `address` directly.
+    pub targets: Vec<String>,
+    pub address: Option<String>,
+    pub port: u16,
+    pub description: Option<String>,
+    /// Files and folders, scanned through `filter` once the send starts
+    pub paths: Vec<PathBuf>,
+    pub filter: PathFilter,
+    pub text: Option<String>,
+    pub no_compress: bool,
+    pub timeouts: Timeouts,
+    pub retry_policy: RetryPolicy,
+}
+
+pub async fn start_send(args: &SendArgs) -> Result<()> {
     let mut peer_username: Option<String> = None;
-    let mut start_ts_unix = 0u64;
-    let mut start_instant = Instant::now();
-    let mut intended_items: Vec<HistoryItem> = Vec::new();
-    let mut intended_bytes: u64 = 0;
-    let mut sent_items: Vec<HistoryItem> = Vec::new();
-    let mut actual_bytes: u64 = 0;
+    let mut description: Option<String> = None;
+    // Once the job is handed to `run_send`, it records every outcome in history itself.
+    let mut handed_off = false;
 
     let result: Result<()> = async {
-        let files = match file {
+        // Checked up front so an oversized text fails before any prompt.
+        let text = match &args.text {
+            Some(value) => {
+                let text = read_text(value)?;
+                pxp::sender::create_text_metadata(&text)?;
+                Some(text)
+            }
+            None => None,
+        };
+        let files = match &args.file {
             Some(path) => path.clone(),
+            None if text.is_some() => Vec::new(),
             None => {
+                interactive::ensure("list the files to send, or use --text")?;
                 if let Ok(Some(selected)) = select_files_to_send().await {
                     selected.clone()
                 } else {
@@ -48,77 +91,54 @@ pub async fn start_send(
             }
         };
 
-        trace!(
-            "Validating existence and type of {} input items",
-            files.len()
-        );
-        for file in &files {
-            if !file.exists() {
-                error!("Path does not exist: {:?}", file);
-                return Err(anyhow!(
-                    "File or directory '{}' does not exist",
-                    file.display()
-                ));
-            }
-            trace!("Verified path exists: {:?}", file);
-            if file.is_dir() {
-                if !recursive {
-                    warn!("Directory encountered without recursive flag: {:?}", file);
-                    return Err(anyhow!(
-                        "-r not specified; omitting directory '{}'",
-                        file.display(),
-                    ));
-                }
-                trace!("Path is a directory, recursive flag is set.");
-            }
-        }
+        check_paths(&files, args.recursive)?;
 
-        // --- Connection ---
-        let (target_ip, target_node_id, target_port) = if let Some(direct_addr) = addr {
-            info!("Using manual IP address override: {}", direct_addr);
-            (direct_addr.clone(), None, *port)
-        } else {
-            let target_username = match to {
-                Some(username) => username.clone(),
-                None => Text::new("Portal: Enter Receiver's username:")
-                    .prompt()
-                    .context("Failed to get username")?,
-            };
+        let path_filter = build_path_filter(&args.exclude).await?;
+        if !path_filter.is_empty() {
+            info!("Directory contents will be filtered before sending");
+        }
 
-            println!("Portal: Searching for receiver...: {}", target_username);
-            peer_username = Some(target_username.clone());
+        let mut timeouts = PortalConfig::load_timeouts().await?;
+        if let Some(secs) = args.discovery_timeout {
+            timeouts.discovery = Duration::from_secs(secs);
+        }
+        debug!("Session timeouts: {:?}", timeouts);
+        let retry_policy = build_retry_policy(&args.retry).await?;
 
-            let (ip, id, p) = pxp::sender::discover_receiver(&target_username, *port).await?;
-            (ip, Some(id), p)
+        let targets: Vec<String> = match &args.address {
+            Some(direct_addr) => {
+                info!("Using manual IP address override: {}", direct_addr);
+                Vec::new()
+            }
+            None if args.to.is_empty() => {
+                interactive::ensure("pass --to <USERNAME> or --address <IP>")?;
+                vec![
+                    Text::new("Portal: Enter Receiver's username:")
+                        .prompt()
+                        .context("Failed to get username")?,
+                ]
+            }
+            None => {
+                let mut targets = Vec::new();
+                for username in &args.to {
+                    if !targets.contains(username) {
+                        targets.push(username.clone());
+                    }
+                }
+                targets
+            }
         };
-
-        let r_addr = format!("{}:{}", target_ip, target_port);
-        peer_addr = Some(target_ip.clone());
-        println!("Portal: Connecting to {}...", r_addr);
-
-        let mut stream: TcpStream = pxp::sender::connect_to_receiver(
-            &target_ip,
-            target_port,
-            target_node_id.as_deref(),
-        )
-        .await?;
-
-        println!("Portal: Connection established!");
-        if target_node_id.is_some() {
-            println!("Portal: Verifying identity...");
-            println!("Portal: Identity verified. Starting transfer...");
-        } else {
-            println!(
-                "Portal: Connected to {} (Manual mode: Identity check skipped).",
-                target_ip
-            );
+        if let [username] = targets.as_slice() {
+            peer_username = Some(username.clone());
         }
 
         // --- Description ---
-        let user_desc = if Confirm::new("Portal: Add description for this transfer?")
-            .with_default(false)
-            .prompt()?
-        {
+        // Asked before connecting: the receiver only waits `handshake_timeout` for the
+        // manifest, and a retried attempt must not ask again.
+        description = if let Some(desc) = &args.description {
+            info!("Description given: \"{}\"", desc);
+            Some(desc.clone())
+        } else if interactive::confirm("Portal: Add description for this transfer?", false)? {
             let desc = Text::new("Portal: Enter transfer description:").prompt()?;
             info!("User added description: \"{}\"", desc);
             Some(desc)
@@ -127,182 +147,329 @@ pub async fn start_send(
             None
         };
 
-        // --- Build item list ---
-        info!("Building item list for transfer...");
-        let mut items_to_send: Vec<(PathBuf, TransferItem)> = Vec::new();
+        // Started once nothing is left to ask on the plain terminal.
+        let _screen = if args.tui {
+            Some(tui::start(Side::Sender)?)
+        } else {
+            None
+        };
+        handed_off = true;
+        run_send(SendJob {
+            targets,
+            address: args.address.clone(),
+            port: args.port,
+            description: description.clone(),
+            paths: files,
+            filter: path_filter,
+            text,
+            no_compress: args.no_compress,
+            timeouts,
+            retry_policy,
+        })
+        .await
+    }
+    .await;
 
-        for path in &files {
-            trace!("Preparing item: {:?}", path);
-            if path.is_dir() {
-                let dir_meta = pxp::sender::create_directory_metadata(path).await?;
-                items_to_send.push((path.clone(), TransferItem::Directory(dir_meta)));
-            } else {
-                let file_meta = pxp::sender::create_file_metadata(path).await?;
-                items_to_send.push((path.clone(), TransferItem::File(file_meta)));
-            }
-        }
-        debug!(
-            "Successfully collected {} top-level items for manifest",
-            items_to_send.len()
+    if let Err(ref e) = result
+        && !handed_off
+    {
+        debug!("Preparing failed transfer history record (failed before connecting)");
+        let mut record = build_history_record(
+            0,
+            0,
+            HistoryStatus::Failed,
+            None,
+            peer_username,
+            description,
+            Vec::new(),
+            0,
+            Vec::new(),
+            0,
         );
+        record.error = Some(format!("{:#}", e));
+        output::summary(&record, Some(output::error_code(e)));
+        if let Err(err) = append_record(&record).await {
+            warn!("Failed to append failed history record: {:#}", err);
+        } else {
+            info!("Successfully appended failed transfer history record.");
+            trace!("Appended failed record details: {:?}", record);
+        }
+    }
 
-        let (file_items, dir_items, calculated_bytes) =
-            items_to_send
-                .iter()
-                .fold((0u32, 0u32, 0u64), |(f, d, b), (_, item)| match item {
-                    TransferItem::File(fm) => (f + 1, d, b.saturating_add(fm.file_size)),
-                    TransferItem::Directory(dm) => (f, d + 1, b.saturating_add(dm.total_size)),
-                });
+    result
+}
+
+/// Scans the job's items and delivers them through a pxp send session: to one receiver
+/// with retries, or to several at once. Every outcome, success or failure, is recorded in
+/// history, one record per receiver.
+pub async fn run_send(job: SendJob) -> Result<()> {
+    let outcome = deliver(job).await?;
+    if outcome.peers.len() <= 1 {
+        return match outcome.peers.into_iter().next().and_then(|peer| peer.error) {
+            Some(e) => Err(e.into()),
+            None => Ok(()),
+        };
+    }
+    let failed = outcome.peers.iter().filter(|peer| peer.error.is_some()).count();
+    if failed > 0 {
+        return Err(anyhow!(
+            "{} of {} receivers did not get the transfer",
+            failed,
+            outcome.peers.len()
+        ));
+    }
+    info!("Fan-out send completed to {} receivers", outcome.peers.len());
+    Ok(())
+}
+
+/// Does the work of `run_send`, handing back how each receiver fared. Errors are only for
+/// failures before anything went out, which affect every receiver alike.
+pub async fn deliver(job: SendJob) -> Result<SendOutcome> {
+    let SendJob {
+        targets,
+        address,
+        port,
+        description,
+        paths,
+        filter,
+        text,
+        no_compress,
+        timeouts,
+        retry_policy,
+    } = job;
+    let peer_username = match targets.as_slice() {
+        [username] => Some(username.clone()),
+        _ => None,
+    };
+    let start_ts_unix = TransferHistoryRecord::now_unix();
+    let start_instant = Instant::now();
+    let mut intended_items: Vec<HistoryItem> = Vec::new();
+    let mut intended_bytes: u64 = 0;
+    let mut hooks = Hooks::default();
+    let mut hook_runs: Vec<HistoryHook> = Vec::new();
 
-        let sender_username = PortalConfig::load_all()
+    // Everything up to the network: anything failing here is one failed record.
+    let outcome: Result<SendOutcome> = async {
+        let cfg = PortalConfig::load_all()
             .await
-            .context("Failed to load sender user config")?
-            .user
-            .username;
+            .context("Failed to load sender user config")?;
+        hooks = Hooks::new(&cfg.hooks);
+        let sender_username = cfg.user.username;
         if sender_username.is_none() {
             warn!("Sender username not set; manifest will omit sender_username");
         } else {
             info!("Sender username loaded for manifest");
         }
 
-        // --- Create and send manifest ---
-        let compressed = !*no_compress;
-        let global_manifest = pxp::sender::create_global_transfer_manifest(
-            file_items,
-            dir_items,
-            calculated_bytes,
-            user_desc,
-            sender_username.clone(),
-            compressed,
-        )
-        .await?;
-
-        start_ts_unix = TransferHistoryRecord::now_unix();
-        start_instant = Instant::now();
-
-        pxp::sender::send_manifest(&mut stream, &global_manifest).await?;
-
-        info!("Global manifest delivered to receiver.");
-        println!(
-            "Portal: Transfer initialized ({} files, {} folders)",
-            file_items, dir_items
-        );
-
-        if let Some(d) = &global_manifest.description {
-            println!("Portal: Note: {}", d);
-            info!("Final manifest description: \"{}\"", d);
+        let report = Arc::new(SendReport::new(targets.len() > 1));
+        let events: Arc<dyn EventSink> = if output::is_json() {
+            Arc::new(JsonEvents::default())
+        } else {
+            report.clone()
+        };
+        let mut builder = SendSession::builder()
+            .fallback_port(port)
+            .description(description.clone())
+            .sender_username(sender_username)
+            .compression(!no_compress)
+            .timeouts(timeouts)
+            .retry(retry_policy)
+            .progress(tui::events(events));
+        builder = match &address {
+            Some(address) => builder.to_address(address.clone(), port),
+            None => targets
+                .iter()
+                .fold(builder, |builder, username| builder.to_user(username.clone())),
+        };
+        if let Some(text) = text {
+            builder = builder.text(text);
         }
 
-        let total_items = items_to_send.len();
-        println!("Portal: Preparing to send {} items(s)...", total_items);
-
-        // --- Progress + history tracking ---
-        let prog = ProgressManager::new();
-        debug!("Progress UI created for sender");
-        prog.set_total_items(total_items);
-
-        intended_items = Vec::with_capacity(items_to_send.len());
-        intended_bytes = 0;
-        for (_, item) in &items_to_send {
-            match item {
-                TransferItem::File(fm) => {
-                    intended_items.push(HistoryItem {
-                        name: fm.filename.clone(),
-                        bytes: fm.file_size,
-                        kind: HistoryItemKind::File,
-                    });
-                    intended_bytes = intended_bytes.saturating_add(fm.file_size);
-                }
-                TransferItem::Directory(dm) => {
-                    intended_items.push(HistoryItem {
-                        name: dm.dirname.clone(),
-                        bytes: dm.total_size,
-                        kind: HistoryItemKind::Directory,
-                    });
-                    intended_bytes = intended_bytes.saturating_add(dm.total_size);
-                }
-            }
+        // --- Build item list ---
+        // Every directory is walked exactly once here. The cached entry list is reused for
+        // the manifest totals, the progress totals and the stream itself, on every attempt.
+        info!("Building item list for transfer...");
+        let scanning =
+            !output::is_json() && !tui::is_active() && paths.iter().any(|p| p.is_dir());
+        let spinner = scanning.then(ScanSpinner::new);
+        if let Some(spinner) = &spinner {
+            builder = builder.scan_progress(spinner.clone());
         }
+        let session = builder.paths(paths).filter(filter).prepare().await;
+        if let Some(spinner) = &spinner {
+            spinner.finish();
+        }
+        let session = session?;
+
+        intended_items = to_history_items(session.items());
+        intended_bytes = session.manifest().total_bytes;
         debug!(
             "History tracker initialized: {} intended items, {} intended bytes",
             intended_items.len(),
             intended_bytes
         );
+        report.describe(session.manifest(), session.items().len());
+        output::emit(JsonEvent::manifest(session.manifest()));
+        tui::set_totals(session.items().len(), intended_bytes);
 
-        // Build sent_items from the items_to_send list (all will be sent if successful)
-        sent_items = intended_items.clone();
-        actual_bytes = intended_bytes;
-
-        // --- Send stream using core ---
-        pxp::sender::send_stream(
-            stream,
-            items_to_send,
-            *no_compress,
-            Some(&prog as &dyn pxp::TransferProgress),
-        )
-        .await?;
-
-        info!(
-            "SUCCESS: All {} items sent and stream flushed to {}",
-            total_items, r_addr
+        let peer_addr = address.as_ref().map(|address| format!("{}:{}", address, port));
+        tui::set_peer(&peer_addr.clone().unwrap_or_else(|| targets.join(", ")));
+        let payload = HookPayload::before_send(
+            &targets,
+            peer_addr.as_deref(),
+            description.as_deref(),
+            session.items(),
         );
+        if let Some(run) = hooks.run(HookKind::PreSend, &payload).await {
+            hook_runs.push(run.to_history());
+            if let Some(error) = run.error {
+                return Err(anyhow!("Send cancelled by the pre_send hook: {}", error));
+            }
+        }
 
-        prog.println("Portal: All file(s) have been sent successfully!");
+        if targets.len() > 1 {
+            say!(
+                "Portal: Sending to {} receivers: {}",
+                targets.len(),
+                targets.join(", ")
+            );
+        }
+        let outcome = session.run().await;
+        report.finish();
+        Ok(outcome)
+    }
+    .await;
 
-        let duration_ms = start_instant.elapsed().as_millis() as u64;
-        debug!(
-            "Preparing successful transfer history record (duration: {}ms)",
-            duration_ms
-        );
-        let record = build_history_record(
-            start_ts_unix,
-            duration_ms,
-            HistoryStatus::Success,
-            peer_addr.clone(),
-            peer_username.clone(),
-            global_manifest.description.clone(),
-            intended_items.clone(),
-            intended_bytes,
-            sent_items.clone(),
-            actual_bytes,
-        );
-        if let Err(e) = append_record(&record).await {
-            warn!("Failed to append history record: {:#}", e);
-        } else {
-            info!("Successfully appended transfer history record.");
-            trace!("Appended success record: {:?}", record);
+    let outcome = match outcome {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            let duration_ms = start_instant.elapsed().as_millis() as u64;
+            debug!(
+                "Preparing failed transfer history record (duration: {}ms)",
+                duration_ms
+            );
+            let mut record = build_history_record(
+                start_ts_unix,
+                duration_ms,
+                HistoryStatus::Failed,
+                None,
+                peer_username,
+                description,
+                intended_items,
+                intended_bytes,
+                Vec::new(),
+                0,
+            );
+            record.error = Some(format!("{:#}", e));
+            record.hooks = (!hook_runs.is_empty()).then_some(hook_runs);
+            hooks.after_transfer(HookKind::PostSend, &mut record).await;
+            output::summary(&record, Some(output::error_code(&e)));
+            if let Err(err) = append_record(&record).await {
+                warn!("Failed to append failed history record: {:#}", err);
+            } else {
+                info!("Successfully appended failed transfer history record.");
+                trace!("Appended failed record details: {:?}", record);
+            }
+            return Err(e);
         }
+    };
 
-        Ok(())
+    let context = SendRecordContext {
+        description,
+        intended_items: &intended_items,
+        intended_bytes,
+        hooks: &hooks,
+        hook_runs: &hook_runs,
+    };
+    let fan_out = outcome.peers.len() > 1;
+    for peer in &outcome.peers {
+        match (&peer.error, fan_out) {
+            (None, false) => say!("Portal: All file(s) have been sent successfully!"),
+            (None, true) => say!(
+                "Portal: [{}] All file(s) have been sent successfully!",
+                peer.target
+            ),
+            (Some(e), true) => say!("Portal: [{}] Failed: {}", peer.target, e),
+            // A single receiver's error is what the send fails with.
+            (Some(_), false) => {}
+        }
+        record_peer(peer, &context).await;
     }
-    .await;
+    Ok(outcome)
+}
 
-    if let Err(ref e) = result {
-        let duration_ms = start_instant.elapsed().as_millis() as u64;
-        debug!(
-            "Preparing failed transfer history record (duration: {}ms)",
-            duration_ms
-        );
-        let mut record = build_history_record(
-            start_ts_unix,
-            duration_ms,
-            HistoryStatus::Failed,
-            peer_addr,
-            peer_username,
-            None,
-            intended_items,
-            intended_bytes,
-            sent_items,
-            actual_bytes,
-        );
-        record.error = Some(format!("{:#}", e));
-        if let Err(err) = append_record(&record).await {
-            warn!("Failed to append failed history record: {:#}", err);
-        } else {
-            info!("Successfully appended failed transfer history record.");
-            trace!("Appended failed record details: {:?}", record);
+/// The text given with `--text`, or stdin when the value is `-`. A single trailing newline
+/// (as left by `echo` or a heredoc) is not part of the text.
+fn read_text(value: &str) -> Result<String> {
+    let text = if value == "-" {
+        let mut stdin = std::io::stdin();
+        if stdin.is_terminal() {
+            return Err(anyhow!(
+                "No text given. Pass it after --text or pipe it in: echo hi | portal send --text"
+            ));
+        }
+        let mut text = String::new();
+        stdin
+            .read_to_string(&mut text)
+            .context("Failed to read text from stdin")?;
+        if let Some(stripped) = text.strip_suffix('\n') {
+            let len = stripped.strip_suffix('\r').unwrap_or(stripped).len();
+            text.truncate(len);
         }
+        text
+    } else {
+        value.to_string()
+    };
+    if text.is_empty() {
+        return Err(anyhow!("The text to send is empty"));
     }
+    trace!("Text to send: {} bytes", text.len());
+    Ok(text)
+}
 
-    result
+/// Checks every input path exists, and that folders are only given with `-r`.
+fn check_paths(files: &[PathBuf], recursive: bool) -> Result<()> {
+    trace!(
+        "Validating existence and type of {} input items",
+        files.len()
+    );
+    for file in files {
+        if !file.exists() {
+            error!("Path does not exist: {:?}", file);
+            return Err(anyhow!(
+                "File or directory '{}' does not exist",
+                file.display()
+            ));
+        }
+        trace!("Verified path exists: {:?}", file);
+        if file.is_dir() {
+            if !recursive {
+                warn!("Directory encountered without recursive flag: {:?}", file);
+                return Err(anyhow!(
+                    "-r not specified; omitting directory '{}'",
+                    file.display(),
+                ));
+            }
+            trace!("Path is a directory, recursive flag is set.");
+        }
+    }
+    Ok(())
+}
+
+fn to_history_items(items: &[SendItem]) -> Vec<HistoryItem> {
+    items
+        .iter()
+        .map(|item| HistoryItem {
+            name: item.name().to_string(),
+            bytes: item.bytes(),
+            path: None,
+            kind: if item.is_dir() {
+                HistoryItemKind::Directory
+            } else if item.is_text() {
+                HistoryItemKind::Text
+            } else {
+                HistoryItemKind::File
+            },
+        })
+        .collect()
 }
@@ -0,0 +1,197 @@
+use {
+    crate::progress::{ProgressManager, ProgressSlot},
+    indicatif::HumanBytes,
+    pxp::{
+        EventSink, TransferEvent, TransferPhase, discovery::PxpBeacon,
+        metadata::GlobalTransferManifest,
+    },
+    std::sync::Mutex,
+    tracing::debug,
+};
+
+/// Prints how a send is going from the events of its pxp session. A send to one receiver
+/// gets step-by-step status lines; with several, each line is tagged with the receiver it
+/// is about and the steps every one of them goes through are left out.
+pub struct SendReport {
+    fan_out: bool,
+    progress: ProgressSlot,
+    state: Mutex<ReportState>,
+}
+
+#[derive(Default)]
+struct ReportState {
+    files: u32,
+    dirs: u32,
+    items: usize,
+    note: Option<String>,
+    /// The attempt in progress and how many there may be, for a single receiver
+    attempt: (u32, u32),
+    /// Receivers that accepted, for a fan-out send
+    accepted: usize,
+}
+
+impl SendReport {
+    pub fn new(fan_out: bool) -> Self {
+        Self {
+            fan_out,
+            progress: ProgressSlot::default(),
+            state: Mutex::new(ReportState::default()),
+        }
+    }
+
+    /// What is being sent, once the session has been prepared.
+    pub fn describe(&self, manifest: &GlobalTransferManifest, items: usize) {
+        if let Ok(mut state) = self.state.lock() {
+            state.files = manifest.total_files;
+            state.dirs = manifest.total_directories;
+            state.items = items;
+            state.note = manifest.description.clone();
+        }
+    }
+
+    /// Clears any progress bar still showing, e.g. after the last attempt failed.
+    pub fn finish(&self) {
+        self.progress.finish();
+    }
+
+    fn tag(&self, peer: &str, message: String) {
+        if self.fan_out {
+            say!("Portal: [{}] {}", peer, message);
+        } else {
+            say!("Portal: {}", message);
+        }
+    }
+
+    fn streaming(&self, state: &ReportState) {
+        let prog = if self.fan_out {
+            say!(
+                "Portal: Transfer initialized ({} files, {} folders) for {} receiver(s)",
+                state.files, state.dirs, state.accepted
+            );
+            if let Some(note) = &state.note {
+                say!("Portal: Note: {}", note);
+            }
+            ProgressManager::new().with_peers(state.accepted)
+        } else {
+            let (attempt, max_attempts) = state.attempt;
+            ProgressManager::new().with_attempt(attempt, max_attempts)
+        };
+        debug!("Progress UI created for sender");
+        prog.set_total_items(state.items);
+        self.progress.show(prog);
+    }
+}
+
+impl EventSink for SendReport {
+    fn emit(&self, event: TransferEvent) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        match event {
+            TransferEvent::AttemptStarted {
+                attempt,
+                max_attempts,
+                ..
+            } if !self.fan_out => {
+                state.attempt = (attempt, max_attempts);
+                if attempt > 1 {
+                    say!("Portal: Attempt {} of {}...", attempt, max_attempts);
+                }
+            }
+            TransferEvent::Discovering { peer } if !self.fan_out => {
+                say!("Portal: Searching for receiver...: {}", peer);
+            }
+            TransferEvent::Discovered { peer, receiver } => self.tag(
+                &peer,
+                format!(
+                    "Receiver found via {} discovery: {}",
+                    receiver.mode,
+                    describe_receiver(&receiver.beacon)
+                ),
+            ),
+            TransferEvent::Connecting { host, port, .. } if !self.fan_out => {
+                say!("Portal: Connecting to {}:{}...", host, port);
+            }
+            TransferEvent::Connected { host, verified, .. } if !self.fan_out => {
+                say!("Portal: Connection established!");
+                if verified {
+                    say!("Portal: Verifying identity...");
+                    say!("Portal: Identity verified. Starting transfer...");
+                } else {
+                    say!(
+                        "Portal: Connected to {} (Manual mode: Identity check skipped).",
+                        host
+                    );
+                }
+            }
+            TransferEvent::AwaitingAcceptance { peer } => {
+                self.tag(&peer, "Waiting for the receiver to accept...".into());
+            }
+            TransferEvent::Accepted { peer } => {
+                if self.fan_out {
+                    state.accepted += 1;
+                    self.tag(&peer, "Receiver accepted the transfer.".into());
+                } else {
+                    say!(
+                        "Portal: Transfer initialized ({} files, {} folders)",
+                        state.files, state.dirs
+                    );
+                    if let Some(note) = &state.note {
+                        say!("Portal: Note: {}", note);
+                    }
+                    say!("Portal: Preparing to send {} items(s)...", state.items);
+                }
+            }
+            TransferEvent::Phase(TransferPhase::Streaming) => self.streaming(&state),
+            TransferEvent::Phase(TransferPhase::Complete) => self.progress.finish(),
+            TransferEvent::AttemptFailed {
+                peer,
+                attempt,
+                max_attempts,
+                error,
+                retry_in,
+            } => {
+                self.progress.finish();
+                if self.fan_out {
+                    self.tag(
+                        &peer,
+                        format!(
+                            "Attempt {} of {} failed: {}. Retrying in {}s...",
+                            attempt,
+                            max_attempts,
+                            error,
+                            retry_in.as_secs_f32()
+                        ),
+                    );
+                } else {
+                    say!(
+                        "Portal: Attempt {} of {} failed: {}",
+                        attempt, max_attempts, error
+                    );
+                    say!("Portal: Retrying in {}s...", retry_in.as_secs_f32());
+                }
+            }
+            other => self.progress.emit(other),
+        }
+    }
+}
+
+/// One-line summary of the device behind a beacon, using whatever details it advertised.
+fn describe_receiver(beacon: &PxpBeacon) -> String {
+    let mut details = Vec::new();
+    if let Some(os) = &beacon.os {
+        details.push(os.clone());
+    }
+    if let Some(version) = &beacon.app_version {
+        details.push(format!("Portal {}", version));
+    }
+    if let Some(free) = beacon.free_space {
+        details.push(format!("{} free", HumanBytes(free)));
+    }
+    let name = beacon.device_name.as_deref().unwrap_or(&beacon.username);
+    if details.is_empty() {
+        name.to_string()
+    } else {
+        format!("{} ({})", name, details.join(", "))
+    }
+}
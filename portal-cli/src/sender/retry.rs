@@ -0,0 +1,27 @@
+use {
+    crate::{
+        commands::RetryArgs,
+        config::models::{PortalConfig, network::parse_retry_on},
+    },
+    anyhow::Result,
+    pxp::RetryPolicy,
+    std::time::Duration,
+    tracing::debug,
+};
+
+/// Resolves the retry policy for a send: command-line flags win over `[network]` config,
+/// which wins over pxp's defaults.
+pub async fn build_retry_policy(args: &RetryArgs) -> Result<RetryPolicy> {
+    let mut policy = PortalConfig::load_retry_policy().await?;
+    if let Some(retries) = args.retries {
+        policy.retries = retries;
+    }
+    if let Some(secs) = args.retry_backoff {
+        policy.initial_backoff = Duration::from_secs(secs);
+    }
+    if let Some(retry_on) = args.retry_on.as_deref() {
+        policy.retry_on = parse_retry_on(retry_on)?;
+    }
+    debug!("Retry policy resolved: {:?}", policy);
+    Ok(policy)
+}
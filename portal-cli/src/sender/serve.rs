@@ -0,0 +1,199 @@
+use {
+    super::{
+        check_paths, exclude::build_path_filter, history::build_history_record, to_history_items,
+    },
+    crate::{
+        commands::ServeArgs,
+        config::models::PortalConfig,
+        history::{HistoryStatus, TransferHistoryRecord, append_record},
+        progress::{ProgressManager, ScanSpinner},
+    },
+    anyhow::{Context, Result, anyhow},
+    gethostname::gethostname,
+    indicatif::HumanBytes,
+    pxp::{
+        Timeouts,
+        discovery::{DeviceInfo, ShareInfo},
+        metadata::{PullResponse, ShareListing},
+        sender::{PullSession, ScanProgress, SendItem, count_items},
+    },
+    std::time::Instant,
+    tracing::{debug, error, info, trace, warn},
+};
+
+/// Offers `args.paths` until interrupted. Getters are served one at a time; each fetch is
+/// recorded in history as a send, and a failed fetch doesn't stop the share.
+pub async fn start_serve(args: &ServeArgs) -> Result<()> {
+    info!("Portal: Initializing share...");
+    check_paths(&args.paths, args.recursive)?;
+    let path_filter = build_path_filter(&args.exclude).await?;
+    let spinner = args.paths.iter().any(|p| p.is_dir()).then(ScanSpinner::new);
+    let scan_progress = spinner.as_ref().map(|s| s as &dyn ScanProgress);
+    let items = pxp::sender::scan_paths(&args.paths, &path_filter, scan_progress).await;
+    if let Some(spinner) = &spinner {
+        spinner.finish();
+    }
+    let items = items?;
+
+    let full_cfg = PortalConfig::load_all()
+        .await
+        .context("Failed to load user config")?;
+    let username = full_cfg.user.username.clone().ok_or_else(|| {
+        error!("Attempted to serve without a username set");
+        anyhow!("No username found. Please run 'portal config set user.username <name>' first.")
+    })?;
+    let port = match args.port.or(full_cfg.network.default_port) {
+        Some(port) => port,
+        None => {
+            error!("Port missing in both CLI and config");
+            return Err(anyhow!("No port provided and config has no port set"));
+        }
+    };
+    let timeouts = full_cfg.network.timeouts();
+    debug!("Session timeouts: {:?}", timeouts);
+    let filter = full_cfg
+        .security
+        .sender_filter()
+        .context("Invalid [security] allow/deny list")?;
+    debug!("Getter filter: {:?}", filter);
+
+    let share = ShareInfo {
+        name: args.name.clone().unwrap_or_else(|| username.clone()),
+        code: pxp::sender::new_share_code(),
+    };
+    let listing = ShareListing {
+        share_name: share.name.clone(),
+        host_username: Some(username.clone()),
+        items: items.iter().map(SendItem::transfer_item).collect(),
+    };
+    let device = DeviceInfo {
+        device_name: gethostname().into_string().ok(),
+        app_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+        free_space: None,
+    };
+
+    let (_, _, total_bytes) = count_items(&items);
+    println!(
+        "Portal: Serving {} item(s), {} as '{}'",
+        items.len(),
+        HumanBytes(total_bytes),
+        share.name
+    );
+    match pxp::receiver::local_ip::get_local_ip().await {
+        Some(ip) => println!("Portal: Listening at {}:{}", ip, port),
+        None => println!("Portal: Listening on port {}", port),
+    }
+    println!("Portal: Share code: {}", share.code);
+    println!("Portal: Fetch with: portal get {}", share.code);
+    println!("Portal: Press Ctrl+C to stop sharing.");
+
+    let mut host = pxp::sender::open_share(port, username.clone(), device, share, listing).await?;
+    loop {
+        let session = host.next_request(&filter, &timeouts).await?;
+        let peer = session
+            .request
+            .username
+            .clone()
+            .unwrap_or_else(|| session.peer_addr.clone());
+        println!(
+            "Portal: {} asked for {} item(s)",
+            peer,
+            session.request.items.len()
+        );
+        match serve_pull(session, &items, &username, &timeouts).await {
+            Ok(()) => println!("Portal: Sent to {}. Waiting for the next getter...", peer),
+            Err(e) => {
+                warn!("Fetch by {} failed: {:#}", peer, e);
+                println!("Portal: Fetch by {} failed: {:#}", peer, e);
+                println!("Portal: Waiting for the next getter...");
+            }
+        }
+    }
+}
+
+/// Sends one getter the items it asked for and records the fetch in history.
+async fn serve_pull(
+    session: PullSession,
+    items: &[SendItem],
+    username: &str,
+    timeouts: &Timeouts,
+) -> Result<()> {
+    let start_ts_unix = TransferHistoryRecord::now_unix();
+    let start_instant = Instant::now();
+    let selected: Vec<SendItem> = session
+        .request
+        .items
+        .iter()
+        .map(|&index| items[index as usize].clone())
+        .collect();
+    let intended_items = to_history_items(&selected);
+    let (_, _, intended_bytes) = count_items(&selected);
+    let peer_addr = Some(session.peer_addr.clone());
+    let peer_username = session.request.username.clone();
+
+    let result = send_selected(session, selected, username, timeouts).await;
+
+    let (status, actual_items, actual_bytes) = match result {
+        Ok(()) => (HistoryStatus::Success, intended_items.clone(), intended_bytes),
+        Err(_) => (HistoryStatus::Failed, Vec::new(), 0),
+    };
+    let mut record = build_history_record(
+        start_ts_unix,
+        start_instant.elapsed().as_millis() as u64,
+        status,
+        peer_addr,
+        peer_username,
+        None,
+        intended_items,
+        intended_bytes,
+        actual_items,
+        actual_bytes,
+    );
+    record.error = result.as_ref().err().map(|e| format!("{:#}", e));
+    if let Err(e) = append_record(&record).await {
+        warn!("Failed to append history record: {:#}", e);
+    } else {
+        trace!("Appended fetch record: {:?}", record);
+    }
+    result
+}
+
+async fn send_selected(
+    session: PullSession,
+    selected: Vec<SendItem>,
+    username: &str,
+    timeouts: &Timeouts,
+) -> Result<()> {
+    let PullSession {
+        mut socket,
+        request,
+        ..
+    } = session;
+    let (files, dirs, bytes) = count_items(&selected);
+    let manifest = pxp::sender::create_global_transfer_manifest(
+        files,
+        dirs,
+        bytes,
+        None,
+        Some(username.to_string()),
+        request.compressed,
+    )
+    .await?;
+
+    pxp::sender::answer_pull(&mut socket, &PullResponse::Manifest(manifest)).await?;
+    pxp::sender::read_manifest_response(&mut socket, timeouts).await?;
+    info!("Getter accepted the manifest; streaming {} item(s)", selected.len());
+
+    let prog = ProgressManager::new();
+    prog.set_total_items(selected.len());
+    let sent = pxp::sender::send_stream(
+        socket,
+        selected,
+        !request.compressed,
+        timeouts,
+        Some(&prog as &dyn pxp::EventSink),
+    )
+    .await;
+    prog.finish();
+    Ok(sent?)
+}
@@ -0,0 +1,295 @@
+use {
+    super::{
+        check_paths, exclude::build_path_filter, history::build_history_record, to_history_items,
+    },
+    crate::{
+        commands::ShareArgs,
+        config::models::PortalConfig,
+        history::{HistoryStatus, TransferHistoryRecord, append_record},
+        progress::ScanSpinner,
+        web,
+    },
+    anyhow::{Context, Result, anyhow},
+    axum::{
+        Router,
+        body::{Body, Bytes},
+        extract::{ConnectInfo, Path, State},
+        http::{
+            StatusCode,
+            header::{CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_TYPE},
+        },
+        response::{IntoResponse, Response},
+        routing::get,
+    },
+    gethostname::gethostname,
+    indicatif::HumanBytes,
+    pxp::{
+        receiver::SenderFilter,
+        sender::{ScanProgress, ScannedDirectory, SendItem, count_items},
+    },
+    std::{
+        fs::File,
+        io::{self, BufWriter, Write},
+        net::SocketAddr,
+        sync::Arc,
+        time::Instant,
+    },
+    tokio::sync::mpsc,
+    tokio_stream::{StreamExt, wrappers::ReceiverStream},
+    tokio_util::io::ReaderStream,
+    tracing::{debug, info, trace, warn},
+    zip::{ZipWriter, write::SimpleFileOptions},
+};
+
+/// Chunks of a download waiting to be written to the browser.
+const DOWNLOAD_BUFFER: usize = 8;
+
+/// What the download page offers.
+struct WebShare {
+    token: String,
+    /// Who the page says the files are from
+    name: String,
+    items: Vec<SendItem>,
+    filter: SenderFilter,
+}
+
+/// Offers `args.paths` on a download page until interrupted. Files download as they are;
+/// folders are zipped while they download. Each download is recorded in history as a send.
+pub async fn start_share(args: &ShareArgs) -> Result<()> {
+    info!("Portal: Initializing web share...");
+    check_paths(&args.paths, args.recursive)?;
+    let path_filter = build_path_filter(&args.exclude).await?;
+    let spinner = args.paths.iter().any(|p| p.is_dir()).then(ScanSpinner::new);
+    let scan_progress = spinner.as_ref().map(|s| s as &dyn ScanProgress);
+    let items = pxp::sender::scan_paths(&args.paths, &path_filter, scan_progress).await;
+    if let Some(spinner) = &spinner {
+        spinner.finish();
+    }
+    let items = items?;
+
+    let full_cfg = PortalConfig::load_all()
+        .await
+        .context("Failed to load user config")?;
+    let port = match args.port.or(full_cfg.network.default_port) {
+        Some(port) => port,
+        None => return Err(anyhow!("No port provided and config has no port set")),
+    };
+    let filter = full_cfg
+        .security
+        .sender_filter()
+        .context("Invalid [security] allow/deny list")?;
+    debug!("Downloader filter: {:?}", filter);
+    let name = full_cfg
+        .user
+        .username
+        .clone()
+        .or_else(|| gethostname().into_string().ok())
+        .unwrap_or_else(|| "this device".into());
+
+    let (_, _, total_bytes) = count_items(&items);
+    let listener = web::bind(port).await?;
+    let state = Arc::new(WebShare {
+        token: web::new_token(),
+        name,
+        items,
+        filter,
+    });
+    let app = Router::new()
+        .route("/{token}", get(share_page))
+        .route("/{token}/{index}", get(download))
+        .with_state(state.clone());
+
+    say!(
+        "Portal: Sharing {} item(s), {}",
+        state.items.len(),
+        HumanBytes(total_bytes)
+    );
+    web::announce(port, &state.token).await;
+    say!("Portal: Press Ctrl+C to stop sharing.");
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn share_page(
+    State(state): State<Arc<WebShare>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(token): Path<String>,
+) -> Response {
+    if !web::token_matches(&token, &state.token) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    if !state.filter.permits(addr.ip(), None) {
+        return (StatusCode::FORBIDDEN, "This device may not download from here.").into_response();
+    }
+    let rows: String = state
+        .items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| {
+            format!(
+                "<li><a href=\"/{}/{}\">{}</a><span class=\"size\">{}</span></li>",
+                state.token,
+                index,
+                web::escape(&download_name(item)),
+                HumanBytes(item.bytes())
+            )
+        })
+        .collect();
+    let body = format!(
+        "<h2>Files from {}</h2><ul>{}</ul>",
+        web::escape(&state.name),
+        rows
+    );
+    web::page("download", &body).into_response()
+}
+
+async fn download(
+    State(state): State<Arc<WebShare>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path((token, index)): Path<(String, usize)>,
+) -> Response {
+    if !web::token_matches(&token, &state.token) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    if !state.filter.permits(addr.ip(), None) {
+        return (StatusCode::FORBIDDEN, "This device may not download from here.").into_response();
+    }
+    let Some(item) = state.items.get(index).cloned() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    say!("Portal: {} is downloading '{}'", addr.ip(), item.name());
+
+    let file_name = download_name(&item).replace(['"', '\\'], "_");
+    let mut response = Response::builder()
+        .header(CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file_name));
+    response = if item.is_dir() {
+        response.header(CONTENT_TYPE, "application/zip")
+    } else {
+        response
+            .header(CONTENT_TYPE, "application/octet-stream")
+            .header(CONTENT_LENGTH, item.bytes())
+    };
+    let (tx, rx) = mpsc::channel(DOWNLOAD_BUFFER);
+    tokio::spawn(deliver(item, tx, addr.ip().to_string()));
+    response
+        .body(Body::from_stream(ReceiverStream::new(rx)))
+        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+/// What an item downloads as: a file under its own name, a folder as a zip.
+fn download_name(item: &SendItem) -> String {
+    if item.is_dir() {
+        format!("{}.zip", item.name())
+    } else {
+        item.name().to_string()
+    }
+}
+
+/// Streams one item into a download's body and records the download in history.
+async fn deliver(item: SendItem, tx: mpsc::Sender<io::Result<Bytes>>, peer: String) {
+    let start_ts_unix = TransferHistoryRecord::now_unix();
+    let start_instant = Instant::now();
+    let result = match &item {
+        SendItem::File { path, .. } => stream_file(path, &tx).await,
+        SendItem::Directory(dir) => {
+            let (dir, zip_tx) = (dir.clone(), tx.clone());
+            tokio::task::spawn_blocking(move || zip_directory(&dir, zip_tx))
+                .await
+                .unwrap_or_else(|e| Err(anyhow!("Zipping stopped unexpectedly: {}", e)))
+        }
+        SendItem::Text { .. } => Err(anyhow!("Text can't be downloaded")),
+    };
+    if let Err(e) = &result {
+        // Ends the body with an error, so the browser sees the download fail.
+        let _ = tx.send(Err(io::Error::other(format!("{:#}", e)))).await;
+    }
+
+    let intended_items = to_history_items(std::slice::from_ref(&item));
+    let (status, actual_items, actual_bytes) = match result {
+        Ok(()) => {
+            say!("Portal: '{}' downloaded by {}", item.name(), peer);
+            (HistoryStatus::Success, intended_items.clone(), item.bytes())
+        }
+        Err(ref e) => {
+            warn!("Download of '{}' by {} failed: {:#}", item.name(), peer, e);
+            say!("Portal: Download of '{}' by {} failed: {:#}", item.name(), peer, e);
+            (HistoryStatus::Failed, Vec::new(), 0)
+        }
+    };
+    let mut record = build_history_record(
+        start_ts_unix,
+        start_instant.elapsed().as_millis() as u64,
+        status,
+        Some(peer),
+        None,
+        None,
+        intended_items,
+        item.bytes(),
+        actual_items,
+        actual_bytes,
+    );
+    record.error = result.err().map(|e| format!("{:#}", e));
+    if let Err(e) = append_record(&record).await {
+        warn!("Failed to append history record: {:#}", e);
+    } else {
+        trace!("Appended download record: {:?}", record);
+    }
+}
+
+async fn stream_file(path: &std::path::Path, tx: &mpsc::Sender<io::Result<Bytes>>) -> Result<()> {
+    let file = tokio::fs::File::open(path)
+        .await
+        .with_context(|| format!("Failed to open '{}'", path.display()))?;
+    let mut chunks = ReaderStream::new(file);
+    while let Some(chunk) = chunks.next().await {
+        tx.send(Ok(chunk?))
+            .await
+            .map_err(|_| anyhow!("The download was cancelled"))?;
+    }
+    Ok(())
+}
+
+/// Writes `dir` as a zip into a download's body as it goes, so nothing is built up on
+/// disk or in memory first. Runs on a blocking thread.
+fn zip_directory(dir: &ScannedDirectory, tx: mpsc::Sender<io::Result<Bytes>>) -> Result<()> {
+    let mut zip = ZipWriter::new_stream(BufWriter::new(BodyWriter(tx)));
+    let options = SimpleFileOptions::default();
+    for entry in &dir.entries {
+        if entry.is_dir {
+            zip.add_directory(entry.tar_path.as_str(), options)?;
+            continue;
+        }
+        // Streamed entries can't be rewritten afterwards, so big files need ZIP64 up front.
+        zip.start_file(
+            entry.tar_path.as_str(),
+            options.large_file(entry.size >= u64::from(u32::MAX)),
+        )?;
+        let path = dir.local_path(entry);
+        let mut file =
+            File::open(&path).with_context(|| format!("Failed to open '{}'", path.display()))?;
+        io::copy(&mut file, &mut zip)?;
+    }
+    zip.finish()?.flush()?;
+    debug!("Zipped '{}' for download", dir.meta.dirname);
+    Ok(())
+}
+
+/// Hands everything written to it to a download's body.
+struct BodyWriter(mpsc::Sender<io::Result<Bytes>>);
+
+impl Write for BodyWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .blocking_send(Ok(Bytes::copy_from_slice(buf)))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "the download was cancelled"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,368 @@
+use {
+    super::{
+        SendJob, deliver, exclude::build_path_filter, retry::build_retry_policy,
+    },
+    crate::{commands::WatchArgs, config::models::PortalConfig},
+    anyhow::{Context, Result, anyhow},
+    notify::{
+        Event, EventKind, RecursiveMode, Watcher,
+        event::ModifyKind,
+    },
+    pxp::{
+        RetryPolicy, Timeouts,
+        sender::{PathFilter, scan_directory},
+    },
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+        time::{Duration, UNIX_EPOCH},
+    },
+    tokio::{
+        fs::{canonicalize, create_dir_all, metadata, read_to_string, write},
+        sync::mpsc::UnboundedReceiver,
+        time::timeout,
+    },
+    tracing::{debug, info, trace, warn},
+};
+
+/// How long to wait before trying a failed batch again when nothing else changes.
+const RETRY_FAILED_AFTER: Duration = Duration::from_secs(30);
+
+/// What a top-level entry of the watched folder looked like when it was last sent. A
+/// folder's fingerprint covers everything inside it, so any change in it sends it again.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct Fingerprint {
+    bytes: u64,
+    files: u64,
+    modified_ms: u64,
+}
+
+/// Fingerprints of what every watch has sent, kept in `~/.portal/watch.json` and keyed by
+/// watched folder and receiver, so a restarted watch only sends what changed since and a
+/// receiver that missed a batch gets it later without the others getting it again.
+type WatchState = BTreeMap<String, BTreeMap<String, Fingerprint>>;
+
+/// One running watch: where it looks, where it sends, and how.
+struct WatchedFolder {
+    root: PathBuf,
+    /// Every receiver by name: the usernames, or `address:port` in direct mode
+    destinations: Vec<String>,
+    filter: PathFilter,
+    address: Option<String>,
+    port: u16,
+    no_compress: bool,
+    timeouts: Timeouts,
+    retry_policy: RetryPolicy,
+}
+
+/// Watches `args.dir` until interrupted. Whatever changed since the last run is sent
+/// straight away; after that, each burst of changes is sent as one transfer once it has
+/// been quiet for `args.debounce` seconds. Only the top-level entries that changed are
+/// sent, a changed folder as a whole.
+pub async fn start_watch(args: &WatchArgs) -> Result<()> {
+    info!("Portal: Initializing watch...");
+    let root = canonicalize(&args.dir)
+        .await
+        .with_context(|| format!("Folder '{}' does not exist", args.dir.display()))?;
+    if !root.is_dir() {
+        return Err(anyhow!("'{}' is not a folder", args.dir.display()));
+    }
+
+    let filter = build_path_filter(&args.exclude).await?;
+    let mut timeouts = PortalConfig::load_timeouts().await?;
+    if let Some(secs) = args.discovery_timeout {
+        timeouts.discovery = Duration::from_secs(secs);
+    }
+    debug!("Session timeouts: {:?}", timeouts);
+    let retry_policy = build_retry_policy(&args.retry).await?;
+
+    let mut targets: Vec<String> = Vec::new();
+    for username in &args.to {
+        if !targets.contains(username) {
+            targets.push(username.clone());
+        }
+    }
+    let destinations = match &args.address {
+        Some(address) => vec![format!("{}:{}", address, args.port)],
+        None => targets,
+    };
+    let watched = WatchedFolder {
+        root,
+        destinations,
+        filter,
+        address: args.address.clone(),
+        port: args.port,
+        no_compress: args.no_compress,
+        timeouts,
+        retry_policy,
+    };
+    let debounce = Duration::from_secs(args.debounce);
+
+    // Events are only a trigger: after each quiet period the folder is rescanned and
+    // compared with what was sent, so missed, duplicated or self-inflicted events
+    // (e.g. reads while sending) can't cause a wrong send.
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        let _ = tx.send(event);
+    })
+    .context("Failed to start watching for file changes")?;
+    watcher
+        .watch(&watched.root, RecursiveMode::Recursive)
+        .with_context(|| format!("Failed to watch '{}'", watched.root.display()))?;
+    info!("Watching {:?} for {:?}", watched.root, watched.destinations);
+
+    println!(
+        "Portal: Watching '{}' and sending changes to {}",
+        watched.root.display(),
+        watched.destinations.join(", ")
+    );
+    println!("Portal: Press Ctrl+C to stop watching.");
+
+    let mut failed = !send_batch(&watched).await;
+    loop {
+        let retry_after = failed.then_some(RETRY_FAILED_AFTER);
+        if !wait_for_change(&mut rx, retry_after).await? {
+            println!("Portal: Retrying the last batch...");
+        }
+        settle(&mut rx, debounce).await?;
+        failed = !send_batch(&watched).await;
+    }
+}
+
+/// Waits for a change that could affect what gets sent. Returns false if `retry_after`
+/// ran out first.
+async fn wait_for_change(
+    rx: &mut UnboundedReceiver<notify::Result<Event>>,
+    retry_after: Option<Duration>,
+) -> Result<bool> {
+    loop {
+        let next = match retry_after {
+            Some(wait) => match timeout(wait, rx.recv()).await {
+                Ok(next) => next,
+                Err(_) => return Ok(false),
+            },
+            None => rx.recv().await,
+        };
+        match next {
+            Some(Ok(event)) if is_relevant(&event) => {
+                trace!("Change detected: {:?}", event);
+                return Ok(true);
+            }
+            Some(Ok(event)) => trace!("Ignoring event: {:?}", event),
+            Some(Err(e)) => warn!("File watcher reported an error: {}", e),
+            None => return Err(anyhow!("The file watcher stopped unexpectedly")),
+        }
+    }
+}
+
+/// Keeps swallowing events until none has arrived for `debounce`, so a file that is still
+/// being written (or a folder still being copied in) is sent once, when it's done.
+async fn settle(
+    rx: &mut UnboundedReceiver<notify::Result<Event>>,
+    debounce: Duration,
+) -> Result<()> {
+    loop {
+        match timeout(debounce, rx.recv()).await {
+            Err(_) => return Ok(()),
+            Ok(None) => return Err(anyhow!("The file watcher stopped unexpectedly")),
+            Ok(Some(_)) => {}
+        }
+    }
+}
+
+/// Reads and permission changes don't change what gets sent.
+fn is_relevant(event: &Event) -> bool {
+    !matches!(
+        event.kind,
+        EventKind::Access(_) | EventKind::Modify(ModifyKind::Metadata(_))
+    )
+}
+
+/// Sends every receiver the top-level entries that changed since it last got them, and
+/// remembers what each one got. Returns false if any receiver missed the batch; it is
+/// tried again with the next change, or after `RETRY_FAILED_AFTER`.
+async fn send_batch(watched: &WatchedFolder) -> bool {
+    match try_send_batch(watched).await {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("Watch batch failed: {:#}", e);
+            println!("Portal: Batch failed: {:#}", e);
+            println!(
+                "Portal: Will try again on the next change (or in {}s).",
+                RETRY_FAILED_AFTER.as_secs()
+            );
+            false
+        }
+    }
+}
+
+impl WatchedFolder {
+    /// Where in `WatchState` what `destination` has been sent is kept.
+    fn key(&self, destination: &str) -> String {
+        format!("{} -> {}", self.root.display(), destination)
+    }
+}
+
+async fn try_send_batch(watched: &WatchedFolder) -> Result<()> {
+    let current = fingerprints(&watched.root, &watched.filter).await?;
+    let state = load_state().await?;
+    // Receivers that are missing the same entries share one transfer. Normally that is
+    // all of them; one that was offline for a batch gets the entries it missed on its own.
+    let mut batches: BTreeMap<Vec<String>, Vec<String>> = BTreeMap::new();
+    for destination in &watched.destinations {
+        let sent = state.get(&watched.key(destination));
+        let changed: Vec<String> = current
+            .iter()
+            .filter(|(name, (_, fingerprint))| {
+                sent.and_then(|s| s.get(*name)) != Some(fingerprint)
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        if !changed.is_empty() {
+            batches.entry(changed).or_default().push(destination.clone());
+        }
+    }
+    if batches.is_empty() {
+        debug!("Rescan found nothing new to send");
+        return Ok(());
+    }
+
+    let mut failed: Vec<String> = Vec::new();
+    for (changed, destinations) in batches {
+        println!(
+            "Portal: Sending {} changed item(s) to {}: {}",
+            changed.len(),
+            destinations.join(", "),
+            changed.join(", ")
+        );
+        let paths: Vec<PathBuf> = changed.iter().map(|name| current[name].0.clone()).collect();
+        let outcome = deliver(SendJob {
+            targets: match watched.address {
+                Some(_) => Vec::new(),
+                None => destinations.clone(),
+            },
+            address: watched.address.clone(),
+            port: watched.port,
+            description: None,
+            paths,
+            filter: watched.filter.clone(),
+            text: None,
+            no_compress: watched.no_compress,
+            timeouts: watched.timeouts,
+            retry_policy: watched.retry_policy,
+        })
+        .await;
+        let delivered: Vec<String> = match outcome {
+            Ok(outcome) => outcome
+                .peers
+                .iter()
+                .filter(|peer| peer.error.is_none())
+                .map(|peer| peer.target.to_string())
+                .collect(),
+            Err(e) => {
+                warn!("Batch for {} failed: {:#}", destinations.join(", "), e);
+                Vec::new()
+            }
+        };
+        failed.extend(
+            destinations
+                .iter()
+                .filter(|destination| !delivered.contains(destination))
+                .cloned(),
+        );
+
+        // Reload before saving so progress made meanwhile by another watch isn't lost.
+        let mut state = load_state().await?;
+        for destination in &delivered {
+            let sent = state.entry(watched.key(destination)).or_default();
+            sent.retain(|name, _| current.contains_key(name));
+            for name in &changed {
+                sent.insert(name.clone(), current[name].1);
+            }
+        }
+        save_state(&state).await?;
+    }
+
+    if !failed.is_empty() {
+        return Err(anyhow!("{} did not get the batch", failed.join(", ")));
+    }
+    println!("Portal: Batch sent. Watching for changes...");
+    Ok(())
+}
+
+/// Fingerprints every top-level entry of `root` that passes `filter`, keyed by name and
+/// paired with the entry's path on disk.
+async fn fingerprints(
+    root: &Path,
+    filter: &PathFilter,
+) -> Result<BTreeMap<String, (PathBuf, Fingerprint)>> {
+    let scan = scan_directory(root, filter, None).await?;
+    let mut result: BTreeMap<String, (PathBuf, Fingerprint)> = BTreeMap::new();
+    for entry in &scan.entries {
+        let Some(top) = entry.rel_path.components().next() else {
+            continue;
+        };
+        let name = top.as_os_str().to_string_lossy().to_string();
+        // Entries can vanish between the walk and the stat; the next rescan catches up.
+        let modified_ms = match metadata(scan.local_path(entry)).await {
+            Ok(meta) => meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_millis() as u64),
+            Err(e) => {
+                trace!("Could not stat {}: {}", entry.tar_path, e);
+                0
+            }
+        };
+        let (_, fingerprint) = result.entry(name).or_insert_with(|| {
+            let fingerprint = Fingerprint {
+                bytes: 0,
+                files: 0,
+                modified_ms: 0,
+            };
+            (root.join(top), fingerprint)
+        });
+        if !entry.is_dir {
+            fingerprint.files += 1;
+            fingerprint.bytes = fingerprint.bytes.saturating_add(entry.size);
+        }
+        fingerprint.modified_ms = fingerprint.modified_ms.max(modified_ms);
+    }
+    trace!("Fingerprinted {} top-level entries of {:?}", result.len(), root);
+    Ok(result)
+}
+
+async fn state_path() -> Result<PathBuf> {
+    let dir = PortalConfig::get_dir()
+        .await
+        .context("Could not determine portal directory")?;
+    Ok(dir.join("watch.json"))
+}
+
+async fn load_state() -> Result<WatchState> {
+    let path = state_path().await?;
+    match read_to_string(&path).await {
+        Ok(content) => serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(WatchState::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+async fn save_state(state: &WatchState) -> Result<()> {
+    let path = state_path().await?;
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)
+            .await
+            .context("Failed to create portal directory")?;
+    }
+    let content =
+        serde_json::to_string_pretty(state).context("Failed to serialize watch state")?;
+    write(&path, content)
+        .await
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    trace!("Watch state saved to {}", path.display());
+    Ok(())
+}
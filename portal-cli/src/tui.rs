@@ -0,0 +1,669 @@
+//! The full-screen view behind `--tui`: a pinned header with the peer, item X of Y, speed
+//! and ETA, a scrolling list of items with the status of each, and a log pane fed by
+//! status lines and tracing. Questions (conflicts, low space) open as dialogs on top, so
+//! nothing has to wait for the bars to be cleared first.
+//!
+//! The screen is drawn from its own thread and reads the state the rest of Portal writes
+//! through the free functions here, all of which do nothing while it isn't showing. When
+//! it closes, the status lines are printed again so the run leaves the usual transcript.
+
+use {
+    crate::progress::Side,
+    anyhow::{Context, Result, anyhow},
+    indicatif::{HumanBytes, HumanDuration},
+    pxp::{ConflictAction, EventSink, TransferEvent, TransferWarning},
+    ratatui::{
+        DefaultTerminal, Frame,
+        crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+        layout::{Constraint, Layout, Rect},
+        style::{Color, Modifier, Style, Stylize},
+        text::{Line, Span},
+        widgets::{Block, Clear, Gauge, List, ListItem, ListState, Paragraph},
+    },
+    std::{
+        collections::VecDeque,
+        io::{self, IsTerminal, Write},
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicBool, Ordering},
+        },
+        thread::JoinHandle,
+        time::{Duration, Instant},
+    },
+    tokio::sync::oneshot,
+    tracing::debug,
+};
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static STATE: Mutex<Option<State>> = Mutex::new(None);
+/// Lines logged since the last frame. Kept apart from `STATE` so logging never waits on
+/// (or, from the drawing thread, deadlocks with) a frame being drawn.
+static PENDING: Mutex<Vec<Printed>> = Mutex::new(Vec::new());
+
+/// How often the screen is redrawn, and how long a key press may wait.
+const TICK: Duration = Duration::from_millis(100);
+/// Lines the log pane keeps for scrolling back to.
+const LOG_LINES: usize = 500;
+
+/// Shows the full-screen view until the returned guard is dropped.
+pub fn start(side: Side) -> Result<TuiGuard> {
+    if !io::stdout().is_terminal() {
+        return Err(anyhow!("--tui needs a terminal to draw on"));
+    }
+    let terminal = ratatui::try_init().context("Failed to start the full-screen view")?;
+    if let Ok(mut state) = STATE.lock() {
+        *state = Some(State::new(side));
+    }
+    ACTIVE.store(true, Ordering::SeqCst);
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread = {
+        let stop = stop.clone();
+        std::thread::spawn(move || run(terminal, &stop))
+    };
+    debug!("Full-screen view started: {:?}", side);
+    Ok(TuiGuard {
+        stop,
+        thread: Some(thread),
+    })
+}
+
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Closes the full-screen view when dropped and prints the run's status lines again.
+pub struct TuiGuard {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for TuiGuard {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        close();
+    }
+}
+
+/// Adds a status line to the log pane. Used by `say!` while the view is showing.
+pub fn print(line: String) {
+    push(Printed::Status(line));
+}
+
+/// Names who the transfer is with, for the header.
+pub fn set_peer(peer: &str) {
+    with_state(|state| state.peer = Some(peer.to_string()));
+}
+
+/// What the whole transfer holds, once it is known, for the header's totals and ETA.
+pub fn set_totals(items: usize, bytes: u64) {
+    with_state(|state| {
+        state.total_items = items;
+        state.total_bytes = bytes;
+    });
+}
+
+/// Asks the user to pick one of `options`, with `details` shown above them. `None` when
+/// the dialog is dismissed or the view closes first.
+pub async fn choose(
+    title: String,
+    details: Vec<String>,
+    options: &[&'static str],
+) -> Option<&'static str> {
+    let kind = DialogKind::Choice {
+        options: options.to_vec(),
+        selected: 0,
+    };
+    match ask(title, details, kind).await? {
+        Answer::Choice(index) => options.get(index).copied(),
+        Answer::Text(_) => None,
+    }
+}
+
+/// Asks the user to type a line. `None` when the dialog is dismissed.
+pub async fn ask_text(title: String) -> Option<String> {
+    match ask(title, Vec::new(), DialogKind::Text(String::new())).await? {
+        Answer::Text(text) => Some(text),
+        Answer::Choice(_) => None,
+    }
+}
+
+/// Asks a yes/no question. A dismissed dialog counts as `default`.
+pub async fn confirm(title: String, default: bool) -> bool {
+    let kind = DialogKind::Choice {
+        options: vec!["Yes", "No"],
+        selected: if default { 0 } else { 1 },
+    };
+    match ask(title, Vec::new(), kind).await {
+        Some(Answer::Choice(index)) => index == 0,
+        _ => default,
+    }
+}
+
+async fn ask(title: String, details: Vec<String>, kind: DialogKind) -> Option<Answer> {
+    let (reply, answer) = oneshot::channel();
+    let dialog = Dialog {
+        title,
+        details,
+        kind,
+        reply,
+    };
+    with_state(|state| state.dialogs.push_back(dialog))?;
+    answer.await.ok().flatten()
+}
+
+/// Feeds a session's events to the view while it is showing, and passes them on to
+/// `inner`, which still prints the status lines.
+pub fn events(inner: Arc<dyn EventSink>) -> Arc<dyn EventSink> {
+    if is_active() {
+        Arc::new(TuiEvents { inner })
+    } else {
+        inner
+    }
+}
+
+struct TuiEvents {
+    inner: Arc<dyn EventSink>,
+}
+
+impl EventSink for TuiEvents {
+    fn emit(&self, event: TransferEvent) {
+        let note = with_state(|state| state.apply(&event)).flatten();
+        if let Some(note) = note {
+            print(note);
+        }
+        self.inner.emit(event);
+    }
+}
+
+/// Where tracing output for the terminal goes: stderr normally, the log pane while the view
+/// is showing, and nowhere for the one of the two that isn't in use.
+pub enum LogWriter {
+    Stderr(io::Stderr),
+    Pane(Vec<u8>),
+    Off,
+}
+
+/// The terminal log layer's writer.
+pub fn stderr() -> LogWriter {
+    if is_active() {
+        LogWriter::Off
+    } else {
+        LogWriter::Stderr(io::stderr())
+    }
+}
+
+/// The log pane layer's writer.
+pub fn pane() -> LogWriter {
+    if is_active() {
+        LogWriter::Pane(Vec::new())
+    } else {
+        LogWriter::Off
+    }
+}
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            LogWriter::Stderr(stderr) => stderr.write(buf),
+            LogWriter::Pane(line) => {
+                line.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            LogWriter::Off => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            LogWriter::Stderr(stderr) => stderr.flush(),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Drop for LogWriter {
+    fn drop(&mut self) {
+        if let LogWriter::Pane(line) = self {
+            for line in String::from_utf8_lossy(line).lines() {
+                push(Printed::Log(line.to_string()));
+            }
+        }
+    }
+}
+
+/// A line for the log pane, printed again when the view closes: status lines to stdout,
+/// log lines to stderr, as they would have been without it.
+enum Printed {
+    Status(String),
+    Log(String),
+}
+
+impl Printed {
+    fn text(&self) -> &str {
+        match self {
+            Printed::Status(line) | Printed::Log(line) => line,
+        }
+    }
+}
+
+fn push(line: Printed) {
+    if let Ok(mut pending) = PENDING.lock() {
+        pending.push(line);
+    }
+}
+
+fn with_state<T>(f: impl FnOnce(&mut State) -> T) -> Option<T> {
+    if !is_active() {
+        return None;
+    }
+    let mut state = STATE.lock().ok()?;
+    state.as_mut().map(f)
+}
+
+/// Draws until told to stop. Ctrl+C ends Portal from here: raw mode keeps it from
+/// reaching the process as a signal.
+fn run(mut terminal: DefaultTerminal, stop: &AtomicBool) {
+    while !stop.load(Ordering::SeqCst) {
+        let pending = match PENDING.lock() {
+            Ok(mut pending) => std::mem::take(&mut *pending),
+            Err(_) => Vec::new(),
+        };
+        if let Ok(mut state) = STATE.lock()
+            && let Some(state) = state.as_mut()
+        {
+            state.log(pending);
+            let _ = terminal.draw(|frame| draw(frame, state));
+        }
+
+        if !event::poll(TICK).unwrap_or(false) {
+            continue;
+        }
+        let Ok(Event::Key(key)) = event::read() else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            ratatui::restore();
+            close();
+            std::process::exit(130);
+        }
+        if let Ok(mut state) = STATE.lock()
+            && let Some(state) = state.as_mut()
+        {
+            state.key(key);
+        }
+    }
+    ratatui::restore();
+}
+
+/// Marks the view closed and prints what it showed as status and log lines.
+fn close() {
+    ACTIVE.store(false, Ordering::SeqCst);
+    let state = STATE.lock().ok().and_then(|mut state| state.take());
+    let pending = match PENDING.lock() {
+        Ok(mut pending) => std::mem::take(&mut *pending),
+        Err(_) => Vec::new(),
+    };
+    let transcript = state.map(|state| state.transcript).unwrap_or_default();
+    for line in transcript.into_iter().chain(pending) {
+        match line {
+            Printed::Status(line) => println!("{}", line),
+            Printed::Log(line) => eprintln!("{}", line),
+        }
+    }
+}
+
+struct State {
+    side: Side,
+    peer: Option<String>,
+    total_items: usize,
+    total_bytes: u64,
+    items: Vec<ItemRow>,
+    /// Bytes of every item so far, including the one in progress
+    bytes_done: u64,
+    /// When the first and the latest bytes moved, for the overall speed
+    started: Option<Instant>,
+    last_progress: Option<Instant>,
+    log: VecDeque<String>,
+    transcript: Vec<Printed>,
+    /// Questions waiting for an answer; the first one is showing
+    dialogs: VecDeque<Dialog>,
+}
+
+struct ItemRow {
+    name: String,
+    bytes: u64,
+    done: u64,
+    status: ItemStatus,
+}
+
+enum ItemStatus {
+    Active,
+    Done { verified: bool },
+    /// Handled as a conflict; the label says how
+    Resolved(&'static str),
+    Failed,
+}
+
+struct Dialog {
+    title: String,
+    details: Vec<String>,
+    kind: DialogKind,
+    reply: oneshot::Sender<Option<Answer>>,
+}
+
+enum DialogKind {
+    Choice {
+        options: Vec<&'static str>,
+        selected: usize,
+    },
+    Text(String),
+}
+
+enum Answer {
+    Choice(usize),
+    Text(String),
+}
+
+impl State {
+    fn new(side: Side) -> Self {
+        Self {
+            side,
+            peer: None,
+            total_items: 0,
+            total_bytes: 0,
+            items: Vec::new(),
+            bytes_done: 0,
+            started: None,
+            last_progress: None,
+            log: VecDeque::new(),
+            transcript: Vec::new(),
+            dialogs: VecDeque::new(),
+        }
+    }
+
+    /// Updates the header and item list for `event`. Returns a line for the log pane when
+    /// the event has nothing else to show it by.
+    fn apply(&mut self, event: &TransferEvent) -> Option<String> {
+        match event {
+            TransferEvent::ItemStarted {
+                index,
+                total,
+                name,
+                bytes,
+                ..
+            } => {
+                // A retried send starts over from its first item.
+                if *index <= self.items.len() {
+                    self.items.truncate(index.saturating_sub(1));
+                    self.bytes_done = self.items.iter().map(|item| item.done).sum();
+                }
+                self.total_items = *total;
+                self.items.push(ItemRow {
+                    name: name.clone(),
+                    bytes: *bytes,
+                    done: 0,
+                    status: ItemStatus::Active,
+                });
+            }
+            TransferEvent::Progress { bytes } => {
+                let now = Instant::now();
+                self.started.get_or_insert(now);
+                self.last_progress = Some(now);
+                self.bytes_done += bytes;
+                if let Some(item) = self.items.last_mut() {
+                    item.done += bytes;
+                }
+            }
+            TransferEvent::ItemFinished { verified, .. } => {
+                if let Some(item) = self.items.last_mut() {
+                    item.done = item.bytes;
+                    item.status = ItemStatus::Done {
+                        verified: *verified,
+                    };
+                }
+            }
+            TransferEvent::Conflict { name, action } => {
+                if let Some(item) = self.items.iter_mut().find(|item| &item.name == name) {
+                    item.status = ItemStatus::Resolved(resolution(action));
+                }
+            }
+            TransferEvent::AttemptFailed { .. } => {
+                if let Some(item) = self.items.last_mut()
+                    && matches!(item.status, ItemStatus::Active)
+                {
+                    item.status = ItemStatus::Failed;
+                }
+            }
+            TransferEvent::Warning(TransferWarning::EmptyDirectory { name }) => {
+                return Some(format!("Portal: Note: Directory '{}' is empty.", name));
+            }
+            TransferEvent::Warning(TransferWarning::Renamed { original, renamed }) => {
+                return Some(format!("Portal: '{}' is saved as '{}'", original, renamed));
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn log(&mut self, lines: Vec<Printed>) {
+        for line in lines {
+            self.log.push_back(line.text().to_string());
+            self.transcript.push(line);
+        }
+        while self.log.len() > LOG_LINES {
+            self.log.pop_front();
+        }
+    }
+
+    fn key(&mut self, key: KeyEvent) {
+        let Some(dialog) = self.dialogs.front_mut() else {
+            return;
+        };
+        let answer = match (&mut dialog.kind, key.code) {
+            (_, KeyCode::Esc) => Some(None),
+            (DialogKind::Choice { selected, .. }, KeyCode::Up | KeyCode::Char('k')) => {
+                *selected = selected.saturating_sub(1);
+                None
+            }
+            (DialogKind::Choice { options, selected }, KeyCode::Down | KeyCode::Char('j')) => {
+                *selected = (*selected + 1).min(options.len().saturating_sub(1));
+                None
+            }
+            (DialogKind::Choice { selected, .. }, KeyCode::Enter) => {
+                Some(Some(Answer::Choice(*selected)))
+            }
+            (DialogKind::Text(text), KeyCode::Enter) if !text.trim().is_empty() => {
+                Some(Some(Answer::Text(text.trim().to_string())))
+            }
+            (DialogKind::Text(text), KeyCode::Backspace) => {
+                text.pop();
+                None
+            }
+            (DialogKind::Text(text), KeyCode::Char(c)) => {
+                text.push(c);
+                None
+            }
+            _ => None,
+        };
+        if let Some(answer) = answer
+            && let Some(dialog) = self.dialogs.pop_front()
+        {
+            let _ = dialog.reply.send(answer);
+        }
+    }
+}
+
+/// How an item was handled as a conflict, in a word or two.
+fn resolution(action: &ConflictAction) -> &'static str {
+    match action {
+        ConflictAction::Overwrite | ConflictAction::OverwriteAll => "overwritten",
+        ConflictAction::Rename | ConflictAction::RenameAll | ConflictAction::RenameTo(_) => {
+            "renamed"
+        }
+        ConflictAction::Skip | ConflictAction::SkipAll => "skipped",
+        ConflictAction::KeepNewer => "kept newer",
+        ConflictAction::KeepLarger => "kept larger",
+        ConflictAction::SkipIfIdentical => "skipped if identical",
+        ConflictAction::MergeDirectories => "merged",
+        ConflictAction::ReplaceDirectory => "replaced",
+    }
+}
+
+fn draw(frame: &mut Frame, state: &State) {
+    let [header, items, log] = Layout::vertical([
+        Constraint::Length(5),
+        Constraint::Min(3),
+        Constraint::Percentage(35),
+    ])
+    .areas(frame.area());
+    draw_header(frame, header, state);
+    draw_items(frame, items, state);
+    draw_log(frame, log, state);
+    if let Some(dialog) = state.dialogs.front() {
+        draw_dialog(frame, dialog);
+    }
+}
+
+fn draw_header(frame: &mut Frame, area: Rect, state: &State) {
+    let block = Block::bordered()
+        .title(format!(" Portal: {} ", state.side.verb()).bold())
+        .border_style(Style::new().fg(Color::Magenta));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    let [peer, numbers, gauge] = Layout::vertical([Constraint::Length(1); 3]).areas(inner);
+
+    let peer_line = match (&state.peer, state.side) {
+        (Some(peer), Side::Sender) => format!("To {}", peer),
+        (Some(peer), Side::Receiver) => format!("From {}", peer),
+        (None, Side::Sender) => "Connecting...".to_string(),
+        (None, Side::Receiver) => "Waiting for a sender...".to_string(),
+    };
+    frame.render_widget(Paragraph::new(peer_line.bold()), peer);
+
+    let mut parts = Vec::new();
+    if state.total_items > 0 {
+        parts.push(format!(
+            "Item {} of {}",
+            state.items.len(),
+            state.total_items
+        ));
+    }
+    if state.total_bytes > 0 {
+        parts.push(format!(
+            "{} of {}",
+            HumanBytes(state.bytes_done),
+            HumanBytes(state.total_bytes)
+        ));
+    }
+    if let (Some(started), Some(last)) = (state.started, state.last_progress) {
+        let secs = last.duration_since(started).as_secs_f64();
+        let speed = if secs > 0.0 {
+            state.bytes_done as f64 / secs
+        } else {
+            0.0
+        };
+        parts.push(format!("{}/s", HumanBytes(speed as u64)));
+        if speed > 0.0 && state.total_bytes > state.bytes_done {
+            let left = (state.total_bytes - state.bytes_done) as f64 / speed;
+            parts.push(format!("ETA {}", HumanDuration(Duration::from_secs_f64(left))));
+        }
+    }
+    frame.render_widget(Paragraph::new(parts.join("  ·  ")), numbers);
+
+    let ratio = if state.total_bytes > 0 {
+        (state.bytes_done as f64 / state.total_bytes as f64).min(1.0)
+    } else {
+        0.0
+    };
+    let bar = Gauge::default()
+        .gauge_style(Style::new().fg(Color::Magenta))
+        .ratio(ratio);
+    frame.render_widget(bar, gauge);
+}
+
+fn draw_items(frame: &mut Frame, area: Rect, state: &State) {
+    let rows: Vec<ListItem> = state
+        .items
+        .iter()
+        .map(|item| {
+            let (mark, status) = match item.status {
+                ItemStatus::Active => {
+                    let percent = (item.done * 100).checked_div(item.bytes).unwrap_or(0);
+                    ("›".yellow(), format!("{}%", percent.min(100)))
+                }
+                ItemStatus::Done { verified: true } => ("✓".green(), "verified".to_string()),
+                ItemStatus::Done { verified: false } => {
+                    ("✓".green(), state.side.done().to_string())
+                }
+                ItemStatus::Resolved(label) => ("✓".cyan(), label.to_string()),
+                ItemStatus::Failed => ("✗".red(), "failed".to_string()),
+            };
+            ListItem::new(Line::from(vec![
+                mark,
+                Span::raw(format!(" {}  ", item.name)),
+                Span::raw(format!("{}  {}", HumanBytes(item.bytes), status)).dim(),
+            ]))
+        })
+        .collect();
+    // Following the item in progress keeps the list scrolled to it.
+    let mut list_state = ListState::default().with_selected(state.items.len().checked_sub(1));
+    let list = List::new(rows)
+        .block(Block::bordered().title(" Items "))
+        .highlight_style(Style::new().add_modifier(Modifier::BOLD));
+    frame.render_stateful_widget(list, area, &mut list_state);
+}
+
+fn draw_log(frame: &mut Frame, area: Rect, state: &State) {
+    let height = area.height.saturating_sub(2) as usize;
+    let lines: Vec<Line> = state
+        .log
+        .iter()
+        .skip(state.log.len().saturating_sub(height))
+        .map(|line| Line::raw(line.as_str()))
+        .collect();
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(" Log ")),
+        area,
+    );
+}
+
+fn draw_dialog(frame: &mut Frame, dialog: &Dialog) {
+    let mut lines: Vec<Line> = dialog.details.iter().map(|d| Line::raw(d.as_str())).collect();
+    if !lines.is_empty() {
+        lines.push(Line::default());
+    }
+    let hint = match &dialog.kind {
+        DialogKind::Choice { options, selected } => {
+            for (index, option) in options.iter().enumerate() {
+                lines.push(if index == *selected {
+                    Line::from(format!("> {}", option)).reversed()
+                } else {
+                    Line::from(format!("  {}", option))
+                });
+            }
+            "↑/↓ choose · Enter confirm · Esc cancel"
+        }
+        DialogKind::Text(text) => {
+            lines.push(Line::from(format!("> {}_", text)));
+            "Enter confirm · Esc cancel"
+        }
+    };
+    lines.push(Line::default());
+    lines.push(Line::from(hint).dim());
+
+    let height = lines.len() as u16 + 2;
+    let area = frame
+        .area()
+        .centered(Constraint::Percentage(70), Constraint::Length(height));
+    let block = Block::bordered()
+        .title(format!(" {} ", dialog.title).bold())
+        .border_style(Style::new().fg(Color::Yellow));
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
@@ -1,7 +1,6 @@
-use crate::progress::stream_download_with_spinner;
+use crate::{interactive, progress::stream_download_with_spinner};
 use anyhow::{Context, Error, Result};
 use flate2::read::GzDecoder;
-use inquire::Confirm;
 use {
     reqwest::blocking::Client,
     self_replace::self_replace,
@@ -124,9 +123,7 @@ pub async fn update_portal() -> Result<()> {
             new_version, current_v
         );
 
-        let proceed = Confirm::new("Portal: Do you want to update?")
-            .with_default(true)
-            .prompt()?;
+        let proceed = interactive::confirm("Portal: Do you want to update?", true)?;
 
         if !proceed {
             info!("User cancelled update to v{}", new_version);
@@ -0,0 +1,95 @@
+//! The built-in web server behind `portal receive --web` and `portal share`, so phones and
+//! anyone without Portal can still send or fetch files from a browser.
+//!
+//! Every page lives under a random token that is made fresh for each run and only shown
+//! in the printed link (and its QR code), so other devices on the network can't guess
+//! their way in.
+
+use {
+    anyhow::Result,
+    axum::response::Html,
+    pxp::PxpError,
+    qrcode::{QrCode, render::unicode::Dense1x2},
+    rand::random,
+    tokio::net::TcpListener,
+    tracing::{debug, warn},
+};
+
+const LAYOUT: &str = include_str!("layout.html");
+pub const UPLOAD_PAGE: &str = include_str!("upload.html");
+
+/// A new token for the link of one run.
+pub fn new_token() -> String {
+    format!("{:032x}", random::<u128>())
+}
+
+/// Compares a token from a URL with the run's own, taking the same time wherever they
+/// differ so the token can't be guessed byte by byte.
+pub fn token_matches(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Opens the web server's port on every interface.
+pub async fn bind(port: u16) -> Result<TcpListener> {
+    let listener = TcpListener::bind(("0.0.0.0", port))
+        .await
+        .map_err(|source| PxpError::BindFailed { port, source })?;
+    debug!("Web server bound to port {}", port);
+    Ok(listener)
+}
+
+/// Prints the link to the page at `token`, with a QR code a phone can scan to open it.
+pub async fn announce(port: u16, token: &str) {
+    let host = match pxp::receiver::local_ip::get_local_ip().await {
+        Some(ip) => ip,
+        None => {
+            warn!("Could not detect a local IP; the link uses localhost instead");
+            "localhost".to_string()
+        }
+    };
+    let url = format!("http://{}:{}/{}", host, port, token);
+    match QrCode::new(&url) {
+        Ok(code) => {
+            // Light modules on a dark terminal, so phones read it like a printed code.
+            let qr = code
+                .render::<Dense1x2>()
+                .dark_color(Dense1x2::Light)
+                .light_color(Dense1x2::Dark)
+                .quiet_zone(true)
+                .build();
+            say!("{}", qr);
+        }
+        Err(e) => warn!("Could not draw a QR code for the link: {}", e),
+    }
+    say!("Portal: Open {}", url);
+}
+
+/// A full page around `body`, which must already be escaped.
+pub fn page(title: &str, body: &str) -> Html<String> {
+    Html(
+        LAYOUT
+            .replace("{{title}}", &escape(title))
+            .replace("{{body}}", body),
+    )
+}
+
+/// Makes `text` safe to put in a page.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
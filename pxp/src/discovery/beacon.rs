@@ -1,15 +1,131 @@
 use {
-    crate::discovery::protocol::{DISCOVERY_PORT, MULTICAST_ADDR, PROTOCOL_NAME, PxpBeacon},
-    crate::error::Result,
+    crate::discovery::protocol::{
+        COMPAT_BEACON_SIZE, DISCOVERY_PORT, DeviceInfo, MULTICAST_ADDR, PROTOCOL_NAME,
+        PROTOCOL_VERSION, PxpBeacon, ReceiverStatus, ShareInfo,
+    },
+    crate::error::{PxpError, Result},
     network_interface::{Addr, NetworkInterface, NetworkInterfaceConfig},
     std::collections::BTreeSet,
     std::time::Duration,
-    tokio::net::UdpSocket,
-    tracing::{debug, info, trace, warn},
+    tokio::{net::UdpSocket, sync::watch, task::JoinHandle, time::sleep},
+    tracing::{debug, error, info, trace, warn},
 };
 
-pub async fn start_beacon(username: String, node_id: String, tcp_port: u16) -> Result<()> {
-    info!("Portal: Starting discovery beacon for '{}'", username);
+/// Time between consecutive beacons (`BEACON_INTERVAL` in PXP-DISCOVERY).
+const BEACON_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Controls a running discovery beacon. Dropping the handle stops the beacon.
+pub struct BeaconHandle {
+    status: watch::Sender<ReceiverStatus>,
+    task: JoinHandle<Result<()>>,
+}
+
+impl BeaconHandle {
+    /// Changes the advertised status. The new status goes out right away rather than on
+    /// the next tick, so senders stop picking a receiver as soon as it is taken.
+    pub fn set_status(&self, status: ReceiverStatus) {
+        self.status.send_replace(status);
+    }
+
+    /// Stops advertising altogether.
+    pub fn stop(self) {}
+
+    /// Resolves if the beacon task ends on its own, which only happens on a socket error.
+    pub(crate) async fn stopped(&mut self) -> PxpError {
+        match (&mut self.task).await {
+            Ok(Err(e)) => {
+                error!("Discovery beacon failed: {}", e);
+                e
+            }
+            _ => PxpError::BeaconStopped,
+        }
+    }
+}
+
+impl Drop for BeaconHandle {
+    fn drop(&mut self) {
+        debug!("Stopping discovery beacon");
+        self.task.abort();
+    }
+}
+
+/// Starts advertising this receiver in the background, initially as idle.
+pub fn spawn_beacon(
+    username: String,
+    node_id: String,
+    tcp_port: u16,
+    device: DeviceInfo,
+) -> BeaconHandle {
+    spawn(build_beacon(username, node_id, tcp_port, device))
+}
+
+/// Starts advertising a share that getters can fetch from, initially as idle. The beacon
+/// carries the share's name and code, so senders looking for `username` never pick it.
+pub fn spawn_share_beacon(
+    username: String,
+    node_id: String,
+    tcp_port: u16,
+    device: DeviceInfo,
+    share: ShareInfo,
+) -> BeaconHandle {
+    let mut beacon = build_beacon(username, node_id, tcp_port, device);
+    beacon.share_name = Some(share.name);
+    beacon.share_code = Some(share.code);
+    spawn(beacon)
+}
+
+fn build_beacon(username: String, node_id: String, tcp_port: u16, device: DeviceInfo) -> PxpBeacon {
+    PxpBeacon {
+        protocol: PROTOCOL_NAME.to_string(),
+        node_id,
+        username,
+        port: tcp_port,
+        device_name: device.device_name,
+        os: Some(std::env::consts::OS.to_string()),
+        app_version: device.app_version,
+        protocol_version: Some(PROTOCOL_VERSION),
+        status: Some(ReceiverStatus::Idle),
+        free_space: device.free_space,
+        share_name: None,
+        share_code: None,
+    }
+}
+
+fn spawn(mut beacon: PxpBeacon) -> BeaconHandle {
+    fit_beacon(&mut beacon);
+    let (status, status_rx) = watch::channel(ReceiverStatus::Idle);
+    let task = tokio::spawn(run_beacon(beacon, status_rx));
+    BeaconHandle { status, task }
+}
+
+/// Shortens `device_name` (dropping it if need be) until the beacon fits in
+/// `COMPAT_BEACON_SIZE`, so listeners with the older, smaller buffer can still read it.
+/// Nothing else is cut: a beacon still too big is sent as it is.
+fn fit_beacon(beacon: &mut PxpBeacon) {
+    loop {
+        let len = serde_json::to_vec(beacon).map_or(0, |msg| msg.len());
+        if len <= COMPAT_BEACON_SIZE {
+            return;
+        }
+        let Some(name) = beacon.device_name.as_mut() else {
+            warn!(
+                "Beacon is {} bytes, more than older senders can read ({})",
+                len, COMPAT_BEACON_SIZE
+            );
+            return;
+        };
+        let keep = name.len().saturating_sub(len - COMPAT_BEACON_SIZE);
+        let cut = (0..=keep).rev().find(|&i| name.is_char_boundary(i)).unwrap_or(0);
+        name.truncate(cut);
+        debug!("Trimmed the device name in the beacon to {} bytes", cut);
+        if name.is_empty() {
+            beacon.device_name = None;
+        }
+    }
+}
+
+async fn run_beacon(mut beacon: PxpBeacon, mut status: watch::Receiver<ReceiverStatus>) -> Result<()> {
+    info!("Portal: Starting discovery beacon for '{}'", beacon.username);
 
     // bind anywhere
     trace!("Binding discovery UDP socket to 0.0.0.0:0");
@@ -21,21 +137,15 @@ pub async fn start_beacon(username: String, node_id: String, tcp_port: u16) -> R
     trace!("Multicast target address set to: {}", multicast_target);
     debug!("Broadcast target addresses set to: {:?}", broadcast_targets);
 
-    let beacon = PxpBeacon {
-        protocol: PROTOCOL_NAME.to_string(),
-        node_id,
-        username,
-        port: tcp_port,
-    };
-
-    let msg = serde_json::to_vec(&beacon)?;
-    debug!(
-        "Discovery payload prepared ({} bytes): {:?}",
-        msg.len(),
-        beacon
-    );
-
     loop {
+        beacon.status = Some(*status.borrow_and_update());
+        let msg = serde_json::to_vec(&beacon)?;
+        trace!(
+            "Discovery payload prepared ({} bytes): {:?}",
+            msg.len(),
+            beacon
+        );
+
         trace!("Sending multicast discovery heartbeat...");
         socket.send_to(&msg, &multicast_target).await?;
 
@@ -46,7 +156,16 @@ pub async fn start_beacon(username: String, node_id: String, tcp_port: u16) -> R
             }
         }
 
-        tokio::time::sleep(Duration::from_secs(1)).await;
+        tokio::select! {
+            _ = sleep(BEACON_INTERVAL) => {}
+            changed = status.changed() => {
+                if changed.is_err() {
+                    // The handle is gone; nobody can be waiting for us any more.
+                    return Ok(());
+                }
+                debug!("Beacon status changed to {:?}", *status.borrow());
+            }
+        }
     }
 }
 
@@ -1,35 +1,174 @@
 use {
-    crate::discovery::protocol::{DISCOVERY_PORT, MULTICAST_ADDR, PROTOCOL_NAME, PxpBeacon},
-    crate::error::Result,
+    crate::discovery::protocol::{
+        DISCOVERY_PORT, MAX_BEACON_SIZE, MULTICAST_ADDR, PROTOCOL_NAME, PxpBeacon,
+    },
+    crate::error::{PxpError, Result},
     socket2::{Domain, Protocol, Socket, Type},
-    std::net::{Ipv4Addr, SocketAddr},
-    tokio::net::UdpSocket,
-    tracing::{debug, info, trace},
+    std::{
+        fmt,
+        net::{Ipv4Addr, SocketAddr},
+        sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+        },
+        time::Duration,
+    },
+    tokio::{net::UdpSocket, task::JoinSet, time::timeout},
+    tracing::{debug, error, info, trace, warn},
 };
 
-#[derive(Debug, Clone, Copy)]
+/// The discovery strategy that found a receiver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DiscoveryMode {
     Multicast,
     Broadcast,
 }
 
-// We split discovery into two stages: Multicast first, then Broadcast.
-// Multicast is preferred because it's cleaner and routed better on most modern networks.
-// However, some restrictive routers or VPNs block multicast traffic, so if it times out, 
-// we fall back to subnet broadcast as a brute-force backup to make sure we find the receiver.
+impl DiscoveryMode {
+    /// Every strategy the sender races when looking for a receiver.
+    pub const ALL: [DiscoveryMode; 2] = [DiscoveryMode::Multicast, DiscoveryMode::Broadcast];
+}
+
+impl fmt::Display for DiscoveryMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiscoveryMode::Multicast => write!(f, "multicast"),
+            DiscoveryMode::Broadcast => write!(f, "broadcast"),
+        }
+    }
+}
+
+/// What a discovery search is looking for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BeaconQuery {
+    /// A receiver advertising this username.
+    Receiver(String),
+    /// A share advertised under this name or code (see `spawn_share_beacon`).
+    Share(String),
+}
+
+impl BeaconQuery {
+    /// Whether `beacon` is the one being searched for, busy or not. Receivers and shares
+    /// never match each other's queries, even when they advertise the same username.
+    pub fn matches(&self, beacon: &PxpBeacon) -> bool {
+        match self {
+            BeaconQuery::Receiver(username) => !beacon.is_share() && beacon.username == *username,
+            BeaconQuery::Share(name_or_code) => {
+                beacon.share_name.as_deref() == Some(name_or_code.as_str())
+                    || beacon
+                        .share_code
+                        .as_deref()
+                        .is_some_and(|code| code.eq_ignore_ascii_case(name_or_code))
+            }
+        }
+    }
+}
 
-pub async fn find_receiver_multicast(target_username: &str) -> Result<(String, String, u16)> {
-    find_receiver(target_username, DiscoveryMode::Multicast).await
+impl fmt::Display for BeaconQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BeaconQuery::Receiver(username) => write!(f, "receiver '{}'", username),
+            BeaconQuery::Share(name_or_code) => write!(f, "share '{}'", name_or_code),
+        }
+    }
 }
 
-pub async fn find_receiver_broadcast(target_username: &str) -> Result<(String, String, u16)> {
-    find_receiver(target_username, DiscoveryMode::Broadcast).await
+/// A receiver (or share host) whose beacon matched what we were looking for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoveredReceiver {
+    pub ip: String,
+    /// Which strategy heard the beacon first.
+    pub mode: DiscoveryMode,
+    /// The matching beacon, including any device details the receiver advertised.
+    pub beacon: PxpBeacon,
 }
 
-async fn find_receiver(
-    target_username: &str,
+// Multicast is cleaner and routed better on most modern networks, but some restrictive
+// routers or VPNs block it, so subnet broadcast runs alongside it as a brute-force backup.
+// Each strategy listens on its own socket; the sender races them and takes the first match.
+
+/// Searches for a beacon matching `query`. Every strategy in `DiscoveryMode::ALL` listens at
+/// once and the first match wins; the rest are cancelled. Fails with `DiscoveryTimeout`
+/// carrying `timeout_hint` if nothing matches within `limit`, or `ReceiverBusy` if only busy
+/// matches were heard.
+pub(crate) async fn discover(
+    query: &BeaconQuery,
+    limit: Duration,
+    timeout_hint: String,
+) -> Result<DiscoveredReceiver> {
+    info!(
+        "Discovery started for {} (strategies: {:?}, timeout: {:?})",
+        query,
+        DiscoveryMode::ALL,
+        limit
+    );
+
+    let busy_seen = Arc::new(AtomicBool::new(false));
+    let mut strategies = JoinSet::new();
+    for mode in DiscoveryMode::ALL {
+        let query = query.clone();
+        let busy_seen = busy_seen.clone();
+        strategies.spawn(async move {
+            find_receiver(&query, mode, &busy_seen)
+                .await
+                .map_err(|e| (mode, e))
+        });
+    }
+
+    // A strategy that fails outright (e.g. multicast unsupported on this host) must not end
+    // the search while another one may still succeed.
+    let race = async {
+        let mut last_error = None;
+        while let Some(joined) = strategies.join_next().await {
+            match joined {
+                Ok(Ok(found)) => return Ok(found),
+                Ok(Err((mode, e))) => {
+                    warn!("{} discovery failed: {}", mode, e);
+                    last_error = Some(e);
+                }
+                Err(e) => warn!("Discovery task ended unexpectedly: {}", e),
+            }
+        }
+        Err(last_error)
+    };
+
+    match timeout(limit, race).await {
+        Ok(Ok(found)) => {
+            info!(
+                "Found {} at {}:{} via {} (Node ID: {})",
+                query, found.ip, found.beacon.port, found.mode, found.beacon.node_id
+            );
+            Ok(found)
+        }
+        Ok(Err(Some(e))) => {
+            error!("All discovery strategies failed for {}", query);
+            Err(e)
+        }
+        Ok(Err(None)) | Err(_) if busy_seen.load(Ordering::Relaxed) => {
+            warn!("{} stayed busy", query);
+            Err(PxpError::ReceiverBusy {
+                username: match query {
+                    BeaconQuery::Receiver(name) | BeaconQuery::Share(name) => name.clone(),
+                },
+            })
+        }
+        Ok(Err(None)) | Err(_) => {
+            warn!("Discovery timed out for {}", query);
+            Err(PxpError::DiscoveryTimeout {
+                message: timeout_hint,
+            })
+        }
+    }
+}
+
+/// Listens for a beacon matching `query` using one discovery strategy. Runs until an idle
+/// match is heard, so callers are expected to bound it with a timeout. Busy matches are
+/// skipped and flagged in `busy_seen`.
+pub async fn find_receiver(
+    query: &BeaconQuery,
     mode: DiscoveryMode,
-) -> Result<(String, String, u16)> {
+    busy_seen: &AtomicBool,
+) -> Result<DiscoveredReceiver> {
     trace!(
         "Creating raw UDP socket for {:?} discovery (port sharing enabled)",
         mode
@@ -47,7 +186,23 @@ async fn find_receiver(
         raw_socket.set_reuse_port(true)?;
     }
 
-    let address: SocketAddr = format!("0.0.0.0:{}", DISCOVERY_PORT).parse().map_err(|e: std::net::AddrParseError| crate::error::PxpError::Io(std::io::Error::new(std::io::ErrorKind::InvalidInput, e)))?;
+    let multicast_addr: Ipv4Addr = MULTICAST_ADDR.parse().map_err(|e: std::net::AddrParseError| crate::error::PxpError::Io(std::io::Error::new(std::io::ErrorKind::InvalidInput, e)))?;
+
+    // Both listeners share the discovery port, so keep each one to its own traffic. That way
+    // the reported mode says which path actually works on this network. Unix can bind to the
+    // group address to filter for it; Windows only allows binding to the wildcard address.
+    let bind_ip = match mode {
+        #[cfg(not(windows))]
+        DiscoveryMode::Multicast => multicast_addr,
+        _ => Ipv4Addr::UNSPECIFIED,
+    };
+    #[cfg(target_os = "linux")]
+    if mode == DiscoveryMode::Broadcast {
+        // Linux otherwise hands wildcard sockets every group joined anywhere on the host.
+        raw_socket.set_multicast_all_v4(false)?;
+    }
+
+    let address = SocketAddr::from((bind_ip, DISCOVERY_PORT));
     raw_socket.set_nonblocking(true)?;
     trace!("Binding discovery socket to {}", address);
     raw_socket.bind(&address.into())?;
@@ -56,12 +211,13 @@ async fn find_receiver(
     let socket = UdpSocket::from_std(std_socket)?;
 
     if let DiscoveryMode::Multicast = mode {
-        let multicast_addr: Ipv4Addr = MULTICAST_ADDR.parse().map_err(|e: std::net::AddrParseError| crate::error::PxpError::Io(std::io::Error::new(std::io::ErrorKind::InvalidInput, e)))?;
         trace!("Joining multicast group: {}", multicast_addr);
         socket.join_multicast_v4(multicast_addr, Ipv4Addr::UNSPECIFIED)?;
     }
 
-    let mut buf = [0u8; 1024];
+    // Beacons carry device details of any length, so nothing may be cut off and then fail
+    // to parse.
+    let mut buf = vec![0u8; MAX_BEACON_SIZE];
 
     trace!("Entering {:?} discovery loop, waiting for beacon...", mode);
     loop {
@@ -77,10 +233,20 @@ async fn find_receiver(
                 beacon.protocol, beacon.username
             );
             if beacon.protocol == PROTOCOL_NAME {
-                if beacon.username == target_username {
+                if query.matches(&beacon) && beacon.is_busy() {
+                    // Keep listening: the receiver goes back to idle (or a second device
+                    // with the same username answers) and we can still take it.
+                    if !busy_seen.swap(true, Ordering::Relaxed) {
+                        warn!(
+                            "{} at {} is busy with another transfer; waiting for it",
+                            query,
+                            remote_addr.ip()
+                        );
+                    }
+                } else if query.matches(&beacon) {
                     info!(
-                        "Portal: Found receiver '{}' at {} via {:?} discovery!",
-                        beacon.username,
+                        "Portal: Found {} at {} via {:?} discovery!",
+                        query,
                         remote_addr.ip(),
                         mode
                     );
@@ -92,18 +258,25 @@ async fn find_receiver(
                         mode
                     );
 
-                    return Ok((remote_addr.ip().to_string(), beacon.node_id, beacon.port));
+                    return Ok(DiscoveredReceiver {
+                        ip: remote_addr.ip().to_string(),
+                        mode,
+                        beacon,
+                    });
                 } else {
                     debug!(
-                        "Beacon username mismatch: expected '{}', got '{}'",
-                        target_username, beacon.username
+                        "Beacon mismatch: looking for {}, got '{}'",
+                        query, beacon.username
                     );
                 }
             } else {
                 trace!("Received non-portal beacon or version mismatch.");
             }
         } else {
-            trace!("Failed to deserialize incoming UDP packet as PxpBeacon.");
+            debug!(
+                "Ignoring a {}-byte UDP packet from {} that isn't a beacon",
+                len, remote_addr
+            );
         }
     }
 }
@@ -1,3 +1,9 @@
 pub mod beacon;
 pub mod listener;
 pub mod protocol;
+
+pub use {
+    beacon::{BeaconHandle, spawn_beacon, spawn_share_beacon},
+    listener::{BeaconQuery, DiscoveredReceiver, DiscoveryMode},
+    protocol::{DeviceInfo, PxpBeacon, ReceiverStatus, ShareInfo},
+};
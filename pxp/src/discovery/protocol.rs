@@ -3,11 +3,88 @@ use serde::{Deserialize, Serialize};
 pub const DISCOVERY_PORT: u16 = 5005;
 pub const MULTICAST_ADDR: &str = "224.0.0.123";
 pub const PROTOCOL_NAME: &str = "portal";
+/// Largest beacon a listener can take in: the most a UDP datagram can carry.
+pub const MAX_BEACON_SIZE: usize = 65_535;
+/// Largest beacon a -00 listener can take in. Beacons are trimmed to fit when they can be.
+pub const COMPAT_BEACON_SIZE: usize = 1024;
+/// PXP revision spoken by this implementation, advertised in `PxpBeacon::protocol_version`.
+pub const PROTOCOL_VERSION: u32 = 1;
 
-#[derive(Serialize, Deserialize, Debug)]
+/// The UDP datagram a receiver broadcasts while it waits for a sender.
+///
+/// Only the first four fields are required. The rest were added in PXP-DISCOVERY-01; they
+/// are omitted when unknown and default to `None` when missing, so older peers on either
+/// side keep working.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct PxpBeacon {
     pub protocol: String,
     pub node_id: String,
     pub username: String,
     pub port: u16,
+    /// Human-readable device name, usually the host name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_name: Option<String>,
+    /// Operating system, as reported by `std::env::consts::OS`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os: Option<String>,
+    /// Version of the application running the receiver.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol_version: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<ReceiverStatus>,
+    /// Bytes free in the receiver's download directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub free_space: Option<u64>,
+    /// Set by a host offering files for others to fetch (pull mode) instead of a receiver.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub share_name: Option<String>,
+    /// Short code a getter can use instead of `share_name`. Only sent alongside it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub share_code: Option<String>,
+}
+
+impl PxpBeacon {
+    /// Whether the receiver is already in a session. Beacons without a status are from
+    /// receivers that stop advertising once busy, so they count as idle.
+    pub fn is_busy(&self) -> bool {
+        self.status == Some(ReceiverStatus::Busy)
+    }
+
+    /// Whether the receiver answers a manifest with a `ManifestResponse`. Version 1 added
+    /// both the response and `protocol_version`, so a beacon without one is from a receiver
+    /// that starts reading the stream straight away.
+    pub fn answers_manifest(&self) -> bool {
+        self.protocol_version.is_some_and(|version| version >= 1)
+    }
+
+    /// Whether this beacon advertises a share rather than a receiver.
+    pub fn is_share(&self) -> bool {
+        self.share_name.is_some()
+    }
+}
+
+/// Whether a receiver (or share host) can take a new session.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReceiverStatus {
+    Idle,
+    Busy,
+}
+
+/// Optional details a receiver adds to its beacon. `os` and `protocol_version` are filled in
+/// by pxp itself; everything else comes from the application.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceInfo {
+    pub device_name: Option<String>,
+    pub app_version: Option<String>,
+    pub free_space: Option<u64>,
+}
+
+/// How a share host names what it offers in its beacon.
+#[derive(Debug, Clone)]
+pub struct ShareInfo {
+    pub name: String,
+    pub code: String,
 }
@@ -1,10 +1,10 @@
-use thiserror::Error;
+use {crate::metadata::RejectReason, std::fmt, thiserror::Error};
 
 /// Top-level error type for pxp operations.
 #[derive(Debug, Error)]
 pub enum PxpError {
     /// Network/IO errors
-    #[error("{0}")]
+    #[error(transparent)]
     Io(#[from] std::io::Error),
 
     /// Serialization errors (bincode)
@@ -27,6 +27,14 @@ pub enum PxpError {
     #[error("Discovery timed out: {message}")]
     DiscoveryTimeout { message: String },
 
+    /// The receiver was found but is busy with another session
+    #[error("'{username}' is busy with another transfer. Try again once it has finished.")]
+    ReceiverBusy { username: String },
+
+    /// A session phase made no progress within its configured time limit
+    #[error("Timed out during {phase}. The other device stopped responding.")]
+    Timeout { phase: TimeoutPhase },
+
     /// Discovery beacon stopped unexpectedly
     #[error("Discovery beacon stopped unexpectedly")]
     BeaconStopped,
@@ -59,6 +67,22 @@ pub enum PxpError {
     #[error("Blocked a potentially unsafe transfer: {0}")]
     Security(String),
 
+    /// The receiver's filesystem is too small for the transfer
+    #[error(
+        "Not enough free space on the receiving device: the transfer needs {}, but only {} is available.",
+        human_bytes(*needed),
+        human_bytes(*available)
+    )]
+    InsufficientSpace { needed: u64, available: u64 },
+
+    /// The receiver declined the transfer
+    #[error("The receiver declined the transfer: {0}")]
+    Rejected(String),
+
+    /// The receiver's allow/deny lists don't admit this sender
+    #[error("The other device does not accept transfers from this device or user.")]
+    NotAllowed,
+
     /// Conflict resolution error (from the consumer's resolver)
     #[error("Could not resolve a file naming conflict: {0}")]
     ConflictResolution(String),
@@ -74,6 +98,143 @@ pub enum PxpError {
     /// Walkdir error
     #[error("Could not read one of the files or folders you're trying to send: {0}")]
     WalkDir(String),
+
+    /// Invalid exclude/ignore glob pattern
+    #[error("Invalid exclude pattern {0}")]
+    Pattern(String),
+
+    /// Invalid sender allow/deny rule
+    #[error("Invalid access rule '{0}': expected an IP address, a CIDR range or a username")]
+    InvalidAccessRule(String),
+
+    /// A text item over the size limit for inline text
+    #[error(
+        "The text is {}, more than the {} that can be sent as text. Send it as a file instead.",
+        human_bytes(*len),
+        human_bytes(*limit)
+    )]
+    TextTooLarge { len: u64, limit: u64 },
+
+    /// A session was started without something it needs (a receiver, items to send)
+    #[error("The transfer can't start: {0}")]
+    InvalidSession(String),
+}
+
+/// The part of a session that stalled, carried by `PxpError::Timeout`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutPhase {
+    Connect,
+    Handshake,
+    /// Waiting for the receiver to accept the manifest, which may mean waiting on its user
+    Acceptance,
+    Transfer,
+}
+
+impl fmt::Display for TimeoutPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeoutPhase::Connect => write!(f, "connection setup"),
+            TimeoutPhase::Handshake => write!(f, "the handshake"),
+            TimeoutPhase::Acceptance => write!(f, "the wait for the receiver to accept"),
+            TimeoutPhase::Transfer => write!(f, "the transfer"),
+        }
+    }
+}
+
+impl PxpError {
+    /// Classifies an I/O error raised while the tar stream is moving. A stall detected by
+    /// the idle timeout becomes a typed `Timeout`; anything else is an archive failure.
+    pub(crate) fn from_stream_io(err: std::io::Error) -> Self {
+        if err.kind() == std::io::ErrorKind::TimedOut {
+            PxpError::Timeout {
+                phase: TimeoutPhase::Transfer,
+            }
+        } else {
+            PxpError::Archive(err.to_string())
+        }
+    }
+
+    /// Turns an I/O error that was already wrapped on the way up into a typed `Timeout`
+    /// when it came from the idle timeout. Every other error passes through unchanged.
+    pub(crate) fn stalled(self) -> Self {
+        match self {
+            PxpError::Io(err) if err.kind() == std::io::ErrorKind::TimedOut => PxpError::Timeout {
+                phase: TimeoutPhase::Transfer,
+            },
+            other => other,
+        }
+    }
+
+    /// A short, stable identifier for the kind of failure, e.g. `receiver_busy`, for
+    /// scripts that need to tell errors apart without parsing their messages.
+    pub fn code(&self) -> &'static str {
+        match self {
+            PxpError::Io(_) | PxpError::StripPrefix(_) => "io",
+            PxpError::Bincode(_)
+            | PxpError::Json(_)
+            | PxpError::Utf8(_)
+            | PxpError::Protocol(_) => "protocol",
+            PxpError::DiscoveryTimeout { .. } => "discovery_timeout",
+            PxpError::ReceiverBusy { .. } => "receiver_busy",
+            PxpError::Timeout { .. } => "timeout",
+            PxpError::BeaconStopped => "discovery_failed",
+            PxpError::ConnectionFailed { .. } => "connection_failed",
+            PxpError::IdentityMismatch { .. } => "identity_mismatch",
+            PxpError::BindFailed { .. } => "bind_failed",
+            PxpError::Security(_) => "policy_violation",
+            PxpError::InsufficientSpace { .. } => "insufficient_space",
+            PxpError::Rejected(_) => "rejected",
+            PxpError::NotAllowed => "not_allowed",
+            PxpError::ConflictResolution(_) => "conflict_unresolved",
+            PxpError::Compression(_) | PxpError::Archive(_) => "archive",
+            PxpError::WalkDir(_) | PxpError::Pattern(_) => "invalid_path",
+            PxpError::InvalidAccessRule(_) => "config",
+            PxpError::TextTooLarge { .. } => "text_too_large",
+            PxpError::InvalidSession(_) => "invalid_input",
+        }
+    }
+
+    /// The message followed by every underlying cause, e.g.
+    /// `Failed to connect to receiver at 10.0.0.2:7878: Connection refused (os error 111)`.
+    pub fn with_causes(&self) -> String {
+        let mut message = self.to_string();
+        let mut source = std::error::Error::source(self);
+        while let Some(cause) = source {
+            message = format!("{}: {}", message, cause);
+            source = cause.source();
+        }
+        message
+    }
+}
+
+impl From<RejectReason> for PxpError {
+    /// The error a peer sees when the other side turns its transfer down for `reason`.
+    fn from(reason: RejectReason) -> Self {
+        match reason {
+            RejectReason::InsufficientSpace { needed, available } => {
+                PxpError::InsufficientSpace { needed, available }
+            }
+            RejectReason::Declined(reason) => PxpError::Rejected(reason),
+            RejectReason::Policy(reason) => PxpError::Security(reason),
+            RejectReason::NotAllowed => PxpError::NotAllowed,
+        }
+    }
+}
+
+/// Formats a byte count for error messages, e.g. `1.5 GB`.
+fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
 }
 
 pub type Result<T> = std::result::Result<T, PxpError>;
@@ -0,0 +1,179 @@
+//! Typed events a transfer reports while it runs, so any frontend (a terminal, a GUI, a
+//! background service) can build its own view of it. The library itself never prints.
+
+use {
+    crate::{ConflictAction, discovery::DiscoveredReceiver},
+    std::{
+        io,
+        pin::Pin,
+        sync::Arc,
+        task::{Context, Poll},
+        time::Duration,
+    },
+    tokio::{
+        io::{AsyncRead, ReadBuf},
+        sync::mpsc::UnboundedSender,
+    },
+};
+
+/// What kind of top-level item an event is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+    File,
+    Directory,
+    Text,
+}
+
+/// How far a transfer has got.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferPhase {
+    /// The tar stream is being sent or received.
+    Streaming,
+    /// The stream is over and received items are being moved into place.
+    Reconciling,
+    /// The stream is flushed (sender) or every item has been placed (receiver).
+    Complete,
+}
+
+/// Something worth showing that doesn't stop the transfer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransferWarning {
+    /// A folder with nothing in it; only its structure is sent.
+    EmptyDirectory { name: String },
+    /// An incoming name isn't valid on this system and is saved as `renamed` instead.
+    Renamed { original: String, renamed: String },
+}
+
+/// One thing that happened during a transfer. Variants are only ever appended, so match
+/// with a catch-all arm.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransferEvent {
+    /// The transfer reached a new phase.
+    Phase(TransferPhase),
+    /// Top-level item `index` (1-based) of `total` started.
+    ItemStarted {
+        index: usize,
+        total: usize,
+        name: String,
+        kind: ItemKind,
+        bytes: u64,
+    },
+    /// `bytes` more of the current item went over the wire.
+    Progress { bytes: u64 },
+    /// The current item is fully sent or received. `verified` is set by the receiver once
+    /// the item (and everything inside a folder) matched its `.portal.meta` contract; the
+    /// sender has nothing to check it against.
+    ItemFinished {
+        name: String,
+        kind: ItemKind,
+        verified: bool,
+    },
+    Warning(TransferWarning),
+    /// A received item collided with an existing one and was handled with `action`.
+    Conflict { name: String, action: ConflictAction },
+    /// A session starts attempt `attempt` of `max_attempts` at reaching `peer` (a username,
+    /// or `host:port` when sending to an address).
+    AttemptStarted {
+        peer: String,
+        attempt: u32,
+        max_attempts: u32,
+    },
+    /// Looking for `peer` through discovery.
+    Discovering { peer: String },
+    /// Discovery found `peer`.
+    Discovered {
+        peer: String,
+        receiver: Box<DiscoveredReceiver>,
+    },
+    Connecting {
+        peer: String,
+        host: String,
+        port: u16,
+    },
+    /// Connected to `peer`. `verified` is set when it proved to be the device discovery
+    /// found; a direct address has nothing to check against.
+    Connected {
+        peer: String,
+        host: String,
+        verified: bool,
+    },
+    /// The manifest is with `peer`, which may be asking its user about it.
+    AwaitingAcceptance { peer: String },
+    Accepted { peer: String },
+    /// An attempt at `peer` failed with `error`; the next one starts after `retry_in`.
+    AttemptFailed {
+        peer: String,
+        attempt: u32,
+        max_attempts: u32,
+        error: String,
+        retry_in: Duration,
+    },
+}
+
+/// Where a transfer delivers its events. Called from inside the transfer, so it should
+/// return quickly; forward to a channel when the work is slow.
+///
+/// Implemented for tokio's `UnboundedSender<TransferEvent>`, for any `Fn(TransferEvent)`
+/// callback, and for an `Arc` of any sink.
+pub trait EventSink: Send + Sync {
+    fn emit(&self, event: TransferEvent);
+}
+
+impl EventSink for UnboundedSender<TransferEvent> {
+    fn emit(&self, event: TransferEvent) {
+        // Nobody listening any more is not a reason to stop the transfer.
+        let _ = self.send(event);
+    }
+}
+
+impl<F> EventSink for F
+where
+    F: Fn(TransferEvent) + Send + Sync,
+{
+    fn emit(&self, event: TransferEvent) {
+        self(event)
+    }
+}
+
+impl<T: EventSink + ?Sized> EventSink for Arc<T> {
+    fn emit(&self, event: TransferEvent) {
+        (**self).emit(event)
+    }
+}
+
+/// Sends `event` to `events`, if anyone is listening.
+pub(crate) fn emit(events: Option<&dyn EventSink>, event: TransferEvent) {
+    if let Some(sink) = events {
+        sink.emit(event);
+    }
+}
+
+/// Reports every byte read through it as a `Progress` event.
+pub(crate) struct Metered<'a, R> {
+    inner: R,
+    events: &'a dyn EventSink,
+}
+
+impl<'a, R> Metered<'a, R> {
+    pub(crate) fn new(inner: R, events: &'a dyn EventSink) -> Self {
+        Self { inner, events }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Metered<'_, R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        let read = buf.filled().len() - before;
+        if read > 0 {
+            self.events.emit(TransferEvent::Progress {
+                bytes: read as u64,
+            });
+        }
+        poll
+    }
+}
@@ -0,0 +1,105 @@
+//! Length-prefixed frames (u32 big-endian length, then payload) as used by the handshake and
+//! manifest phases, plus bounded bincode decoding.
+//!
+//! Every read takes a limit: the length prefix is checked before anything is allocated, so a
+//! peer can't make us reserve gigabytes by sending a large prefix. The limits below come from
+//! the spec drafts.
+
+use {
+    crate::error::{PxpError, Result},
+    bincode::Options,
+    serde::{Serialize, de::DeserializeOwned},
+    tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    tracing::{error, trace},
+};
+
+/// Largest identity proof (session ID) a sender accepts. PXP-HANDSHAKE.
+pub const MAX_IDENTITY_LEN: usize = 1024;
+/// Largest manifest a receiver accepts. PXP-MANIFEST.
+pub const MAX_MANIFEST_LEN: usize = 10 * 1024 * 1024;
+/// Largest manifest response a sender accepts: a few bytes plus an optional reason. PXP-MANIFEST.
+pub const MAX_RESPONSE_LEN: usize = 64 * 1024;
+/// Largest `.portal.meta` contract a receiver accepts: a path and a size. PXP-STREAMING.
+pub const MAX_META_LEN: usize = 64 * 1024;
+/// Largest text item either side accepts; anything bigger should be sent as a file.
+/// PXP-STREAMING.
+pub const MAX_TEXT_LEN: usize = 1024 * 1024;
+/// Largest share listing a getter accepts: one name and size per offered item. PXP-PULL.
+pub const MAX_LISTING_LEN: usize = 10 * 1024 * 1024;
+/// Largest pull request a share host accepts: a username and item indices. PXP-PULL.
+pub const MAX_PULL_REQUEST_LEN: usize = 1024 * 1024;
+
+/// Reads one frame, refusing any whose length prefix exceeds `limit`. `what` names the frame
+/// in the error.
+pub async fn read_frame<R>(reader: &mut R, limit: usize, what: &str) -> Result<Vec<u8>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut len_buf = [0u8; 4];
+    reader.read_exact(&mut len_buf).await?;
+    let len = u32::from_be_bytes(len_buf) as usize;
+    trace!("Incoming {} frame: {} bytes", what, len);
+    check_len(len, limit, what)?;
+
+    let mut buf = vec![0u8; len];
+    reader.read_exact(&mut buf).await?;
+    Ok(buf)
+}
+
+/// Writes `payload` as one frame. Does not flush.
+pub async fn write_frame<W>(writer: &mut W, payload: &[u8]) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let len = u32::try_from(payload.len())
+        .map_err(|_| PxpError::Protocol(format!("frame of {} bytes is too large", payload.len())))?;
+    writer.write_all(&len.to_be_bytes()).await?;
+    writer.write_all(payload).await?;
+    Ok(())
+}
+
+/// Reads a frame and decodes its bincode payload, both bounded by `limit`.
+pub async fn read_message<R, T>(reader: &mut R, limit: usize, what: &str) -> Result<T>
+where
+    R: AsyncRead + Unpin,
+    T: DeserializeOwned,
+{
+    let buf = read_frame(reader, limit, what).await?;
+    decode(&buf, limit)
+}
+
+/// Encodes `value` with bincode and writes it as one frame.
+pub async fn write_message<W, T>(writer: &mut W, value: &T) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+    T: Serialize + ?Sized,
+{
+    let encoded = bincode::serialize(value)?;
+    write_frame(writer, &encoded).await
+}
+
+/// Decodes a bincode payload with the same encoding as `bincode::deserialize`, but refuses to
+/// allocate more than `limit` bytes while doing so. A length field inside the payload (e.g. a
+/// string or vec length) can't then blow past the frame limit.
+pub fn decode<T: DeserializeOwned>(bytes: &[u8], limit: usize) -> Result<T> {
+    Ok(bincode::options()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(limit as u64)
+        .deserialize(bytes)?)
+}
+
+/// Errors with a protocol violation when a declared length is over `limit`.
+pub(crate) fn check_len(len: usize, limit: usize, what: &str) -> Result<()> {
+    if len > limit {
+        error!(
+            "Refusing {} of {} bytes (limit is {} bytes)",
+            what, len, limit
+        );
+        return Err(PxpError::Protocol(format!(
+            "{} of {} bytes exceeds the {} byte limit",
+            what, len, limit
+        )));
+    }
+    Ok(())
+}
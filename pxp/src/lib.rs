@@ -10,53 +10,93 @@
 //! - [PXP-HANDSHAKE](https://github.com/Spectra010s/portal/blob/main/spec/draft-pxp-handshake-00.md)
 //! - [PXP-MANIFEST](https://github.com/Spectra010s/portal/blob/main/spec/draft-pxp-manifest-00.md)
 //! - [PXP-STREAMING](https://github.com/Spectra010s/portal/blob/main/spec/draft-pxp-streaming-00.md)
+//! - [PXP-PULL](https://github.com/Spectra010s/portal/blob/main/spec/draft-pxp-pull-00.md)
 
 pub mod error;
 pub mod discovery;
+pub mod event;
+pub mod frame;
 pub mod metadata;
+pub mod net;
 pub mod receiver;
+pub mod retry;
 pub mod sender;
+pub mod session;
 
-pub use error::{PxpError, Result};
+pub use error::{PxpError, Result, TimeoutPhase};
+pub use event::{EventSink, ItemKind, TransferEvent, TransferPhase, TransferWarning};
+pub use net::Timeouts;
+pub use receiver::conflict::{ConflictInfo, ConflictSide};
+pub use retry::{RetryOn, RetryPolicy};
+pub use session::{ReceiveSession, SendSession};
 
-use tokio::io::{AsyncRead, AsyncWrite};
-
-/// Trait for tracking progress of individual file/directory items during transfer.
-pub trait ItemProgress: Send + Sync {
-    /// Wrap an async reader with progress tracking.
-    fn wrap_read(
-        &self,
-        reader: Box<dyn AsyncRead + Unpin + Send>,
-    ) -> Box<dyn AsyncRead + Unpin + Send>;
-    /// Wrap an async writer with progress tracking.
-    fn wrap_write(
-        &self,
-        writer: Box<dyn AsyncWrite + Unpin + Send>,
-    ) -> Box<dyn AsyncWrite + Unpin + Send>;
-    /// Signal completion of this item's progress tracking.
-    fn finish_and_clear(&self);
-}
-
-/// Trait for managing overall transfer progress across multiple items.
-pub trait TransferProgress: Send + Sync {
-    fn set_total_items(&self, total: usize);
-    fn set_current_item(&self, current: usize, total: usize);
-    fn create_item_progress(&self, name: &str, total_bytes: u64) -> Box<dyn ItemProgress>;
-    fn println(&self, msg: &str);
-}
+use std::{future::Future, pin::Pin};
 
 /// Action to take when a file conflict occurs during receive.
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub enum ConflictAction {
+    /// Replace the existing copy. Folders are merged: files in both are replaced and files
+    /// only in the existing folder are kept.
     Overwrite,
     OverwriteAll,
     Rename,
     RenameAll,
     Skip,
     SkipAll,
+    /// Keep whichever copy was modified more recently. A received folder that wins is
+    /// merged in as with `Overwrite`.
+    KeepNewer,
+    /// Keep whichever copy is larger; the existing one wins a tie. A received folder that
+    /// wins is merged in as with `Overwrite`.
+    KeepLarger,
+    /// Skip the received copy if its contents match the existing one, otherwise keep both
+    /// as `Rename` would.
+    SkipIfIdentical,
+    /// Save the received copy under this name, next to the existing one. A name that is
+    /// taken as well gets a number added, as with `Rename`.
+    RenameTo(String),
+    /// Move the received folder's contents into the existing folder, asking about every
+    /// file that is in both and leaving the rest alone. Acts as `Overwrite` unless both are
+    /// folders.
+    MergeDirectories,
+    /// Delete the existing folder and put the received one in its place, so files only in
+    /// the existing folder are lost.
+    ReplaceDirectory,
 }
 
-/// Trait for resolving file name conflicts during receive.
+/// A boxed future, as returned by the async methods of the traits consumers implement.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Trait for resolving file name conflicts during receive. Called from the async reconcile,
+/// so an implementation that waits on a person should do so without blocking the runtime
+/// (e.g. via `tokio::task::spawn_blocking`).
+///
+/// ```no_run
+/// use pxp::{BoxFuture, ConflictAction, ConflictInfo, ConflictResolver};
+///
+/// struct KeepNewest;
+///
+/// impl ConflictResolver for KeepNewest {
+///     fn resolve<'a>(
+///         &'a self,
+///         conflict: &'a ConflictInfo,
+///     ) -> BoxFuture<'a, pxp::Result<ConflictAction>> {
+///         Box::pin(async move {
+///             if conflict.identical().await? {
+///                 Ok(ConflictAction::Skip)
+///             } else {
+///                 Ok(ConflictAction::KeepNewer)
+///             }
+///         })
+///     }
+/// }
+/// ```
 pub trait ConflictResolver: Send + Sync {
-    fn resolve(&self, item_name: &str) -> crate::error::Result<ConflictAction>;
+    fn resolve<'a>(&'a self, conflict: &'a ConflictInfo) -> BoxFuture<'a, Result<ConflictAction>>;
+}
+
+/// Trait for choosing where each received top-level item is saved. Consulted once per item
+/// during reconcile, before any conflict check; `None` keeps the transfer's target dir.
+pub trait ItemRouter: Send + Sync {
+    fn route(&self, item_name: &str, bytes: u64, is_dir: bool) -> Option<std::path::PathBuf>;
 }
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GlobalTransferManifest {
     pub total_files: u32,
     pub total_directories: u32,
@@ -10,6 +10,54 @@ pub struct GlobalTransferManifest {
     pub compressed: bool,
 }
 
+/// The receiver's answer to a manifest, sent back before any data is streamed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum ManifestResponse {
+    Accept,
+    Reject(RejectReason),
+}
+
+/// Why a receiver turned a transfer down. New reasons are only ever appended, so the
+/// bincode variant indices of existing ones never change.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum RejectReason {
+    /// The target filesystem can't hold `needed` bytes.
+    InsufficientSpace { needed: u64, available: u64 },
+    /// Declined for a reason given as text.
+    Declined(String),
+    /// The transfer breaks the receiver's security policy (size, count or file type limits).
+    Policy(String),
+    /// The receiver's allow/deny lists don't admit this sender.
+    NotAllowed,
+}
+
+/// What a share host offers, sent to every getter right after the identity proof.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ShareListing {
+    pub share_name: String,
+    pub host_username: Option<String>,
+    /// The top-level items on offer, in the order getters refer to them by index.
+    pub items: Vec<TransferItem>,
+}
+
+/// A getter's choice from a `ShareListing`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PullRequest {
+    pub username: Option<String>,
+    /// Indices into `ShareListing::items`; at least one.
+    pub items: Vec<u32>,
+    /// Whether the getter wants the stream gzip-compressed.
+    pub compressed: bool,
+}
+
+/// The host's answer to a `PullRequest`. On `Manifest`, the transfer carries on exactly as a
+/// push would, with the getter answering the manifest.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum PullResponse {
+    Manifest(GlobalTransferManifest),
+    Refused(RejectReason),
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct FileMetadata {
     pub filename: String,
@@ -22,10 +70,21 @@ pub struct DirectoryMetadata {
     pub total_size: u64,
 }
 
+/// A short piece of text (a URL, a token, a snippet) carried inline rather than as a file.
+/// `name` is only used if the receiver chooses to save it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TextMetadata {
+    pub name: String,
+    pub byte_len: u64,
+}
+
+/// New kinds are only ever appended, so the bincode variant indices of existing ones never
+/// change.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum TransferItem {
     File(FileMetadata),
     Directory(DirectoryMetadata),
+    Text(TextMetadata),
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -40,6 +99,15 @@ pub struct ReceivedItem {
     pub name: String,
     pub bytes: u64,
     pub is_directory: bool,
+    /// The text itself for a text item. Text is kept in memory, not written to the target dir.
+    pub text: Option<String>,
+}
+
+/// An incoming path that was saved under a different name to be safe on this system.
+#[derive(Debug, Clone)]
+pub struct RenamedEntry {
+    pub original: String,
+    pub renamed: String,
 }
 
 /// Summary of items received during a transfer.
@@ -47,4 +115,6 @@ pub struct ReceivedItem {
 pub struct ReceiveSummary {
     pub items: Vec<ReceivedItem>,
     pub total_bytes: u64,
+    /// Paths that were renamed by filename sanitization, in arrival order.
+    pub renamed: Vec<RenamedEntry>,
 }
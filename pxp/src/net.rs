@@ -0,0 +1,191 @@
+use {
+    crate::error::{PxpError, Result, TimeoutPhase},
+    socket2::{SockRef, TcpKeepalive},
+    std::{
+        future::Future,
+        io,
+        pin::Pin,
+        task::{Context, Poll},
+        time::Duration,
+    },
+    tokio::{
+        io::{AsyncRead, AsyncWrite, ReadBuf},
+        net::TcpStream,
+        time::{Instant, Sleep, sleep, timeout},
+    },
+    tracing::{debug, warn},
+};
+
+/// How long an idle TCP connection waits before the OS starts sending keepalive probes.
+const KEEPALIVE_TIME: Duration = Duration::from_secs(15);
+/// Gap between keepalive probes once they have started.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    windows
+))]
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Time limits for each phase of a session. A half-open peer would otherwise hang
+/// `connect`, the handshake reads or the tar stream forever.
+#[derive(Debug, Clone, Copy)]
+pub struct Timeouts {
+    /// Overall limit for finding a receiver by username, across every discovery strategy.
+    pub discovery: Duration,
+    /// Limit for establishing the TCP connection (sender only).
+    pub connect: Duration,
+    /// Limit for the identity proof and manifest exchange.
+    pub handshake: Duration,
+    /// Longest the data stream may go without any progress before it counts as stalled.
+    pub idle: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        // The connect default follows the 10 seconds recommended by PXP-HANDSHAKE.
+        Self {
+            discovery: Duration::from_secs(30),
+            connect: Duration::from_secs(10),
+            handshake: Duration::from_secs(15),
+            idle: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Runs `fut`, turning an expired deadline into a typed `PxpError::Timeout` for `phase`.
+pub(crate) async fn within<F, T>(phase: TimeoutPhase, limit: Duration, fut: F) -> Result<T>
+where
+    F: Future<Output = Result<T>>,
+{
+    match timeout(limit, fut).await {
+        Ok(result) => result,
+        Err(_) => {
+            warn!("Timed out after {:?} during {}", limit, phase);
+            Err(PxpError::Timeout { phase })
+        }
+    }
+}
+
+/// Enables TCP keepalive so a peer that vanished (power loss, Wi-Fi drop) is noticed by the
+/// OS even while neither side is writing.
+pub(crate) fn enable_keepalive(stream: &TcpStream) {
+    let keepalive = TcpKeepalive::new().with_time(KEEPALIVE_TIME);
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        windows
+    ))]
+    let keepalive = keepalive.with_interval(KEEPALIVE_INTERVAL);
+
+    // Keepalive is a safety net, not a requirement: a platform that refuses it still works,
+    // it just relies on the idle timeout alone.
+    match SockRef::from(stream).set_tcp_keepalive(&keepalive) {
+        Ok(()) => debug!("TCP keepalive enabled ({:?})", KEEPALIVE_TIME),
+        Err(e) => warn!("Could not enable TCP keepalive: {}", e),
+    }
+}
+
+/// Wraps a stream and fails any read or write that makes no progress for `timeout`, with an
+/// `io::ErrorKind::TimedOut` error. Used around the data stream so a stalled peer surfaces
+/// as `PxpError::Timeout { phase: Transfer }` instead of hanging forever.
+///
+/// The clock starts when a read or write begins, so time the caller spends not polling
+/// (e.g. while the user answers a conflict prompt) never counts against the peer.
+pub struct IdleTimeout<S> {
+    inner: S,
+    timeout: Duration,
+    deadline: Pin<Box<Sleep>>,
+    /// Set while a read or write is pending, so polling it again keeps its deadline.
+    waiting: bool,
+}
+
+impl<S> IdleTimeout<S> {
+    pub fn new(inner: S, timeout: Duration) -> Self {
+        Self {
+            inner,
+            timeout,
+            deadline: Box::pin(sleep(timeout)),
+            waiting: false,
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Starts the clock for a read or write that isn't already waiting.
+    fn begin(&mut self) {
+        if !self.waiting {
+            let next = Instant::now() + self.timeout;
+            self.deadline.as_mut().reset(next);
+        }
+    }
+
+    fn done<T>(&mut self, result: io::Result<T>) -> Poll<io::Result<T>> {
+        self.waiting = false;
+        Poll::Ready(result)
+    }
+
+    fn poll_stalled<T>(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<T>> {
+        self.waiting = true;
+        match self.deadline.as_mut().poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("no data moved for {:?}", self.timeout),
+            ))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for IdleTimeout<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.begin();
+        match Pin::new(&mut this.inner).poll_read(cx, buf) {
+            Poll::Ready(result) => this.done(result),
+            Poll::Pending => this.poll_stalled(cx),
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for IdleTimeout<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        this.begin();
+        match Pin::new(&mut this.inner).poll_write(cx, buf) {
+            Poll::Ready(result) => this.done(result),
+            Poll::Pending => this.poll_stalled(cx),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.begin();
+        match Pin::new(&mut this.inner).poll_flush(cx) {
+            Poll::Ready(result) => this.done(result),
+            Poll::Pending => this.poll_stalled(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.begin();
+        match Pin::new(&mut this.inner).poll_shutdown(cx) {
+            Poll::Ready(result) => this.done(result),
+            Poll::Pending => this.poll_stalled(cx),
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use {
+    crate::error::{PxpError, Result},
+    std::{fmt, net::IpAddr, str::FromStr},
+    tracing::{debug, warn},
+};
+
+/// One entry of a sender allow or deny list: a single IP, a CIDR range, or a username.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccessRule {
+    Ip(IpAddr),
+    Cidr { network: IpAddr, prefix: u8 },
+    /// Matched case-insensitively; `alice` also matches `alice@portal`.
+    User(String),
+}
+
+impl AccessRule {
+    fn matches_ip(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        match self {
+            AccessRule::Ip(rule) => rule.to_canonical() == ip,
+            AccessRule::Cidr { network, prefix } => in_range(*network, *prefix, ip),
+            AccessRule::User(_) => false,
+        }
+    }
+
+    fn matches_user(&self, username: &str) -> bool {
+        match self {
+            AccessRule::User(rule) => {
+                let strip = |name: &str| name.trim_end_matches("@portal").to_lowercase();
+                strip(rule) == strip(username)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for AccessRule {
+    type Err = PxpError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let value = value.trim();
+        if let Some((network, prefix)) = value.split_once('/') {
+            let network: IpAddr = network.parse().map_err(|_| invalid_rule(value))?;
+            let prefix: u8 = prefix.parse().map_err(|_| invalid_rule(value))?;
+            let max = if network.is_ipv4() { 32 } else { 128 };
+            if prefix > max {
+                return Err(invalid_rule(value));
+            }
+            return Ok(AccessRule::Cidr { network, prefix });
+        }
+        if let Ok(ip) = value.parse::<IpAddr>() {
+            return Ok(AccessRule::Ip(ip));
+        }
+        if value.is_empty() || value.chars().any(char::is_whitespace) {
+            return Err(invalid_rule(value));
+        }
+        Ok(AccessRule::User(value.to_string()))
+    }
+}
+
+impl fmt::Display for AccessRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccessRule::Ip(ip) => write!(f, "{}", ip),
+            AccessRule::Cidr { network, prefix } => write!(f, "{}/{}", network, prefix),
+            AccessRule::User(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Decides which senders a receiver will take a transfer from.
+///
+/// A sender is refused when its IP or claimed username matches any `deny` rule. When `allow`
+/// is non-empty, it must also match at least one `allow` rule. Usernames are whatever the
+/// sender put in its manifest; Portal has no way to verify them, so rely on IP rules where
+/// that matters.
+#[derive(Debug, Clone, Default)]
+pub struct SenderFilter {
+    pub allow: Vec<AccessRule>,
+    pub deny: Vec<AccessRule>,
+}
+
+impl SenderFilter {
+    /// Whether a sender at `ip`, claiming `username`, may start a transfer.
+    pub fn permits(&self, ip: IpAddr, username: Option<&str>) -> bool {
+        let matches = |rule: &AccessRule| {
+            rule.matches_ip(ip) || username.is_some_and(|name| rule.matches_user(name))
+        };
+
+        if let Some(rule) = self.deny.iter().find(|r| matches(r)) {
+            warn!(
+                "Sender {} ({}) matches deny rule '{}'",
+                ip,
+                username.unwrap_or("no username"),
+                rule
+            );
+            return false;
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(matches) {
+            warn!(
+                "Sender {} ({}) is not on the allow list",
+                ip,
+                username.unwrap_or("no username")
+            );
+            return false;
+        }
+        debug!("Sender {} passed the access filter", ip);
+        true
+    }
+
+    /// Whether `ip` is refused whatever username it goes on to claim.
+    pub fn refuses_ip(&self, ip: IpAddr) -> bool {
+        if self.deny.iter().any(|r| r.matches_ip(ip)) {
+            return true;
+        }
+        !self.allow.is_empty()
+            && !self.allow.iter().any(|r| r.matches_ip(ip))
+            && !self.allow.iter().any(|r| matches!(r, AccessRule::User(_)))
+    }
+}
+
+fn in_range(network: IpAddr, prefix: u8, ip: IpAddr) -> bool {
+    match (network.to_canonical(), ip) {
+        (IpAddr::V4(net), IpAddr::V4(ip)) => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+            u32::from(net) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(net), IpAddr::V6(ip)) => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0);
+            u128::from(net) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
+
+fn invalid_rule(value: &str) -> PxpError {
+    PxpError::InvalidAccessRule(value.to_string())
+}
@@ -0,0 +1,148 @@
+use {
+    crate::error::{PxpError, Result},
+    sha2::{Digest, Sha256},
+    std::{
+        fs::{File, read_dir, symlink_metadata},
+        io::copy,
+        path::{Path, PathBuf},
+        time::SystemTime,
+    },
+    tracing::trace,
+};
+
+/// Everything a `ConflictResolver` gets to decide about a collision: the item as it was
+/// received and the copy already at its destination.
+#[derive(Debug, Clone)]
+pub struct ConflictInfo {
+    /// The top-level item name, as shown to the user.
+    pub name: String,
+    /// The copy already at the destination.
+    pub existing: ConflictSide,
+    /// The received copy, still in staging.
+    pub incoming: ConflictSide,
+}
+
+/// One copy of a conflicting item.
+#[derive(Debug, Clone)]
+pub struct ConflictSide {
+    pub path: PathBuf,
+    pub is_dir: bool,
+    /// Size in bytes; for a folder, the total of every file in it.
+    pub bytes: u64,
+    /// When it was last modified, if known; for a folder, its most recently modified file.
+    pub modified: Option<SystemTime>,
+}
+
+impl ConflictInfo {
+    pub(crate) async fn new(name: &str, existing: &Path, incoming: &Path) -> Result<Self> {
+        Ok(Self {
+            name: name.to_string(),
+            existing: ConflictSide::load(existing).await?,
+            incoming: ConflictSide::load(incoming).await?,
+        })
+    }
+
+    /// Whether both copies have the same contents. Kinds and sizes are compared first, so
+    /// the copies are only read and hashed when they could match.
+    pub async fn identical(&self) -> Result<bool> {
+        if self.existing.is_dir != self.incoming.is_dir
+            || self.existing.bytes != self.incoming.bytes
+        {
+            return Ok(false);
+        }
+        Ok(self.existing.sha256().await? == self.incoming.sha256().await?)
+    }
+
+    /// Whether the received copy was modified after the existing one. A copy with no known
+    /// modification time counts as the older one.
+    pub fn incoming_is_newer(&self) -> bool {
+        self.incoming.modified > self.existing.modified
+    }
+}
+
+impl ConflictSide {
+    async fn load(path: &Path) -> Result<Self> {
+        let path = path.to_path_buf();
+        blocking(move || {
+            let meta = symlink_metadata(&path)?;
+            let (bytes, modified) = if meta.is_dir() {
+                tree_stats(&path)?
+            } else {
+                (meta.len(), meta.modified().ok())
+            };
+            Ok(Self {
+                is_dir: meta.is_dir(),
+                path,
+                bytes,
+                modified,
+            })
+        })
+        .await
+    }
+
+    /// SHA-256 of the contents. A folder's covers the relative path and contents of every
+    /// file in it, so two folders only match if they hold the same files. This reads the
+    /// whole copy, so it is computed on demand rather than up front.
+    pub async fn sha256(&self) -> Result<[u8; 32]> {
+        let (path, is_dir) = (self.path.clone(), self.is_dir);
+        blocking(move || {
+            let mut hasher = Sha256::new();
+            if is_dir {
+                hash_tree(&path, Path::new(""), &mut hasher)?;
+            } else {
+                copy(&mut File::open(&path)?, &mut hasher)?;
+            }
+            trace!("Hashed {:?}", path);
+            Ok(hasher.finalize().into())
+        })
+        .await
+    }
+}
+
+/// Total size and newest modification time of the files under `dir`.
+fn tree_stats(dir: &Path) -> Result<(u64, Option<SystemTime>)> {
+    let (mut bytes, mut newest) = (0, None);
+    for entry in read_dir(dir)? {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        let (size, modified) = if meta.is_dir() {
+            tree_stats(&entry.path())?
+        } else {
+            (meta.len(), meta.modified().ok())
+        };
+        bytes += size;
+        newest = newest.max(modified);
+    }
+    Ok((bytes, newest))
+}
+
+/// Feeds every file under `dir` into `hasher` in name order, each preceded by its path
+/// relative to the folder being hashed.
+fn hash_tree(dir: &Path, relative: &Path, hasher: &mut Sha256) -> Result<()> {
+    let mut entries = read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let relative = relative.join(entry.file_name());
+        hasher.update(relative.to_string_lossy().as_bytes());
+        if entry.file_type()?.is_dir() {
+            hasher.update(b"/");
+            hash_tree(&entry.path(), &relative, hasher)?;
+        } else {
+            let mut file = File::open(entry.path())?;
+            hasher.update(file.metadata()?.len().to_le_bytes());
+            copy(&mut file, hasher)?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs filesystem work that walks or reads whole trees off the async workers.
+async fn blocking<T, F>(work: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| PxpError::Io(std::io::Error::other(e)))?
+}
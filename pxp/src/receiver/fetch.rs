@@ -0,0 +1,83 @@
+use {
+    crate::{
+        discovery::listener::{BeaconQuery, DiscoveredReceiver, discover},
+        metadata::{GlobalTransferManifest, PullRequest, PullResponse, ShareListing},
+        sender::connect_to_receiver,
+    },
+    crate::error::{Result, TimeoutPhase},
+    crate::frame::{MAX_LISTING_LEN, MAX_MANIFEST_LEN, read_message, write_message},
+    crate::net::{Timeouts, within},
+    std::time::Duration,
+    tokio::{io::AsyncWriteExt, net::TcpStream},
+    tracing::{debug, info, warn},
+};
+
+/// Finds a share by its name or code. Busy hosts are waited for like busy receivers.
+pub async fn discover_share(
+    name_or_code: &str,
+    fallback_port: u16,
+    limit: Duration,
+) -> Result<DiscoveredReceiver> {
+    let query = BeaconQuery::Share(name_or_code.to_string());
+    let hint = format!(
+        "Search timed out. Make sure the host is running `portal serve` on the same network.\n\
+         Portal: Try direct address mode:\n\
+         Portal:   portal get --address <host-ip> --port {}\n\
+         Tip: The host shows its address and share code when running `portal serve`.",
+        fallback_port
+    );
+    discover(&query, limit, hint).await
+}
+
+/// Connects to a share host, verifies it against `expected_node_id` when known (as for a
+/// receiver), and reads what it offers.
+pub async fn connect_to_share(
+    host_ip: &str,
+    host_port: u16,
+    expected_node_id: Option<&str>,
+    timeouts: &Timeouts,
+) -> Result<(TcpStream, ShareListing)> {
+    let mut stream = connect_to_receiver(host_ip, host_port, expected_node_id, timeouts).await?;
+    let listing: ShareListing = within(
+        TimeoutPhase::Handshake,
+        timeouts.handshake,
+        read_message(&mut stream, MAX_LISTING_LEN, "share listing"),
+    )
+    .await?;
+    info!(
+        "Share '{}' offers {} item(s)",
+        listing.share_name,
+        listing.items.len()
+    );
+    Ok((stream, listing))
+}
+
+/// Asks for the chosen items and returns the manifest describing them. The caller then
+/// answers it with `respond_to_manifest` and receives with `receive_stream`, as for a push.
+/// A refusal comes back as a typed error.
+pub async fn request_items(
+    stream: &mut TcpStream,
+    request: &PullRequest,
+    timeouts: &Timeouts,
+) -> Result<GlobalTransferManifest> {
+    debug!("Sending pull request: {:?}", request);
+    write_message(stream, request).await?;
+    stream.flush().await?;
+
+    let response: PullResponse = within(
+        TimeoutPhase::Handshake,
+        timeouts.handshake,
+        read_message(stream, MAX_MANIFEST_LEN, "pull response"),
+    )
+    .await?;
+    match response {
+        PullResponse::Manifest(manifest) => {
+            debug!("Pull accepted; manifest: {:?}", manifest);
+            Ok(manifest)
+        }
+        PullResponse::Refused(reason) => {
+            warn!("Host refused the pull: {:?}", reason);
+            Err(reason.into())
+        }
+    }
+}
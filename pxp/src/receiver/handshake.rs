@@ -1,30 +1,53 @@
 use {
     crate::{
-        discovery::beacon::start_beacon,
-        metadata::GlobalTransferManifest,
+        discovery::{BeaconHandle, DeviceInfo, ReceiverStatus, spawn_beacon},
+        metadata::{GlobalTransferManifest, ManifestResponse, RejectReason},
+        receiver::access::SenderFilter,
     },
-    crate::error::{PxpError, Result},
-    bincode::deserialize,
+    crate::error::{PxpError, Result, TimeoutPhase},
+    crate::frame::{MAX_MANIFEST_LEN, read_message, write_frame, write_message},
+    crate::net::{Timeouts, enable_keepalive, within},
     tokio::{
-        io::{AsyncReadExt, AsyncWriteExt},
+        io::AsyncWriteExt,
         net::{TcpListener, TcpStream},
+        task::JoinSet,
     },
-    tracing::{debug, error, info, trace},
+    tracing::{debug, error, info, trace, warn},
     uuid::Uuid,
 };
 
+/// Most connections that may be mid-handshake at once; further ones wait in the backlog.
+/// Each can hold a manifest of up to `MAX_MANIFEST_LEN`, so this also bounds memory.
+const MAX_PENDING_HANDSHAKES: usize = 4;
+
 pub struct HandshakeResult {
     pub socket: TcpStream,
     pub peer_addr: Option<String>,
     pub peer_username: Option<String>,
     pub manifest: GlobalTransferManifest,
+    /// The beacon, now advertising this receiver as busy. Drop it once the session ends.
+    pub beacon: BeaconHandle,
 }
 
 /// Accept a connection, run discovery beacon, verify identity, and read manifest.
+/// The beacon carries `device` and switches to busy once a sender has been admitted.
 /// This is the core protocol handshake — no config loading or user-facing output.
+/// Waiting for a sender is unbounded; once one connects, the identity proof and manifest
+/// exchange must finish within `timeouts.handshake`.
+///
+/// Connections from addresses `filter` refuses outright are dropped before anything is
+/// sent. Senders it doesn't admit by username are told so with a `NotAllowed` rejection
+/// once their manifest is in. Either way the receiver goes back to waiting, as it does for
+/// any connection whose handshake fails or times out (a port scan, a stray or broken
+/// client): only the beacon or the listener failing ends the wait. Up to
+/// `MAX_PENDING_HANDSHAKES` connections are handshaken at once, so one slow peer can't
+/// hold up the rest.
 pub async fn accept_and_read_manifest(
     port: u16,
     username: String,
+    device: DeviceInfo,
+    filter: &SenderFilter,
+    timeouts: &Timeouts,
 ) -> Result<HandshakeResult> {
     let node_id = Uuid::new_v4().to_string();
     debug!("Generated session Node ID: {}", node_id);
@@ -38,68 +61,118 @@ pub async fn accept_and_read_manifest(
 
     info!("TCP Listener bound to {}", bind_addr);
 
-    // Run beacon and TCP accept concurrently
-    let (mut socket, addr) = tokio::select! {
-        _ = start_beacon(username, node_id.clone(), port) => {
-            error!("Discovery beacon exited unexpectedly");
-            return Err(PxpError::BeaconStopped);
-        }
-        result = listener.accept() => {
-            let (conn, addr) = result?;
-            trace!("Accepted raw TCP connection from: {:?}", addr);
-            (conn, addr)
+    // Run beacon and TCP accept concurrently. Handshakes run side by side, so a slow or
+    // silent peer only holds up its own slot rather than every sender behind it.
+    let mut beacon = spawn_beacon(username, node_id.clone(), port, device);
+    let mut pending = JoinSet::new();
+    loop {
+        let (mut socket, addr, manifest) = tokio::select! {
+            err = beacon.stopped() => {
+                error!("Discovery beacon exited unexpectedly");
+                return Err(err);
+            }
+            result = listener.accept(), if pending.len() < MAX_PENDING_HANDSHAKES => {
+                let (mut socket, addr) = result?;
+                trace!("Accepted raw TCP connection from: {:?}", addr);
+                // Refused addresses learn nothing about us and can't make us read a manifest.
+                if filter.refuses_ip(addr.ip()) {
+                    warn!("Dropped connection from refused sender {}", addr);
+                    continue;
+                }
+                info!("Connection accepted from sender: {}", addr);
+                enable_keepalive(&socket);
+                let node_id = node_id.clone();
+                let limit = timeouts.handshake;
+                pending.spawn(async move {
+                    let manifest = within(
+                        TimeoutPhase::Handshake,
+                        limit,
+                        exchange_identity_and_manifest(&mut socket, &node_id),
+                    )
+                    .await;
+                    (socket, addr, manifest)
+                });
+                continue;
+            }
+            Some(joined) = pending.join_next() => match joined {
+                Ok(handshake) => handshake,
+                Err(e) => {
+                    warn!("Handshake task failed: {}", e);
+                    continue;
+                }
+            },
+        };
+
+        let manifest = match manifest {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                warn!(
+                    "Handshake with {} failed: {}; waiting for another sender",
+                    addr, e
+                );
+                continue;
+            }
+        };
+
+        if !filter.permits(addr.ip(), manifest.sender_username.as_deref()) {
+            warn!(
+                "Refused transfer from {} ({}); waiting for another sender",
+                addr,
+                manifest.sender_username.as_deref().unwrap_or("no username")
+            );
+            // Sent in the background so a peer that stops reading can't stall the loop.
+            tokio::spawn(async move {
+                let refusal = ManifestResponse::Reject(RejectReason::NotAllowed);
+                if let Err(e) = respond_to_manifest(&mut socket, &refusal).await {
+                    debug!("Could not send refusal to {}: {}", addr, e);
+                }
+            });
+            continue;
         }
-    };
+        beacon.set_status(ReceiverStatus::Busy);
 
-    info!("Connection accepted from sender: {}", addr);
-    let peer_addr = Some(addr.ip().to_string());
+        // Dropping `pending` abandons any other handshakes still in progress.
+        return Ok(HandshakeResult {
+            socket,
+            peer_addr: Some(addr.ip().to_string()),
+            peer_username: manifest.sender_username.clone(),
+            manifest,
+            beacon,
+        });
+    }
+}
 
+/// Sends our node ID so the sender can verify who we are, then reads the manifest.
+async fn exchange_identity_and_manifest(
+    socket: &mut TcpStream,
+    node_id: &str,
+) -> Result<GlobalTransferManifest> {
     // Send ID to Sender so they can verify who we are
     debug!("Sending Node ID for verification: {}", node_id);
     let id_bytes = node_id.as_bytes();
-    let id_len = id_bytes.len() as u32;
-    trace!("Node ID length: {} bytes", id_len);
+    trace!("Node ID length: {} bytes", id_bytes.len());
 
-    socket
-        .write_all(&id_len.to_be_bytes())
-        .await?;
-    socket
-        .write_all(id_bytes)
-        .await?;
+    write_frame(socket, id_bytes).await?;
     trace!("Verification identity sent to peer.");
 
-    // Read the manifest length
-    let mut global_manifest_len_buf = [0u8; 4];
-    socket
-        .read_exact(&mut global_manifest_len_buf)
-        .await?;
-
-    let global_manifest_len = u32::from_be_bytes(global_manifest_len_buf) as usize;
-    debug!(
-        "Incoming global manifest length: {} bytes",
-        global_manifest_len
-    );
-
-    // Read the manifest blob
-    let mut global_manifest_buf = vec![0u8; global_manifest_len];
-    socket
-        .read_exact(&mut global_manifest_buf)
-        .await?;
-    trace!(
-        "Read global manifest raw bytes (size: {}). Deserializing...",
-        global_manifest_len
-    );
-
+    // Read the manifest. The length prefix is checked before anything is allocated, so a
+    // hostile peer can't make us reserve gigabytes up front.
     let manifest: GlobalTransferManifest =
-        deserialize(&global_manifest_buf)?;
+        read_message(socket, MAX_MANIFEST_LEN, "manifest").await?;
 
     info!("Global manifest received and deserialized successfully.");
     trace!("Manifest data: {:?}", manifest);
+    Ok(manifest)
+}
 
-    Ok(HandshakeResult {
-        socket,
-        peer_addr,
-        peer_username: manifest.sender_username.clone(),
-        manifest,
-    })
+/// Tells the sender whether the transfer described by the manifest is accepted. Must be
+/// called exactly once after the handshake; on a rejection the caller then drops the socket.
+pub async fn respond_to_manifest(
+    socket: &mut TcpStream,
+    response: &ManifestResponse,
+) -> Result<()> {
+    debug!("Sending manifest response: {:?}", response);
+    write_message(socket, response).await?;
+    socket.flush().await?;
+    Ok(())
 }
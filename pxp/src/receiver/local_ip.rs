@@ -29,11 +29,11 @@ pub async fn get_local_ip() -> Option<String> {
             );
             for addr in interface.addr {
                 trace!("Found address: {:?}", addr.ip());
-                if let IpAddr::V4(ipv4) = addr.ip() {
-                    if !ipv4.is_loopback() {
-                        debug!("Selected suitable local IPv4: {}", ipv4);
-                        return Some(ipv4.to_string());
-                    }
+                if let IpAddr::V4(ipv4) = addr.ip()
+                    && !ipv4.is_loopback()
+                {
+                    debug!("Selected suitable local IPv4: {}", ipv4);
+                    return Some(ipv4.to_string());
                 }
             }
         }
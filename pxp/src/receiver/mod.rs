@@ -1,6 +1,17 @@
+pub mod access;
+pub mod conflict;
+pub mod fetch;
 pub mod handshake;
 pub mod local_ip;
+pub mod policy;
 pub mod receive_item;
+pub mod sanitize;
+pub mod space;
 pub mod stream;
 
-pub use receive_item::{reconcile, StagedItem, StagedTransfer};
+pub use access::{AccessRule, SenderFilter};
+pub use conflict::{ConflictInfo, ConflictSide};
+pub use fetch::{connect_to_share, discover_share, request_items};
+pub use receive_item::{discard, reconcile, save_text, PlacedItem, StagedItem, StagedTransfer};
+pub use policy::ReceivePolicy;
+pub use space::{available_space, check_free_space};
@@ -0,0 +1,227 @@
+use {
+    crate::{
+        error::{PxpError, Result},
+        metadata::GlobalTransferManifest,
+    },
+    std::path::{Component, Path},
+    tokio::io::{AsyncRead, AsyncReadExt},
+    tracing::{debug, error, trace},
+};
+
+/// How much of each file is read to recognise its type from its content. Enough for every
+/// signature `infer` knows, the furthest of which (tar's) sits at byte 257.
+const SNIFF_LEN: u64 = 1024;
+
+/// Limits a receiver places on what it accepts. Every limit is optional; the default policy
+/// accepts everything, as before.
+///
+/// Extension and MIME lists are case-insensitive. An allow list, when non-empty, admits only
+/// files that match it; a deny list always wins over an allow list. MIME types are guessed
+/// from the file extension and, for file types with a known signature (executables,
+/// archives, images, ...), also recognised from the first bytes of the file, so renaming a
+/// file doesn't get it past a MIME rule. Patterns may end in `/*` (e.g. `image/*`).
+#[derive(Debug, Clone, Default)]
+pub struct ReceivePolicy {
+    pub max_total_bytes: Option<u64>,
+    /// Most files (top-level and nested) a single transfer may contain.
+    pub max_files: Option<u64>,
+    /// Deepest path allowed, counting the top-level item as depth 1.
+    pub max_depth: Option<usize>,
+    pub allowed_extensions: Vec<String>,
+    pub denied_extensions: Vec<String>,
+    pub allowed_mime_types: Vec<String>,
+    pub denied_mime_types: Vec<String>,
+}
+
+impl ReceivePolicy {
+    /// Returns true when the policy places no limits at all.
+    pub fn is_empty(&self) -> bool {
+        self.max_total_bytes.is_none()
+            && self.max_files.is_none()
+            && self.max_depth.is_none()
+            && self.allowed_extensions.is_empty()
+            && self.denied_extensions.is_empty()
+            && self.allowed_mime_types.is_empty()
+            && self.denied_mime_types.is_empty()
+    }
+
+    /// Checks the totals a sender declared, so an oversized transfer is refused before a
+    /// single byte of it is streamed. Nested files aren't counted in the manifest, so the
+    /// per-entry checks in `PolicyGuard` still apply afterwards.
+    pub fn check_manifest(&self, manifest: &GlobalTransferManifest) -> Result<()> {
+        if let Some(max) = self.max_total_bytes
+            && manifest.total_bytes > max
+        {
+            return Err(violation(format!(
+                "transfer is {} bytes, more than the {} byte limit",
+                manifest.total_bytes, max
+            )));
+        }
+        if let Some(max) = self.max_files
+            && u64::from(manifest.total_files) > max
+        {
+            return Err(violation(format!(
+                "transfer has {} files, more than the limit of {}",
+                manifest.total_files, max
+            )));
+        }
+        debug!("Manifest passed the receive policy");
+        Ok(())
+    }
+
+    fn check_file_type(&self, path: &Path) -> Result<()> {
+        let name = path.to_string_lossy().to_lowercase();
+        let has_ext = |exts: &[String]| {
+            exts.iter().any(|ext| {
+                let ext = ext.trim_start_matches('.').to_lowercase();
+                name.ends_with(&format!(".{}", ext))
+            })
+        };
+
+        if has_ext(&self.denied_extensions) {
+            return Err(violation(format!("'{}' has a blocked extension", path.display())));
+        }
+        if !self.allowed_extensions.is_empty() && !has_ext(&self.allowed_extensions) {
+            return Err(violation(format!(
+                "'{}' does not have an allowed extension",
+                path.display()
+            )));
+        }
+
+        if self.allowed_mime_types.is_empty() && self.denied_mime_types.is_empty() {
+            return Ok(());
+        }
+        let guesses: Vec<String> = mime_guess::from_path(path)
+            .iter()
+            .map(|m| m.essence_str().to_lowercase())
+            .collect();
+        trace!("MIME guesses for {:?}: {:?}", path, guesses);
+        self.check_mime(path, &guesses)
+    }
+
+    fn has_mime_rules(&self) -> bool {
+        !self.allowed_mime_types.is_empty() || !self.denied_mime_types.is_empty()
+    }
+
+    fn check_mime(&self, path: &Path, mimes: &[String]) -> Result<()> {
+        let has_mime = |patterns: &[String]| {
+            mimes
+                .iter()
+                .any(|mime| patterns.iter().any(|p| mime_matches(&p.to_lowercase(), mime)))
+        };
+
+        if has_mime(&self.denied_mime_types) {
+            return Err(violation(format!("'{}' has a blocked file type", path.display())));
+        }
+        if !self.allowed_mime_types.is_empty() && !has_mime(&self.allowed_mime_types) {
+            return Err(violation(format!(
+                "'{}' is not an allowed file type",
+                path.display()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Applies a `ReceivePolicy` to each archive entry as it arrives, keeping running totals.
+/// Entries are checked from their tar header, before anything is written to disk.
+pub(crate) struct PolicyGuard<'a> {
+    policy: &'a ReceivePolicy,
+    files: u64,
+    bytes: u64,
+}
+
+impl<'a> PolicyGuard<'a> {
+    pub(crate) fn new(policy: &'a ReceivePolicy) -> Self {
+        Self {
+            policy,
+            files: 0,
+            bytes: 0,
+        }
+    }
+
+    /// Bytes still allowed before `max_total_bytes` is reached, if there is a limit.
+    pub(crate) fn remaining_bytes(&self) -> Option<u64> {
+        self.policy
+            .max_total_bytes
+            .map(|max| max.saturating_sub(self.bytes))
+    }
+
+    pub(crate) fn check_entry(&mut self, path: &Path, size: u64, is_dir: bool) -> Result<()> {
+        if self.policy.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(max) = self.policy.max_depth {
+            let depth = path
+                .components()
+                .filter(|c| matches!(c, Component::Normal(_)))
+                .count();
+            if depth > max {
+                return Err(violation(format!(
+                    "'{}' is nested {} levels deep, more than the limit of {}",
+                    path.display(),
+                    depth,
+                    max
+                )));
+            }
+        }
+        if is_dir {
+            return Ok(());
+        }
+
+        self.files += 1;
+        self.bytes = self.bytes.saturating_add(size);
+        if let Some(max) = self.policy.max_files
+            && self.files > max
+        {
+            return Err(violation(format!(
+                "transfer contains more than the limit of {} files",
+                max
+            )));
+        }
+        if let Some(max) = self.policy.max_total_bytes
+            && self.bytes > max
+        {
+            return Err(violation(format!(
+                "transfer exceeds the {} byte limit",
+                max
+            )));
+        }
+        self.policy.check_file_type(path)
+    }
+
+    /// Reads the start of a file from `reader` and checks the type its content shows
+    /// against the MIME rules, before any of it is written. Returns the bytes read, which
+    /// the caller writes out ahead of the rest; nothing is read when there are no MIME
+    /// rules. Content with no known signature (plain text, say) was already judged by its
+    /// extension in `check_entry`.
+    pub(crate) async fn sniff<R>(&self, path: &Path, reader: &mut R) -> Result<Vec<u8>>
+    where
+        R: AsyncRead + Unpin,
+    {
+        if !self.policy.has_mime_rules() {
+            return Ok(Vec::new());
+        }
+        let mut head = Vec::new();
+        reader.take(SNIFF_LEN).read_to_end(&mut head).await?;
+        if let Some(kind) = infer::get(&head) {
+            trace!("Content of {:?} looks like {}", path, kind.mime_type());
+            self.policy.check_mime(path, &[kind.mime_type().to_string()])?;
+        }
+        Ok(head)
+    }
+}
+
+/// Matches a MIME type against a pattern such as `image/png` or `image/*`.
+fn mime_matches(pattern: &str, mime: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(top) => mime.split('/').next() == Some(top),
+        None => pattern == mime,
+    }
+}
+
+fn violation(reason: String) -> PxpError {
+    error!("SECURITY ALERT: Receive policy violated: {}", reason);
+    PxpError::Security(reason)
+}
@@ -1,14 +1,28 @@
 use {
     crate::{
-        metadata::{PxpMeta, ReceiveSummary, ReceivedItem, TransferItem},
-        ConflictAction, ConflictResolver, TransferProgress,
+        metadata::{PxpMeta, ReceiveSummary, ReceivedItem, RenamedEntry, TransferItem},
+        receiver::{
+            conflict::ConflictInfo,
+            policy::{PolicyGuard, ReceivePolicy},
+            sanitize::PathSanitizer,
+        },
+        ConflictAction, ConflictResolver, ItemRouter,
+        event::{
+            EventSink, ItemKind, Metered, TransferEvent, TransferPhase, TransferWarning, emit,
+        },
     },
     crate::error::{PxpError, Result},
-    bincode::deserialize,
-    std::path::PathBuf,
+    crate::frame::{MAX_META_LEN, MAX_TEXT_LEN, check_len, decode},
+    std::{
+        path::{Path, PathBuf},
+        time::{Duration, UNIX_EPOCH},
+    },
     tokio::{
-        fs::{File, create_dir_all, remove_dir, remove_dir_all, remove_file, rename, try_exists},
-        io::AsyncRead,
+        fs::{
+            File, copy, create_dir_all, read_dir, remove_dir, remove_dir_all, remove_file,
+            rename, try_exists, write,
+        },
+        io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
     },
     tokio_stream::StreamExt,
     tokio_tar::Archive,
@@ -32,6 +46,15 @@ pub struct StagedItem {
     pub staged_path: PathBuf,
     pub final_path: PathBuf,
     pub is_dir: bool,
+    /// The item's size from its contract (a folder's total), for routing decisions.
+    pub bytes: u64,
+}
+
+/// Where a staged item ended up after reconcile; `path` is `None` if it was skipped.
+#[derive(Debug)]
+pub struct PlacedItem {
+    pub name: String,
+    pub path: Option<PathBuf>,
 }
 
 /// What the receiver ends up with after streaming: every item unpacked into a private
@@ -48,25 +71,32 @@ pub struct StagedTransfer {
 /// staging dir (so conflicts never interrupt the stream). Appends each completed top-level
 /// item to `staged_items`, which the caller can reconcile into the target dir even when the
 /// stream fails part-way through.
+///
+/// Every entry is checked against `policy` from its tar header, before anything is written.
+#[allow(clippy::too_many_arguments)]
 pub async fn receive_item<R>(
     archive: &mut Archive<R>,
-    target_dir: &PathBuf,
-    staging_dir: &PathBuf,
+    target_dir: &Path,
+    staging_dir: &Path,
     total_items: u32,
-    progress: Option<&dyn TransferProgress>,
+    policy: &ReceivePolicy,
+    events: Option<&dyn EventSink>,
     summary: &mut ReceiveSummary,
     staged_items: &mut Vec<StagedItem>,
 ) -> Result<()>
 where
     R: AsyncRead + Unpin + Send + 'static,
 {
+    let mut guard = PolicyGuard::new(policy);
+    let mut sanitizer = PathSanitizer::new();
     let mut contract: Option<PxpMeta> = None;
     let mut items_processed: u32 = 0;
-    let mut active_dir_progress: Option<Box<dyn crate::ItemProgress>> = None;
-    let mut pending_dir_success: Option<String> = None;
+    // A folder is only finished once the next top-level item starts (or the stream ends),
+    // since its nested entries arrive one by one until then.
+    let mut open_dir: Option<String> = None;
     let mut entries = archive.entries()?;
     while let Some(entry_result) = entries.next().await {
-        let mut entry = entry_result.map_err(|e| PxpError::Archive(e.to_string()))?;
+        let mut entry = entry_result.map_err(PxpError::from_stream_io)?;
         let path = entry.path()?.to_path_buf();
         let entry_size = entry.header().size()?;
         trace!("--- Processing archive entry {} ---", path.display());
@@ -77,10 +107,15 @@ where
         // that matches this contract. If we get raw data without a preceding contract, we error out.
         if path.to_string_lossy().replace('\\', "/") == ".portal.meta" {
             debug!("Caught metadata block (.portal.meta)");
+            // Contracts are tiny; the header size is checked before reading so a forged
+            // one can't make us buffer gigabytes in memory.
+            check_len(entry_size as usize, MAX_META_LEN, ".portal.meta contract")?;
             let mut meta_bytes = Vec::new();
-            tokio::io::copy(&mut entry, &mut meta_bytes)
+            (&mut entry)
+                .take(MAX_META_LEN as u64)
+                .read_to_end(&mut meta_bytes)
                 .await?;
-            let deserialized: PxpMeta = deserialize(&meta_bytes)?;
+            let deserialized: PxpMeta = decode(&meta_bytes, MAX_META_LEN)?;
             trace!("Deserialized metadata content: {:?}", deserialized);
             contract = Some(deserialized);
             continue;
@@ -98,14 +133,9 @@ where
             path.display()
         );
 
-        let mut entry_item_progress: Option<Box<dyn crate::ItemProgress>> = None;
         if let PxpMeta::Item(item) = &meta {
             items_processed += 1;
 
-            if let Some(prog) = &progress {
-                prog.set_current_item(items_processed as usize, total_items as usize);
-            }
-
             match item {
                 TransferItem::File(f) => {
                     trace!(
@@ -116,6 +146,7 @@ where
                         name: f.filename.clone(),
                         bytes: f.file_size,
                         is_directory: false,
+                        text: None,
                     });
                     summary.total_bytes = summary.total_bytes.saturating_add(f.file_size);
                     info!(
@@ -132,6 +163,7 @@ where
                         name: d.dirname.clone(),
                         bytes: d.total_size,
                         is_directory: true,
+                        text: None,
                     });
                     summary.total_bytes = summary.total_bytes.saturating_add(d.total_size);
                     info!(
@@ -139,6 +171,9 @@ where
                         d.dirname, d.total_size
                     );
                 }
+                TransferItem::Text(t) => {
+                    info!("Incoming text item ({} bytes)", t.byte_len);
+                }
             }
 
             // Hard Stop: reject anything beyond the manifest
@@ -152,49 +187,112 @@ where
                 ));
             }
 
-            // Close any active directory progress before starting a new top-level item
-            if let Some(dir_prog) = active_dir_progress.take() {
-                dir_prog.finish_and_clear();
-                if let Some(dir_name) = pending_dir_success.take() {
-                    if let Some(prog) = &progress {
-                        prog.println(&format!(
-                            "Portal: Directory '{}' received successfully!",
-                            dir_name
-                        ));
-                    }
-                }
+            // Every entry of the previous folder has been checked by now.
+            if let Some(name) = open_dir.take() {
+                finish_dir(events, name);
             }
 
-            if let Some(prog) = &progress {
-                match item {
-                    TransferItem::File(f) => {
-                        entry_item_progress = Some(prog.create_item_progress(&f.filename, f.file_size));
-                    }
-                    TransferItem::Directory(d) => {
-                        active_dir_progress = Some(prog.create_item_progress(&d.dirname, d.total_size));
-                        pending_dir_success = Some(d.dirname.clone());
-                    }
-                }
+            let (name, kind, bytes) = match item {
+                TransferItem::File(f) => (&f.filename, ItemKind::File, f.file_size),
+                TransferItem::Directory(d) => (&d.dirname, ItemKind::Directory, d.total_size),
+                TransferItem::Text(t) => (&t.name, ItemKind::Text, t.byte_len),
+            };
+            emit(
+                events,
+                TransferEvent::ItemStarted {
+                    index: items_processed as usize,
+                    total: total_items as usize,
+                    name: name.clone(),
+                    kind,
+                    bytes,
+                },
+            );
+            if kind == ItemKind::Directory {
+                open_dir = Some(name.clone());
             }
         }
 
+        // Text never reaches the staging dir: it is read into the summary, and the caller
+        // shows it or saves it with `save_text`.
+        if let PxpMeta::Item(TransferItem::Text(t)) = &meta {
+            check_len(entry_size as usize, MAX_TEXT_LEN, "text item")?;
+            if t.name != path.to_string_lossy() || entry.header().entry_type().is_dir() {
+                error!("Text name mismatch: Expected {}, got {}", t.name, path.display());
+                return Err(PxpError::Protocol("Text item name mismatch".into()));
+            }
+            if t.byte_len != entry_size {
+                error!(
+                    "Text size mismatch: Expected {}, got {}",
+                    t.byte_len, entry_size
+                );
+                return Err(PxpError::Protocol("Text item size mismatch".into()));
+            }
+            // Only the last component of the name is kept; it only matters if the text is
+            // saved, and then it must be a plain file name that is safe here.
+            let name = Path::new(&t.name)
+                .file_name()
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from("snippet.txt"));
+            let safe_name = sanitizer.sanitize(&name, false);
+            for renamed in sanitizer.take_renamed() {
+                report_rename(events, &renamed);
+                summary.renamed.push(renamed);
+            }
+            guard.check_entry(&safe_name, entry_size, false)?;
+
+            let mut bytes = Vec::with_capacity(entry_size as usize);
+            (&mut entry)
+                .take(MAX_TEXT_LEN as u64)
+                .read_to_end(&mut bytes)
+                .await?;
+            let text = String::from_utf8(bytes)?;
+            summary.items.push(ReceivedItem {
+                name: safe_name.to_string_lossy().to_string(),
+                bytes: entry_size,
+                is_directory: false,
+                text: Some(text),
+            });
+            summary.total_bytes = summary.total_bytes.saturating_add(entry_size);
+            emit(events, TransferEvent::Progress { bytes: entry_size });
+            emit(
+                events,
+                TransferEvent::ItemFinished {
+                    name: safe_name.to_string_lossy().to_string(),
+                    kind: ItemKind::Text,
+                    verified: true,
+                },
+            );
+            info!("Text item verified ({} bytes)", entry_size);
+            continue;
+        }
+
         // Determine if entry is a directory or file
         let is_dir = entry.header().entry_type().is_dir();
-        let item_name = path
-            .file_name()
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_else(|| "unknown".into());
 
         // We clean and validate the incoming path by stripping out any weird components 
         // (like `..` or absolute path roots). This is a crucial security measure to prevent 
         // "Zip Slip" style attacks where a malicious sender tries to write outside the target dir.
-        let safe_path = path
+        let normal_path = path
             .components()
             .filter(|c| matches!(c, std::path::Component::Normal(_)))
             .collect::<PathBuf>();
+        // Then make every name safe to create here (reserved names, illegal characters,
+        // Unicode normalization, case-insensitive collisions). Names the sender used may
+        // be fine on its OS and still unusable on ours.
+        let safe_path = sanitizer.sanitize(&normal_path, is_dir);
+        for renamed in sanitizer.take_renamed() {
+            info!("Saving '{}' as '{}'", renamed.original, renamed.renamed);
+            report_rename(events, &renamed);
+            summary.renamed.push(renamed);
+        }
+        let item_name = safe_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "unknown".into());
         let staged_path = staging_dir.join(&safe_path);
-        let final_path = target_dir.join(safe_path);
+        let final_path = target_dir.join(&safe_path);
         let is_top_level = matches!(&meta, PxpMeta::Item(_));
+        guard.check_entry(&safe_path, entry_size, is_dir)?;
         trace!(
             "Resolved extraction paths: staged={:?}, final={:?}",
             staged_path, final_path
@@ -205,24 +303,32 @@ where
         // by `reconcile` once the stream has fully completed. The whole staging dir is the
         // temp area now — no more per-item `.tmp_*_portal` dance.
         if !is_dir {
+            let head = guard.sniff(&safe_path, &mut entry).await?;
             trace!("Unpacking file to staging: {}", staged_path.display());
             if let Some(parent) = staged_path.parent() {
                 create_dir_all(parent).await?;
             }
-            let outfile = File::create(&staged_path).await?;
+            let mut outfile = File::create(&staged_path).await?;
+            let mtime = entry.header().mtime().unwrap_or(0);
+            if !head.is_empty() {
+                outfile.write_all(&head).await?;
+                emit(events, TransferEvent::Progress { bytes: head.len() as u64 });
+            }
 
-            if let Some(prog) = entry_item_progress.take() {
-                let mut writer = prog.wrap_write(Box::new(outfile));
-                tokio::io::copy(&mut entry, &mut *writer).await?;
-                prog.finish_and_clear();
-            } else if let Some(prog) = &active_dir_progress {
-                let mut reader = prog.wrap_read(Box::new(entry));
-                let mut outfile = outfile;
-                tokio::io::copy(&mut *reader, &mut outfile).await?;
+            if let Some(sink) = events {
+                let mut reader = Metered::new(&mut entry, sink);
+                tokio::io::copy(&mut reader, &mut outfile).await?;
             } else {
-                let mut outfile = outfile;
                 tokio::io::copy(&mut entry, &mut outfile).await?;
             }
+            // Older senders leave the modification time out; the file then keeps the
+            // time it was received.
+            if mtime > 0 {
+                let modified = UNIX_EPOCH + Duration::from_secs(mtime);
+                if let Err(e) = outfile.into_std().await.set_modified(modified) {
+                    debug!("Could not keep the modification time of {:?}: {}", staged_path, e);
+                }
+            }
         } else {
             trace!("Creating staging directory: {}", staged_path.display());
             create_dir_all(&staged_path).await?;
@@ -232,11 +338,16 @@ where
         // folder's staged dir, so conflict resolution happens once per folder — we treat a
         // whole folder as one unit instead of prompting for every single file in it.
         if is_top_level {
+            let bytes = match &meta {
+                PxpMeta::Item(TransferItem::Directory(d)) => d.total_size,
+                _ => entry_size,
+            };
             staged_items.push(StagedItem {
                 name: item_name.clone(),
                 staged_path: staged_path.clone(),
                 final_path,
                 is_dir,
+                bytes,
             });
             debug!("Item staged at {:?}", staged_path);
         }
@@ -268,13 +379,15 @@ where
                         "Self-check: file size matches manifest ({} bytes)",
                         f.file_size
                     );
-                    if let Some(prog) = &progress {
-                        prog.println(&format!(
-                            "Portal: File '{}' received successfully!",
-                            f.filename
-                        ));
-                    }
                     info!("Successfully verified and saved: {}", f.filename);
+                    emit(
+                        events,
+                        TransferEvent::ItemFinished {
+                            name: f.filename,
+                            kind: ItemKind::File,
+                            verified: true,
+                        },
+                    );
                 }
                 TransferItem::Directory(d) => {
                     if d.dirname != path.to_string_lossy().replace('\\', "/") {
@@ -287,6 +400,8 @@ where
                     }
                     info!("Successfully verified and saved directory: {}", d.dirname);
                 }
+                // Handled above; text items never get this far.
+                TransferItem::Text(_) => {}
             },
             PxpMeta::NestedFile(f) => {
                 debug!("Verifying nested item: {}", f.filename);
@@ -312,16 +427,8 @@ where
             }
         }
     }
-    if let Some(dir_prog) = active_dir_progress.take() {
-        dir_prog.finish_and_clear();
-        if let Some(dir_name) = pending_dir_success.take() {
-            if let Some(prog) = &progress {
-                prog.println(&format!(
-                    "Portal: Directory '{}' received successfully!",
-                    dir_name
-                ));
-            }
-        }
+    if let Some(name) = open_dir.take() {
+        finish_dir(events, name);
     }
     if items_processed != total_items {
         error!(
@@ -338,96 +445,104 @@ where
     Ok(())
 }
 
+/// Reports a folder whose entries have all been received and checked.
+fn finish_dir(events: Option<&dyn EventSink>, name: String) {
+    info!("Directory '{}' fully received", name);
+    emit(
+        events,
+        TransferEvent::ItemFinished {
+            name,
+            kind: ItemKind::Directory,
+            verified: true,
+        },
+    );
+}
+
+pub(crate) fn report_rename(events: Option<&dyn EventSink>, renamed: &RenamedEntry) {
+    emit(
+        events,
+        TransferEvent::Warning(TransferWarning::Renamed {
+            original: renamed.original.clone(),
+            renamed: renamed.renamed.clone(),
+        }),
+    );
+}
+
 /// Moves staged items into the target dir, resolving any filename collisions now that the
 /// stream has fully completed — so conflict prompts never interrupt the progress UI.
+/// `router` may send individual items to other dirs; conflicts are checked where each item
+/// actually lands. Returns where every item ended up, in order. Every collision is reported
+/// to `events` with the action taken.
 ///
-/// Strategy memoization is shared across items: choosing "Overwrite All", "Rename All" or
-/// "Skip All" applies to every remaining item without prompting again.
+/// The resolver is given a `ConflictInfo` describing both copies. Strategy memoization is
+/// shared across items: choosing "Overwrite All", "Rename All" or "Skip All" applies to every
+/// remaining item without asking the resolver again.
 pub async fn reconcile(
     staged: &StagedTransfer,
     conflict_resolver: Option<&dyn ConflictResolver>,
-) -> Result<()> {
-    let mut global_strategy = ConflictStrategy::Prompt;
+    router: Option<&dyn ItemRouter>,
+    events: Option<&dyn EventSink>,
+) -> Result<Vec<PlacedItem>> {
+    let mut conflicts = Conflicts {
+        resolver: conflict_resolver,
+        events,
+        strategy: ConflictStrategy::Prompt,
+    };
+    let mut placed = Vec::with_capacity(staged.items.len());
+    emit(events, TransferEvent::Phase(TransferPhase::Reconciling));
 
     for item in &staged.items {
-        let final_exists = try_exists(&item.final_path).await?;
         let mut final_path = item.final_path.clone();
+        if let Some(dir) = router.and_then(|r| r.route(&item.name, item.bytes, item.is_dir))
+            && let Some(file_name) = item.final_path.file_name()
+        {
+            final_path = dir.join(file_name);
+            info!("Routing {:?} to {:?}", item.name, final_path);
+        }
 
-        if final_exists && global_strategy != ConflictStrategy::OverwriteAll {
-            match global_strategy {
-                ConflictStrategy::SkipAll => {
-                    debug!("Strategy SkipAll: skipping {:?}", item.name);
-                    remove_staged_item(item).await?;
-                    continue;
-                }
-                ConflictStrategy::RenameAll => {
-                    final_path = get_unused_path(final_path).await;
-                    debug!("Strategy RenameAll: new path {:?}", final_path);
-                }
-                _ => {
-                    if let Some(resolver) = conflict_resolver {
-                        let action = resolver.resolve(&item.name)?;
-                        trace!("Conflict resolver returned: {:?}", action);
-
-                        match action {
-                            ConflictAction::Overwrite => {
-                                info!("Chose to overwrite {:?}", item.name)
-                            }
-                            ConflictAction::OverwriteAll => {
-                                info!("Enabled Overwrite All strategy");
-                                global_strategy = ConflictStrategy::OverwriteAll;
-                            }
-                            ConflictAction::Rename => {
-                                final_path = get_unused_path(final_path).await;
-                                info!("Chose to rename to {:?}", final_path);
-                            }
-                            ConflictAction::RenameAll => {
-                                info!("Enabled Rename All strategy");
-                                global_strategy = ConflictStrategy::RenameAll;
-                                final_path = get_unused_path(final_path).await;
-                            }
-                            ConflictAction::Skip => {
-                                info!("Skipped item {:?}", item.name);
-                                remove_staged_item(item).await?;
-                                continue;
-                            }
-                            ConflictAction::SkipAll => {
-                                info!("Enabled Skip All strategy");
-                                global_strategy = ConflictStrategy::SkipAll;
-                                remove_staged_item(item).await?;
-                                continue;
-                            }
-                        }
-                    } else {
-                        // No conflict resolver provided, default to overwrite
-                        info!(
-                            "No conflict resolver: defaulting to overwrite {:?}",
-                            item.name
-                        );
-                    }
-                }
+        let placement = if try_exists(&final_path).await? {
+            conflicts
+                .settle(&item.name, &item.staged_path, item.is_dir, &mut final_path)
+                .await?
+        } else {
+            Placement::Move
+        };
+
+        match placement {
+            Placement::Skip => {
+                remove_staged_item(item).await?;
+                placed.push(PlacedItem {
+                    name: item.name.clone(),
+                    path: None,
+                });
+                continue;
+            }
+            Placement::Merge { ask } => {
+                conflicts
+                    .merge(&item.name, &item.staged_path, &final_path, ask)
+                    .await?;
+                debug!("Item merged into target path: {:?}", final_path);
+                placed.push(PlacedItem {
+                    name: item.name.clone(),
+                    path: Some(final_path),
+                });
+                continue;
             }
+            Placement::Move => {}
         }
 
         // Move the staged item into its final location. Staging lives inside the target
-        // dir, so this is always a same-filesystem rename (fast even on external drives).
+        // dir, so this is a same-filesystem rename (fast even on external drives) unless
+        // the item was routed to another filesystem.
         if let Some(parent) = final_path.parent() {
             create_dir_all(parent).await?;
         }
-        if !item.is_dir {
-            if try_exists(&final_path).await? {
-                trace!("Overwriting existing file at {:?}", final_path);
-                let _ = remove_file(&final_path).await;
-            }
-            rename(&item.staged_path, &final_path).await?;
-        } else {
-            if try_exists(&final_path).await? {
-                trace!("Overwriting existing directory at {:?}", final_path);
-                let _ = remove_dir_all(&final_path).await;
-            }
-            rename(&item.staged_path, &final_path).await?;
-        }
+        replace_item(&item.staged_path, &final_path, item.is_dir).await?;
         debug!("Item reconciled at target path: {:?}", final_path);
+        placed.push(PlacedItem {
+            name: item.name.clone(),
+            path: Some(final_path),
+        });
     }
 
     // Clean up the staging root now that every item has been moved or skipped,
@@ -447,7 +562,330 @@ pub async fn reconcile(
         staged.items.len(),
         staged.target_dir.display()
     );
-    Ok(())
+    emit(events, TransferEvent::Phase(TransferPhase::Complete));
+    Ok(placed)
+}
+
+/// What reconcile does with a staged item once any conflict is settled.
+#[derive(Clone, Copy)]
+enum Placement {
+    /// Move it to its (possibly renamed) final path, replacing whatever is there.
+    Move,
+    /// Move a folder's contents into the existing folder at the final path. With `ask`,
+    /// every file that is in both goes to the resolver; otherwise the received one wins.
+    Merge { ask: bool },
+    /// Drop it from staging.
+    Skip,
+}
+
+/// Settles the collisions of one reconcile pass, remembering the "All" answers across its
+/// items and the files inside merged folders.
+struct Conflicts<'a> {
+    resolver: Option<&'a dyn ConflictResolver>,
+    events: Option<&'a dyn EventSink>,
+    strategy: ConflictStrategy,
+}
+
+impl Conflicts<'_> {
+    /// Decides what happens to `incoming` now that something is already at `final_path`,
+    /// which is updated when the item is to be saved under another name. `name` is what
+    /// the resolver and `events` are told about.
+    async fn settle(
+        &mut self,
+        name: &str,
+        incoming: &Path,
+        incoming_is_dir: bool,
+        final_path: &mut PathBuf,
+    ) -> Result<Placement> {
+        let both_dirs = incoming_is_dir
+            && tokio::fs::metadata(&*final_path)
+                .await
+                .map(|meta| meta.is_dir())
+                .unwrap_or(false);
+        // Overwriting a folder with another merges the two, so files that only exist on
+        // this side are kept.
+        let overwrite = if both_dirs {
+            Placement::Merge { ask: false }
+        } else {
+            Placement::Move
+        };
+        let placement = match self.strategy {
+            ConflictStrategy::SkipAll => {
+                debug!("Strategy SkipAll: skipping {:?}", name);
+                self.report(name, ConflictAction::SkipAll);
+                Placement::Skip
+            }
+            ConflictStrategy::RenameAll => {
+                *final_path = get_unused_path(final_path.clone()).await;
+                debug!("Strategy RenameAll: new path {:?}", final_path);
+                self.report(name, ConflictAction::RenameAll);
+                Placement::Move
+            }
+            ConflictStrategy::OverwriteAll => {
+                debug!("Strategy OverwriteAll: replacing {:?}", name);
+                self.report(name, ConflictAction::OverwriteAll);
+                overwrite
+            }
+            ConflictStrategy::Prompt => {
+                if let Some(resolver) = self.resolver {
+                    let info = ConflictInfo::new(name, final_path, incoming).await?;
+                    let action = resolver.resolve(&info).await?;
+                    trace!("Conflict resolver returned: {:?}", action);
+                    self.report(name, action.clone());
+                    self.apply(action, &info, final_path, overwrite).await?
+                } else {
+                    // No conflict resolver provided, default to overwrite
+                    info!("No conflict resolver: defaulting to overwrite {:?}", name);
+                    self.report(name, ConflictAction::Overwrite);
+                    overwrite
+                }
+            }
+        };
+        Ok(placement)
+    }
+
+    /// Turns the resolver's answer for one conflict into a placement, updating the
+    /// remembered strategy for the "All" answers and `final_path` for the renaming ones.
+    /// `overwrite` is what overwriting means for this item.
+    async fn apply(
+        &mut self,
+        action: ConflictAction,
+        info: &ConflictInfo,
+        final_path: &mut PathBuf,
+        overwrite: Placement,
+    ) -> Result<Placement> {
+        let keep_incoming = |keep: bool| if keep { overwrite } else { Placement::Skip };
+        let placement = match action {
+            ConflictAction::Overwrite => {
+                info!("Chose to overwrite {:?}", info.name);
+                overwrite
+            }
+            ConflictAction::OverwriteAll => {
+                info!("Enabled Overwrite All strategy");
+                self.strategy = ConflictStrategy::OverwriteAll;
+                overwrite
+            }
+            ConflictAction::Rename => {
+                *final_path = get_unused_path(final_path.clone()).await;
+                info!("Chose to rename to {:?}", final_path);
+                Placement::Move
+            }
+            ConflictAction::RenameAll => {
+                info!("Enabled Rename All strategy");
+                self.strategy = ConflictStrategy::RenameAll;
+                *final_path = get_unused_path(final_path.clone()).await;
+                Placement::Move
+            }
+            ConflictAction::Skip => {
+                info!("Skipped item {:?}", info.name);
+                Placement::Skip
+            }
+            ConflictAction::SkipAll => {
+                info!("Enabled Skip All strategy");
+                self.strategy = ConflictStrategy::SkipAll;
+                Placement::Skip
+            }
+            ConflictAction::KeepNewer => {
+                let newer = info.incoming_is_newer();
+                info!("Keeping the newer copy of {:?} (received: {})", info.name, newer);
+                keep_incoming(newer)
+            }
+            ConflictAction::KeepLarger => {
+                let larger = info.incoming.bytes > info.existing.bytes;
+                info!("Keeping the larger copy of {:?} (received: {})", info.name, larger);
+                keep_incoming(larger)
+            }
+            ConflictAction::SkipIfIdentical => {
+                if info.identical().await? {
+                    info!("Skipped {:?}: identical to the existing copy", info.name);
+                    Placement::Skip
+                } else {
+                    *final_path = get_unused_path(final_path.clone()).await;
+                    info!(
+                        "{:?} differs from the existing copy; saving as {:?}",
+                        info.name, final_path
+                    );
+                    Placement::Move
+                }
+            }
+            ConflictAction::RenameTo(name) => {
+                let mut components = Path::new(&name).components();
+                let valid = matches!(
+                    (components.next(), components.next()),
+                    (Some(std::path::Component::Normal(_)), None)
+                );
+                if !valid {
+                    return Err(PxpError::ConflictResolution(format!(
+                        "'{}' is not a valid name",
+                        name
+                    )));
+                }
+                let renamed = final_path.with_file_name(&name);
+                *final_path = if try_exists(&renamed).await? {
+                    get_unused_path(renamed).await
+                } else {
+                    renamed
+                };
+                info!("Chose to rename to {:?}", final_path);
+                Placement::Move
+            }
+            ConflictAction::MergeDirectories => {
+                if info.existing.is_dir && info.incoming.is_dir {
+                    info!("Chose to merge {:?} file by file", info.name);
+                    Placement::Merge { ask: true }
+                } else {
+                    info!("{:?} is not a folder on both sides; overwriting", info.name);
+                    Placement::Move
+                }
+            }
+            ConflictAction::ReplaceDirectory => {
+                info!("Chose to replace {:?} as a whole", info.name);
+                Placement::Move
+            }
+        };
+        Ok(placement)
+    }
+
+    /// Moves everything under the staged folder `from` into the existing folder `to`.
+    /// Folders in both are merged in turn and anything only in `to` is left alone. A file
+    /// in both is settled like a top-level item when `ask` is set, and replaced otherwise.
+    async fn merge(&mut self, name: &str, from: &Path, to: &Path, ask: bool) -> Result<()> {
+        let mut pending = vec![(from.to_path_buf(), to.to_path_buf())];
+        while let Some((from_dir, to_dir)) = pending.pop() {
+            let mut entries = read_dir(&from_dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let source = entry.path();
+                let mut target = to_dir.join(entry.file_name());
+                let is_dir = entry.file_type().await?.is_dir();
+                let existing = tokio::fs::symlink_metadata(&target).await.ok();
+                if is_dir && existing.as_ref().is_some_and(|meta| meta.is_dir()) {
+                    pending.push((source, target));
+                    continue;
+                }
+
+                if existing.is_some() {
+                    let relative = source.strip_prefix(from).unwrap_or(&source);
+                    let entry_name = format!(
+                        "{}/{}",
+                        name,
+                        relative.to_string_lossy().replace('\\', "/")
+                    );
+                    let placement = if ask {
+                        self.settle(&entry_name, &source, is_dir, &mut target).await?
+                    } else {
+                        self.report(&entry_name, ConflictAction::Overwrite);
+                        Placement::Move
+                    };
+                    if let Placement::Skip = placement {
+                        if is_dir {
+                            let _ = remove_dir_all(&source).await;
+                        } else {
+                            let _ = remove_file(&source).await;
+                        }
+                        continue;
+                    }
+                }
+                trace!("Merging {:?} into {:?}", source, target);
+                replace_item(&source, &target, is_dir).await?;
+            }
+        }
+        let _ = remove_dir_all(from).await;
+        Ok(())
+    }
+
+    fn report(&self, name: &str, action: ConflictAction) {
+        emit(
+            self.events,
+            TransferEvent::Conflict {
+                name: name.to_string(),
+                action,
+            },
+        );
+    }
+}
+
+/// Moves `from` to `to`, first removing whatever is at `to`.
+async fn replace_item(from: &Path, to: &Path, is_dir: bool) -> Result<()> {
+    match tokio::fs::symlink_metadata(to).await {
+        Ok(meta) if meta.is_dir() => {
+            trace!("Overwriting existing directory at {:?}", to);
+            let _ = remove_dir_all(to).await;
+        }
+        Ok(_) => {
+            trace!("Overwriting existing file at {:?}", to);
+            let _ = remove_file(to).await;
+        }
+        Err(_) => {}
+    }
+    move_item(from, to, is_dir).await
+}
+
+/// Renames `from` to `to`, falling back to copy-and-delete when they are on different
+/// filesystems (only possible for routed items).
+async fn move_item(from: &Path, to: &Path, is_dir: bool) -> Result<()> {
+    match rename(from, to).await {
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            debug!("{:?} is on another filesystem; copying instead", to);
+            if is_dir {
+                copy_dir(from.to_path_buf(), to.to_path_buf()).await?;
+                let _ = remove_dir_all(from).await;
+            } else {
+                copy(from, to).await?;
+                let _ = remove_file(from).await;
+            }
+            Ok(())
+        }
+        other => Ok(other?),
+    }
+}
+
+/// Copies a directory tree. Boxed because it recurses.
+fn copy_dir(
+    from: PathBuf,
+    to: PathBuf,
+) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>> + Send>> {
+    Box::pin(async move {
+        create_dir_all(&to).await?;
+        let mut entries = read_dir(&from).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let target = to.join(entry.file_name());
+            if entry.file_type().await?.is_dir() {
+                copy_dir(entry.path(), target).await?;
+            } else {
+                copy(entry.path(), target).await?;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Saves a received text item as `name` in `target_dir`. An existing file is never
+/// replaced: the text goes under the next free name instead (`snippet (1).txt`, ...).
+pub async fn save_text(target_dir: &Path, name: &str, text: &str) -> Result<PathBuf> {
+    create_dir_all(target_dir).await?;
+    let mut path = target_dir.join(name);
+    if try_exists(&path).await? {
+        path = get_unused_path(path).await;
+    }
+    write(&path, text).await?;
+    info!("Saved text item to {:?}", path);
+    Ok(path)
+}
+
+/// Throws away everything staged for a transfer without moving it into the target dir, e.g.
+/// after a security policy violation where even the items that passed shouldn't be kept.
+pub async fn discard(staged: &StagedTransfer) {
+    let _ = remove_dir_all(&staged.staging_dir).await;
+    if let Some(stage) = staged.staging_dir.parent() {
+        let _ = remove_dir(stage).await;
+        if let Some(portal) = stage.parent() {
+            let _ = remove_dir(portal).await;
+        }
+    }
+    info!(
+        "Discarded {} staged item(s) without saving them",
+        staged.items.len()
+    );
 }
 
 /// Deletes a skipped item from staging so it doesn't linger and get mistaken for a
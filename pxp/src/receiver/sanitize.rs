@@ -0,0 +1,155 @@
+use {
+    crate::metadata::RenamedEntry,
+    std::{
+        collections::HashMap,
+        path::{Component, Path, PathBuf},
+    },
+    tracing::{debug, warn},
+    unicode_normalization::UnicodeNormalization,
+};
+
+/// Names Windows reserves for devices, with or without an extension.
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Maps incoming archive paths to names that are safe on every platform Portal runs on.
+/// The same input always gets the same output, whatever OS the receiver is on, so a folder
+/// sent twice lands the same way twice.
+///
+/// Each path component is NFC-normalized, has characters Windows forbids replaced with `_`,
+/// loses trailing dots and spaces, and gets a `_` appended to reserved device names
+/// (`CON` → `CON_`, `aux.txt` → `aux_.txt`). Two entries that would end up as the same file on
+/// a case-insensitive filesystem get a ` (n)` suffix on the later one.
+#[derive(Default)]
+pub(crate) struct PathSanitizer {
+    /// Where each incoming directory was placed, so its children follow a renamed parent.
+    dirs: HashMap<PathBuf, PathBuf>,
+    /// Case-folded output paths already handed out, and the incoming path that got each one.
+    taken: HashMap<String, PathBuf>,
+    renamed: Vec<RenamedEntry>,
+}
+
+impl PathSanitizer {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the path `path` should be written to, relative to the staging dir. `path`
+    /// must already be reduced to its normal components.
+    pub(crate) fn sanitize(&mut self, path: &Path, is_dir: bool) -> PathBuf {
+        self.place(path, is_dir, false)
+    }
+
+    /// Like `sanitize`, for a file that is separate from every earlier one even when it has
+    /// the same name, as with the parts of an upload form: a repeated name gets a ` (n)`
+    /// suffix rather than the same path.
+    pub(crate) fn sanitize_distinct(&mut self, path: &Path) -> PathBuf {
+        self.place(path, false, true)
+    }
+
+    fn place(&mut self, path: &Path, is_dir: bool, distinct: bool) -> PathBuf {
+        let Some(name) = path.file_name() else {
+            return path.to_path_buf();
+        };
+        let parent = path.parent().unwrap_or(Path::new(""));
+        let safe_parent = match self.dirs.get(parent) {
+            Some(dir) => dir.clone(),
+            // Parents normally arrive as their own entries first; fall back to cleaning each
+            // component on its own.
+            None => parent
+                .components()
+                .filter_map(|c| match c {
+                    Component::Normal(part) => Some(sanitize_component(&part.to_string_lossy())),
+                    _ => None,
+                })
+                .collect(),
+        };
+
+        let original_name = name.to_string_lossy();
+        let clean_name = sanitize_component(&original_name);
+        let mut candidate = safe_parent.join(&clean_name);
+        let mut n = 1;
+        loop {
+            match self.taken.get(&fold(&candidate)) {
+                Some(owner) if distinct || owner != path => {
+                    candidate = safe_parent.join(with_suffix(&clean_name, n));
+                    n += 1;
+                }
+                _ => break,
+            }
+        }
+        if n > 1 {
+            warn!(
+                "'{}' collides with another entry (at least on case-insensitive filesystems)",
+                path.display()
+            );
+        }
+
+        self.taken.insert(fold(&candidate), path.to_path_buf());
+        if is_dir {
+            self.dirs.insert(path.to_path_buf(), candidate.clone());
+        }
+
+        // Only a changed final component counts as a rename; children of a renamed folder
+        // just follow it. A change of Unicode normalization alone looks identical to the
+        // user, so it isn't reported.
+        let normalized: String = original_name.nfc().collect();
+        if candidate.file_name().map(|f| f.to_string_lossy()) != Some(normalized.into()) {
+            let entry = RenamedEntry {
+                original: to_slash(path),
+                renamed: to_slash(&candidate),
+            };
+            debug!("Sanitized '{}' to '{}'", entry.original, entry.renamed);
+            self.renamed.push(entry);
+        }
+        candidate
+    }
+
+    /// Renames made since the last call.
+    pub(crate) fn take_renamed(&mut self) -> Vec<RenamedEntry> {
+        std::mem::take(&mut self.renamed)
+    }
+}
+
+/// Makes one path component safe to create on Windows, macOS and Linux.
+pub fn sanitize_component(name: &str) -> String {
+    let mut clean: String = name
+        .nfc()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    let trimmed_len = clean.trim_end_matches(['.', ' ']).len();
+    clean.truncate(trimmed_len);
+    if clean.is_empty() {
+        return "_".to_string();
+    }
+
+    let stem = clean.split('.').next().unwrap_or_default();
+    if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem.trim_end())) {
+        clean.insert(stem.len(), '_');
+    }
+    clean
+}
+
+/// Case-insensitive key for collision checks.
+fn fold(path: &Path) -> String {
+    to_slash(path).to_lowercase()
+}
+
+fn to_slash(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// `report.pdf` → `report (1).pdf`, matching how conflicts are renamed at reconcile time.
+fn with_suffix(name: &str, n: u32) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{} ({}).{}", stem, n, ext),
+        _ => format!("{} ({})", name, n),
+    }
+}
@@ -0,0 +1,42 @@
+use {
+    crate::error::{PxpError, Result},
+    std::path::Path,
+    tracing::{debug, info, trace, warn},
+};
+
+/// Bytes available to this user on the filesystem holding `dir`. The download directory
+/// may not exist yet, so the nearest existing ancestor is measured instead. Returns `None`
+/// when the platform can't tell us.
+pub fn available_space(dir: &Path) -> Option<u64> {
+    let existing = dir.ancestors().find(|p| p.exists())?;
+    trace!("Measuring free space for {:?} via {:?}", dir, existing);
+    match fs4::available_space(existing) {
+        Ok(bytes) => Some(bytes),
+        Err(e) => {
+            debug!("Could not read free space for {:?}: {}", existing, e);
+            None
+        }
+    }
+}
+
+/// Checks that the filesystem holding `target_dir` can take `needed` bytes. Items are staged
+/// under the target dir and then moved, so only one copy ever needs to fit. If free space
+/// can't be measured the check passes; the stream would still fail cleanly on a full disk.
+pub fn check_free_space(target_dir: &Path, needed: u64) -> Result<()> {
+    let Some(available) = available_space(target_dir) else {
+        warn!("Free space for {:?} is unknown; skipping the pre-flight check", target_dir);
+        return Ok(());
+    };
+    if needed > available {
+        warn!(
+            "Not enough space in {:?}: need {} bytes, {} available",
+            target_dir, needed, available
+        );
+        return Err(PxpError::InsufficientSpace { needed, available });
+    }
+    info!(
+        "Free space check passed: need {} bytes, {} available",
+        needed, available
+    );
+    Ok(())
+}
@@ -1,10 +1,12 @@
 use {
     crate::{
         metadata::ReceiveSummary,
+        net::{IdleTimeout, Timeouts},
+        receiver::policy::ReceivePolicy,
         receiver::receive_item::{receive_item, StagedItem, StagedTransfer},
-        TransferProgress,
+        event::{EventSink, TransferEvent, TransferPhase, emit},
     },
-    crate::error::Result,
+    crate::error::{PxpError, Result},
     async_compression::tokio::bufread::GzipDecoder,
     std::{
         path::{Path, PathBuf},
@@ -20,17 +22,25 @@ use {
 
 /// Returns the stream outcome, the staged items (even when the stream failed part-way,
 /// so partial transfers can still be reconciled into the target dir), and the summary.
+/// Entries that break `policy` abort the stream before they reach the disk.
+#[allow(clippy::too_many_arguments)]
 pub async fn receive_stream(
     socket: TcpStream,
     compressed: bool,
-    target_dir: &PathBuf,
+    target_dir: &Path,
     total_items: u32,
-    progress: Option<&dyn TransferProgress>,
+    policy: &ReceivePolicy,
+    timeouts: &Timeouts,
+    events: Option<&dyn EventSink>,
 ) -> (Result<()>, StagedTransfer, ReceiveSummary) {
     let mut summary = ReceiveSummary {
         items: Vec::new(),
         total_bytes: 0,
+        renamed: Vec::new(),
     };
+    // A sender that goes silent mid-stream surfaces as a typed transfer timeout rather
+    // than leaving the receiver parked on a read forever.
+    let socket = IdleTimeout::new(socket, timeouts.idle);
     let reader: Box<dyn AsyncRead + Unpin + Send> = if compressed {
         debug!("Initializing Gzip decoder and Tar archive reader...");
         Box::new(GzipDecoder::new(BufReader::new(socket)))
@@ -39,44 +49,29 @@ pub async fn receive_stream(
         Box::new(BufReader::new(socket))
     };
     let mut archive = Archive::new(reader);
-
-    // Clean up stale staging dirs left behind by interrupted runs so they never
-    // accumulate. Recent ones are kept in case another transfer is still active.
-    sweep_stale_staging(target_dir).await;
-
-    // The staging dir lives inside the target dir so the final reconcile move is always
-    // a same-filesystem rename, even when the target is an external drive. All portal
-    // artifacts are grouped under `.portal/stage/`, one subdir per transfer.
-    let staging_dir = target_dir
-        .join(".portal")
-        .join("stage")
-        .join(format!(
-            "{}",
-            SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_nanos()
-        ));
+    let staging_dir = new_staging_dir(target_dir).await;
 
     let mut staged_items: Vec<StagedItem> = Vec::new();
+    emit(events, TransferEvent::Phase(TransferPhase::Streaming));
     let result = receive_item(
         &mut archive,
         target_dir,
         &staging_dir,
         total_items,
-        progress,
+        policy,
+        events,
         &mut summary,
         &mut staged_items,
     )
     .await;
 
-    if let Err(err) = result {
+    if let Err(err) = result.map_err(PxpError::stalled) {
         // Connection cut or protocol error. The items that already finished staging are
         // kept so the caller can still move them into the target dir.
         let staged = StagedTransfer {
             items: staged_items,
             staging_dir,
-            target_dir: target_dir.clone(),
+            target_dir: target_dir.to_path_buf(),
         };
         return (Err(err), staged, summary);
     }
@@ -89,11 +84,29 @@ pub async fn receive_stream(
     let staged = StagedTransfer {
         items: staged_items,
         staging_dir,
-        target_dir: target_dir.clone(),
+        target_dir: target_dir.to_path_buf(),
     };
     (Ok(()), staged, summary)
 }
 
+/// Picks the staging dir for a new transfer into `target_dir`. It lives inside the target
+/// dir so the final reconcile move is always a same-filesystem rename, even when the target
+/// is an external drive. All portal artifacts are grouped under `.portal/stage/`, one subdir
+/// per transfer. The dir itself is created once something is staged in it.
+pub(crate) async fn new_staging_dir(target_dir: &Path) -> PathBuf {
+    // Clean up stale staging dirs left behind by interrupted runs so they never
+    // accumulate. Recent ones are kept in case another transfer is still active.
+    sweep_stale_staging(target_dir).await;
+
+    target_dir.join(".portal").join("stage").join(format!(
+        "{}",
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos()
+    ))
+}
+
 /// Removes per-transfer staging subdirs under `.portal/stage/` that are older than 24h,
 /// then prunes the now-empty `.portal/stage` and `.portal` parents.
 async fn sweep_stale_staging(target_dir: &Path) {
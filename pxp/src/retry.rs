@@ -0,0 +1,114 @@
+use {
+    crate::error::{PxpError, TimeoutPhase},
+    std::{io::ErrorKind, time::Duration},
+};
+
+/// Which failures a `RetryPolicy` is allowed to retry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryOn {
+    /// Only failures before any data was streamed: connect, handshake, identity.
+    Connect,
+    /// Connection failures and transfers that dropped mid-stream.
+    All,
+}
+
+/// How often, and how patiently, a failed session is attempted again.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Extra attempts after the first one. Zero disables retrying.
+    pub retries: u32,
+    /// Wait before the first retry. Doubles on every further attempt.
+    pub initial_backoff: Duration,
+    /// Upper bound for the wait between attempts.
+    pub max_backoff: Duration,
+    pub retry_on: RetryOn,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            retry_on: RetryOn::All,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries.
+    pub fn none() -> Self {
+        Self {
+            retries: 0,
+            ..Self::default()
+        }
+    }
+
+    /// Total number of attempts this policy allows, including the first.
+    pub fn max_attempts(&self) -> u32 {
+        self.retries.saturating_add(1)
+    }
+
+    /// Wait before attempt number `next_attempt` (2 for the first retry), using exponential
+    /// backoff capped at `max_backoff`.
+    pub fn backoff(&self, next_attempt: u32) -> Duration {
+        let exponent = next_attempt.saturating_sub(2).min(16);
+        self.initial_backoff
+            .saturating_mul(1u32 << exponent)
+            .min(self.max_backoff)
+    }
+
+    /// Whether `err`, raised by attempt number `attempt`, should be tried again.
+    pub fn should_retry(&self, err: &PxpError, attempt: u32) -> bool {
+        if attempt >= self.max_attempts() {
+            return false;
+        }
+        match self.retry_on {
+            RetryOn::Connect => err.is_connect_failure(),
+            RetryOn::All => err.is_connect_failure() || err.is_transfer_drop(),
+        }
+    }
+}
+
+impl PxpError {
+    /// Failures that happen before any data is streamed and are likely to go away on their
+    /// own: the receiver restarted, its IP changed, a stale beacon pointed at the wrong node, or
+    /// the receiver was still busy with someone else. A receiver slow to accept is not one:
+    /// its user may still be answering, and reconnecting would ask them again.
+    pub fn is_connect_failure(&self) -> bool {
+        match self {
+            PxpError::ConnectionFailed { .. }
+            | PxpError::IdentityMismatch { .. }
+            | PxpError::ReceiverBusy { .. } => true,
+            PxpError::Timeout { phase } => {
+                matches!(phase, TimeoutPhase::Connect | TimeoutPhase::Handshake)
+            }
+            _ => false,
+        }
+    }
+
+    /// Failures where an established stream dropped or stalled part-way through.
+    pub fn is_transfer_drop(&self) -> bool {
+        match self {
+            PxpError::Timeout {
+                phase: TimeoutPhase::Transfer,
+            } => true,
+            PxpError::Io(err) => is_connection_io_error(err.kind()),
+            _ => false,
+        }
+    }
+}
+
+/// I/O errors that mean "the connection went away" rather than "the data is bad".
+pub(crate) fn is_connection_io_error(kind: ErrorKind) -> bool {
+    matches!(
+        kind,
+        ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::BrokenPipe
+            | ErrorKind::NotConnected
+            | ErrorKind::UnexpectedEof
+            | ErrorKind::TimedOut
+    )
+}
@@ -0,0 +1,179 @@
+use {
+    std::{
+        io,
+        pin::Pin,
+        sync::{Arc, Mutex},
+        task::{Context, Poll, ready},
+    },
+    tokio::io::AsyncWrite,
+    tracing::warn,
+};
+
+/// Why each sink of a `FanOut` was dropped, by sink index. `None` means it is still live.
+/// Shared with the caller so failures stay readable even when the writer itself is lost
+/// inside a failed tar builder.
+pub(crate) type SinkErrors = Arc<Mutex<Vec<Option<io::Error>>>>;
+
+/// Writes every byte to several sinks, so one tar stream (and one read of each file) feeds
+/// many receivers. A sink that errors is dropped and its error recorded; the others carry
+/// on. Writes only fail once every sink has failed.
+///
+/// The stream moves at the pace of the slowest live sink. Each sink is expected to carry its
+/// own idle timeout, so a stalled one is dropped instead of holding the rest back forever.
+pub(crate) struct FanOut<W> {
+    sinks: Vec<Sink<W>>,
+    errors: SinkErrors,
+    /// Bytes accepted from the caller but not yet written to every live sink.
+    pending: Vec<u8>,
+}
+
+struct Sink<W> {
+    writer: W,
+    /// How much of `pending` this sink has taken.
+    offset: usize,
+    live: bool,
+    shut_down: bool,
+}
+
+impl<W: AsyncWrite + Unpin> FanOut<W> {
+    pub(crate) fn new(writers: Vec<W>) -> (Self, SinkErrors) {
+        let errors: SinkErrors = Arc::new(Mutex::new(writers.iter().map(|_| None).collect()));
+        let sinks = writers
+            .into_iter()
+            .map(|writer| Sink {
+                writer,
+                offset: 0,
+                live: true,
+                shut_down: false,
+            })
+            .collect();
+        let fanout = Self {
+            sinks,
+            errors: errors.clone(),
+            pending: Vec::new(),
+        };
+        (fanout, errors)
+    }
+
+    fn fail(&mut self, index: usize, err: io::Error) {
+        warn!("Dropping receiver {} from the transfer: {}", index + 1, err);
+        self.sinks[index].live = false;
+        if let Ok(mut errors) = self.errors.lock() {
+            errors[index] = Some(err);
+        }
+    }
+
+    /// Errors once no sink is left to write to.
+    fn check_live(&self) -> io::Result<()> {
+        if self.sinks.iter().any(|s| s.live) {
+            return Ok(());
+        }
+        Err(io::Error::new(
+            io::ErrorKind::BrokenPipe,
+            "every receiver dropped out of the transfer",
+        ))
+    }
+
+    /// Pushes `pending` to every live sink. Ready once all of them have taken all of it.
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut waiting = false;
+        for index in 0..self.sinks.len() {
+            let sink = &mut self.sinks[index];
+            let mut failure = None;
+            while sink.live && sink.offset < self.pending.len() {
+                match Pin::new(&mut sink.writer).poll_write(cx, &self.pending[sink.offset..]) {
+                    Poll::Ready(Ok(0)) => {
+                        failure = Some(io::Error::from(io::ErrorKind::WriteZero));
+                        break;
+                    }
+                    Poll::Ready(Ok(n)) => sink.offset += n,
+                    Poll::Ready(Err(e)) => {
+                        failure = Some(e);
+                        break;
+                    }
+                    Poll::Pending => {
+                        waiting = true;
+                        break;
+                    }
+                }
+            }
+            if let Some(err) = failure {
+                self.fail(index, err);
+            }
+        }
+        self.check_live()?;
+        if waiting {
+            return Poll::Pending;
+        }
+        self.pending.clear();
+        for sink in &mut self.sinks {
+            sink.offset = 0;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for FanOut<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        this.pending.extend_from_slice(buf);
+        // Start pushing right away. If a sink can't take it all yet, the rest goes out on
+        // the next write or flush, which waits for it.
+        if let Poll::Ready(Err(e)) = this.poll_drain(cx) {
+            return Poll::Ready(Err(e));
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        let mut waiting = false;
+        for index in 0..this.sinks.len() {
+            if !this.sinks[index].live {
+                continue;
+            }
+            match Pin::new(&mut this.sinks[index].writer).poll_flush(cx) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(e)) => this.fail(index, e),
+                Poll::Pending => waiting = true,
+            }
+        }
+        this.check_live()?;
+        if waiting {
+            Poll::Pending
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        let mut waiting = false;
+        for index in 0..this.sinks.len() {
+            if !this.sinks[index].live || this.sinks[index].shut_down {
+                continue;
+            }
+            match Pin::new(&mut this.sinks[index].writer).poll_shutdown(cx) {
+                Poll::Ready(Ok(())) => this.sinks[index].shut_down = true,
+                Poll::Ready(Err(e)) => this.fail(index, e),
+                Poll::Pending => waiting = true,
+            }
+        }
+        this.check_live()?;
+        if waiting {
+            Poll::Pending
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+}
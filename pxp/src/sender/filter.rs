@@ -0,0 +1,147 @@
+use {
+    crate::error::{PxpError, Result},
+    ignore::{WalkBuilder, overrides::OverrideBuilder},
+    std::path::{Path, PathBuf},
+    tokio::sync::mpsc,
+    tokio_stream::wrappers::ReceiverStream,
+    tracing::{debug, trace},
+};
+
+/// Name of the Portal-specific ignore file, honoured alongside `.gitignore` when
+/// ignore files are respected. Uses the same syntax as `.gitignore`.
+pub const PORTAL_IGNORE_FILE: &str = ".portalignore";
+
+/// Decides which entries under a directory are part of a transfer. Applied once, by
+/// `scan_directory`, whose cached entry list is then reused for streaming.
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+    excludes: Vec<String>,
+    respect_ignore_files: bool,
+}
+
+/// A single file or directory found while walking a directory item.
+#[derive(Debug, Clone)]
+pub struct WalkEntry {
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub size: u64,
+}
+
+impl PathFilter {
+    /// Builds a filter from gitignore-style exclude globs (e.g. `target`, `*.log`,
+    /// `build/**/*.o`). When `respect_ignore_files` is set, `.gitignore` and
+    /// `.portalignore` files found in the tree are honoured too.
+    pub fn new(excludes: Vec<String>, respect_ignore_files: bool) -> Result<Self> {
+        // Compile the globs once up front so a typo fails before we connect to anyone,
+        // not halfway through walking the first directory.
+        let mut builder = OverrideBuilder::new(".");
+        for pattern in &excludes {
+            builder
+                .add(&format!("!{}", pattern))
+                .map_err(|e| PxpError::Pattern(format!("'{}': {}", pattern, e)))?;
+        }
+        builder
+            .build()
+            .map_err(|e| PxpError::Pattern(e.to_string()))?;
+
+        debug!(
+            "Path filter built: {} exclude pattern(s), respect_ignore_files={}",
+            excludes.len(),
+            respect_ignore_files
+        );
+        Ok(Self {
+            excludes,
+            respect_ignore_files,
+        })
+    }
+
+    /// Returns true when the filter lets everything through.
+    pub fn is_empty(&self) -> bool {
+        self.excludes.is_empty() && !self.respect_ignore_files
+    }
+
+    /// Walks everything under `root` that passes the filter, yielding files (with their size)
+    /// and directories (the root itself is not included). Symlinks and special files are skipped.
+    ///
+    /// The walk runs on a blocking thread and feeds a bounded channel, so huge trees are
+    /// streamed rather than collected up front.
+    pub(crate) fn walk(&self, root: &Path) -> Result<ReceiverStream<Result<WalkEntry>>> {
+        let mut overrides = OverrideBuilder::new(root);
+        for pattern in &self.excludes {
+            overrides
+                .add(&format!("!{}", pattern))
+                .map_err(|e| PxpError::Pattern(format!("'{}': {}", pattern, e)))?;
+        }
+        if self.respect_ignore_files {
+            // `.gitignore` never lists the repository database itself, but nobody wants
+            // `.git/` shipped when they asked for gitignore-aware sending.
+            overrides
+                .add("!.git/")
+                .map_err(|e| PxpError::Pattern(e.to_string()))?;
+        }
+        let overrides = overrides
+            .build()
+            .map_err(|e| PxpError::Pattern(e.to_string()))?;
+
+        let mut builder = WalkBuilder::new(root);
+        builder
+            .standard_filters(false)
+            .hidden(false)
+            .follow_links(false)
+            .git_ignore(self.respect_ignore_files)
+            .git_exclude(self.respect_ignore_files)
+            .parents(self.respect_ignore_files)
+            .require_git(false)
+            .overrides(overrides);
+        if self.respect_ignore_files {
+            builder.add_custom_ignore_filename(PORTAL_IGNORE_FILE);
+        }
+
+        let (tx, rx) = mpsc::channel(256);
+        let root = root.to_path_buf();
+        trace!("Starting filtered walk of {:?}", root);
+        tokio::task::spawn_blocking(move || {
+            for result in builder.build() {
+                let item = match result {
+                    Ok(entry) => {
+                        if entry.depth() == 0 {
+                            continue;
+                        }
+                        let Some(file_type) = entry.file_type() else {
+                            continue;
+                        };
+                        if !file_type.is_file() && !file_type.is_dir() {
+                            trace!("Skipping special entry: {:?}", entry.path());
+                            continue;
+                        }
+                        // Stat here, on the blocking thread, so the async side never has to.
+                        let size = if file_type.is_file() {
+                            match entry.metadata() {
+                                Ok(meta) => meta.len(),
+                                Err(e) => {
+                                    let _ = tx.blocking_send(Err(PxpError::WalkDir(e.to_string())));
+                                    break;
+                                }
+                            }
+                        } else {
+                            0
+                        };
+                        Ok(WalkEntry {
+                            is_dir: file_type.is_dir(),
+                            path: entry.into_path(),
+                            size,
+                        })
+                    }
+                    Err(e) => Err(PxpError::WalkDir(e.to_string())),
+                };
+                // The consumer hung up (error or cancellation), stop walking.
+                if tx.blocking_send(item).is_err() {
+                    break;
+                }
+            }
+            trace!("Filtered walk of {:?} finished", root);
+        });
+
+        Ok(ReceiverStream::new(rx))
+    }
+}
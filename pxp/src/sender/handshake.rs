@@ -1,82 +1,59 @@
 use {
-    crate::discovery::listener::{find_receiver_broadcast, find_receiver_multicast},
-    crate::error::{PxpError, Result},
+    crate::discovery::listener::{BeaconQuery, DiscoveredReceiver, discover},
+    crate::error::{PxpError, Result, TimeoutPhase},
+    crate::frame::{MAX_IDENTITY_LEN, read_frame},
+    crate::net::{Timeouts, enable_keepalive, within},
     std::time::Duration,
-    tokio::{io::AsyncReadExt, net::TcpStream, time::timeout},
+    tokio::net::TcpStream,
     tracing::{debug, error, info, trace, warn},
 };
 
-/// Discover a receiver by username, trying multicast first then broadcast.
-/// Returns (ip, node_id, port) on success.
+/// Discover a receiver by username. Every strategy in `DiscoveryMode::ALL` listens at once
+/// and the first beacon that matches wins; the rest are cancelled. Fails with
+/// `DiscoveryTimeout` if nothing matches within `limit`.
 pub async fn discover_receiver(
     target_username: &str,
     fallback_port: u16,
-) -> Result<(String, String, u16)> {
-    info!("Discovery started for user: {}", target_username);
-
-    let discovery_result = match timeout(
-        Duration::from_secs(30),
-        find_receiver_multicast(target_username),
-    )
-    .await
-    {
-        Ok(result) => result?,
-        Err(_) => {
-            warn!("Multicast discovery timed out for user: {}", target_username);
-            warn!("Trying subnet broadcast discovery for user: {}", target_username);
-
-            match timeout(
-                Duration::from_secs(30),
-                find_receiver_broadcast(target_username),
-            )
-            .await
-            {
-                Ok(result) => result?,
-                Err(_) => {
-                    warn!("Broadcast discovery timed out for user: {}", target_username);
-                    return Err(PxpError::DiscoveryTimeout {
-                        message: format!(
-                            "Search timed out. Make sure the receiver is active and on the same network.\n\
-                             Portal: Try direct address mode:\n\
-                             Portal:   portal send --address <receiver-ip> --port {} <file-or-folder>\n\
-                             Tip: The receiver shows its listening address when running `portal receive`.",
-                            fallback_port
-                        )
-                    });
-                }
-            }
-        }
-    };
-
-    let (ip, id, p) = discovery_result;
-    info!("Receiver found at {}:{} (Node ID: {})", ip, p, id);
-    Ok((ip, id, p))
+    limit: Duration,
+) -> Result<DiscoveredReceiver> {
+    let query = BeaconQuery::Receiver(target_username.to_string());
+    let hint = format!(
+        "Search timed out. Make sure the receiver is active and on the same network.\n\
+         Portal: Try direct address mode:\n\
+         Portal:   portal send --address <receiver-ip> --port {} <file-or-folder>\n\
+         Tip: The receiver shows its listening address when running `portal receive`.",
+        fallback_port
+    );
+    discover(&query, limit, hint).await
 }
 
 /// Connect to a receiver at the given address and verify its identity.
 /// If `expected_node_id` is Some, the receiver's claimed ID must match.
+/// The connect and the identity read are each bounded by `timeouts`.
 pub async fn connect_to_receiver(
     target_ip: &str,
     target_port: u16,
     expected_node_id: Option<&str>,
+    timeouts: &Timeouts,
 ) -> Result<TcpStream> {
     let r_addr = format!("{}:{}", target_ip, target_port);
 
-    let mut stream = TcpStream::connect(&r_addr)
-        .await
-        .map_err(|e| PxpError::ConnectionFailed { address: r_addr.clone(), source: e })?;
+    let mut stream = within(TimeoutPhase::Connect, timeouts.connect, async {
+        TcpStream::connect(&r_addr)
+            .await
+            .map_err(|e| PxpError::ConnectionFailed { address: r_addr.clone(), source: e })
+    })
+    .await?;
     info!("TCP connection established with {}", r_addr);
+    enable_keepalive(&stream);
 
     // Read the ID the receiver is claiming
     debug!("Reading receiver identity proof...");
-    let mut id_len_buf = [0u8; 4];
-    stream.read_exact(&mut id_len_buf).await?;
-    let id_len = u32::from_be_bytes(id_len_buf) as usize;
-    trace!("Target claimed ID length: {} bytes", id_len);
-
-    let mut id_buf = vec![0u8; id_len];
-    stream.read_exact(&mut id_buf).await?;
-    let claimed_id = String::from_utf8(id_buf)?;
+    let claimed_id = within(TimeoutPhase::Handshake, timeouts.handshake, async {
+        let id_buf = read_frame(&mut stream, MAX_IDENTITY_LEN, "identity proof").await?;
+        Ok(String::from_utf8(id_buf)?)
+    })
+    .await?;
     trace!("Target claimed ID string: {}", claimed_id);
 
     // Verify it matches what we heard in the beacon
@@ -1,11 +1,11 @@
 use {
-    crate::metadata::{DirectoryMetadata, FileMetadata, GlobalTransferManifest},
+    crate::metadata::{FileMetadata, GlobalTransferManifest, TextMetadata},
     crate::error::{PxpError, Result},
-    async_walkdir::WalkDir,
+    crate::frame::MAX_TEXT_LEN,
+    crate::sender::scan::SendItem,
     std::path::PathBuf,
     tokio::fs::metadata,
-    tokio_stream::StreamExt,
-    tracing::{debug, trace},
+    tracing::debug,
 };
 
 pub async fn create_file_metadata(path: &PathBuf) -> Result<FileMetadata> {
@@ -24,36 +24,21 @@ pub async fn create_file_metadata(path: &PathBuf) -> Result<FileMetadata> {
     })
 }
 
-pub async fn create_directory_metadata(dir: &PathBuf) -> Result<DirectoryMetadata> {
-    debug!("Calculating total size for directory: {:?}", dir);
-    let mut total_size = 0u64;
-    let mut entries = WalkDir::new(dir);
-    while let Some(entry) = entries.next().await {
-        let entry = entry.map_err(|e| PxpError::WalkDir(e.to_string()))?;
-        let entry_path = entry.path();
-        trace!("Scanning path for size calculation: {:?}", entry_path);
+/// The name a text item is saved under when the receiver asks for it to be saved.
+pub const TEXT_ITEM_NAME: &str = "snippet.txt";
 
-        let file_type = entry.file_type().await?;
-
-        if file_type.is_file() {
-            if let Ok(meta) = entry.metadata().await {
-                trace!("Found file: {:?} ({} bytes)", entry_path, meta.len());
-                total_size += meta.len();
-            }
-        }
+/// Describes `text` as a text item, refusing anything over the inline text limit.
+pub fn create_text_metadata(text: &str) -> Result<TextMetadata> {
+    debug!("Generating metadata for {} bytes of text", text.len());
+    if text.len() > MAX_TEXT_LEN {
+        return Err(PxpError::TextTooLarge {
+            len: text.len() as u64,
+            limit: MAX_TEXT_LEN as u64,
+        });
     }
-
-    debug!(
-        "Directory size calculation complete: {} bytes total for {:?}",
-        total_size, dir
-    );
-    Ok(DirectoryMetadata {
-        dirname: dir
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("unknown_dir")
-            .to_string(),
-        total_size,
+    Ok(TextMetadata {
+        name: TEXT_ITEM_NAME.to_string(),
+        byte_len: text.len() as u64,
     })
 }
 
@@ -78,3 +63,16 @@ pub async fn create_global_transfer_manifest(
         compressed,
     })
 }
+
+/// Top-level file count, folder count and total bytes, as the manifest wants them.
+pub fn count_items(items: &[SendItem]) -> (u32, u32, u64) {
+    items
+        .iter()
+        .fold((0u32, 0u32, 0u64), |(f, d, b), item| match item {
+            // Text travels as a single file entry, so the manifest counts it as a file.
+            SendItem::File { .. } | SendItem::Text { .. } => {
+                (f + 1, d, b.saturating_add(item.bytes()))
+            }
+            SendItem::Directory(_) => (f, d + 1, b.saturating_add(item.bytes())),
+        })
+}
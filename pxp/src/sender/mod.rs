@@ -1,17 +1,31 @@
+mod fanout;
+pub mod filter;
 mod handshake;
 pub mod manifest;
+pub mod scan;
 pub(crate) mod send_item;
+pub mod serve;
 mod stream;
 
+pub use filter::PathFilter;
 pub use handshake::{connect_to_receiver, discover_receiver};
-pub use manifest::{create_directory_metadata, create_file_metadata, create_global_transfer_manifest};
-pub use stream::send_stream;
+pub use manifest::{
+    TEXT_ITEM_NAME, count_items, create_file_metadata, create_global_transfer_manifest,
+    create_text_metadata,
+};
+pub use scan::{
+    ScanProgress, ScannedDirectory, ScannedEntry, SendItem, scan_directory, scan_paths,
+};
+pub use serve::{PullSession, ShareHost, answer_pull, new_share_code, open_share};
+pub use stream::{send_stream, send_stream_to_many};
 
 use {
-    crate::metadata::GlobalTransferManifest,
-    crate::error::Result,
-    tokio::{io::AsyncWriteExt, net::TcpStream},
-    tracing::debug,
+    crate::metadata::{GlobalTransferManifest, ManifestResponse},
+    crate::error::{Result, TimeoutPhase},
+    crate::frame::{MAX_RESPONSE_LEN, read_message, write_message},
+    crate::net::{Timeouts, within},
+    tokio::net::TcpStream,
+    tracing::{debug, info, warn},
 };
 
 /// Serialize and send the global transfer manifest over the TCP stream.
@@ -19,10 +33,32 @@ pub async fn send_manifest(
     stream: &mut TcpStream,
     manifest: &GlobalTransferManifest,
 ) -> Result<()> {
-    let encoded = bincode::serialize(manifest)?;
-    let manifest_len = encoded.len() as u32;
-    debug!("Sending serialized global manifest ({} bytes)...", manifest_len);
-    stream.write_all(&manifest_len.to_be_bytes()).await?;
-    stream.write_all(&encoded).await?;
-    Ok(())
+    debug!("Sending serialized global manifest...");
+    write_message(stream, manifest).await
+}
+
+/// Waits for the receiver to accept or reject the manifest. The receiver may be asking its
+/// user (e.g. about low disk space), so this is bounded by `timeouts.idle` rather than the
+/// shorter handshake limit, and running out of it is never retried. A rejection comes back
+/// as a typed error. Receivers whose beacon predates the response never send one; see
+/// `PxpBeacon::answers_manifest`.
+pub async fn read_manifest_response(stream: &mut TcpStream, timeouts: &Timeouts) -> Result<()> {
+    debug!("Waiting for manifest response...");
+    let response: ManifestResponse = within(
+        TimeoutPhase::Acceptance,
+        timeouts.idle,
+        read_message(stream, MAX_RESPONSE_LEN, "manifest response"),
+    )
+    .await?;
+
+    match response {
+        ManifestResponse::Accept => {
+            info!("Receiver accepted the transfer.");
+            Ok(())
+        }
+        ManifestResponse::Reject(reason) => {
+            warn!("Receiver rejected the transfer: {:?}", reason);
+            Err(reason.into())
+        }
+    }
 }
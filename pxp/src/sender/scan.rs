@@ -0,0 +1,196 @@
+use {
+    crate::{
+        error::Result,
+        event::ItemKind,
+        metadata::{DirectoryMetadata, FileMetadata, TextMetadata, TransferItem},
+        sender::{filter::PathFilter, manifest::create_file_metadata},
+    },
+    std::path::{Path, PathBuf},
+    tokio_stream::StreamExt,
+    tracing::{debug, trace},
+};
+
+/// How often (in scanned entries) the scan reports progress back to the caller.
+const SCAN_PROGRESS_INTERVAL: u64 = 256;
+
+/// Receives updates while a directory is being scanned, so large trees don't look frozen.
+pub trait ScanProgress: Send + Sync {
+    /// Called periodically with running totals for the directory being scanned.
+    fn scanned(&self, dirname: &str, files: u64, bytes: u64);
+    /// Called once the directory has been fully scanned.
+    fn finished(&self, dirname: &str, files: u64, bytes: u64);
+}
+
+/// One nested entry found while scanning a directory item. Only relative paths are kept
+/// (the on-disk path is joined to the directory root when streaming) to keep the cached
+/// list small on trees with millions of files.
+#[derive(Debug, Clone)]
+pub struct ScannedEntry {
+    pub tar_path: String,
+    /// The entry's path under the directory root, exactly as it is on disk. `tar_path` is
+    /// a lossy UTF-8 rendering with `/` separators and can't be used to open the file.
+    pub rel_path: PathBuf,
+    pub size: u64,
+    pub is_dir: bool,
+}
+
+/// A directory item scanned exactly once. The same list backs the manifest totals, the
+/// progress totals and the stream itself, so sizes can never disagree between them.
+#[derive(Debug, Clone)]
+pub struct ScannedDirectory {
+    pub root: PathBuf,
+    pub meta: DirectoryMetadata,
+    pub entries: Vec<ScannedEntry>,
+    pub file_count: u64,
+}
+
+impl ScannedDirectory {
+    /// Where a scanned entry lives on disk.
+    pub fn local_path(&self, entry: &ScannedEntry) -> PathBuf {
+        self.root.join(&entry.rel_path)
+    }
+}
+
+/// A top-level item ready to be streamed.
+#[derive(Debug, Clone)]
+pub enum SendItem {
+    File { path: PathBuf, meta: FileMetadata },
+    Directory(ScannedDirectory),
+    Text { meta: TextMetadata, text: String },
+}
+
+impl SendItem {
+    /// The protocol-level description of this item, as sent in its metadata contract.
+    pub fn transfer_item(&self) -> TransferItem {
+        match self {
+            SendItem::File { meta, .. } => TransferItem::File(meta.clone()),
+            SendItem::Directory(dir) => TransferItem::Directory(dir.meta.clone()),
+            SendItem::Text { meta, .. } => TransferItem::Text(meta.clone()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            SendItem::File { meta, .. } => &meta.filename,
+            SendItem::Directory(dir) => &dir.meta.dirname,
+            SendItem::Text { meta, .. } => &meta.name,
+        }
+    }
+
+    pub fn bytes(&self) -> u64 {
+        match self {
+            SendItem::File { meta, .. } => meta.file_size,
+            SendItem::Directory(dir) => dir.meta.total_size,
+            SendItem::Text { meta, .. } => meta.byte_len,
+        }
+    }
+
+    pub fn is_text(&self) -> bool {
+        matches!(self, SendItem::Text { .. })
+    }
+
+    pub fn is_dir(&self) -> bool {
+        matches!(self, SendItem::Directory(_))
+    }
+
+    pub fn kind(&self) -> ItemKind {
+        match self {
+            SendItem::File { .. } => ItemKind::File,
+            SendItem::Directory(_) => ItemKind::Directory,
+            SendItem::Text { .. } => ItemKind::Text,
+        }
+    }
+}
+
+/// Walks `dir` once, applying `filter`, and caches every entry's relative path, size and
+/// type. Sizes are taken here and nowhere else.
+pub async fn scan_directory(
+    dir: &Path,
+    filter: &PathFilter,
+    progress: Option<&dyn ScanProgress>,
+) -> Result<ScannedDirectory> {
+    let dirname = dir
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("unknown_dir")
+        .to_string();
+    debug!("Scanning directory {:?} as '{}'", dir, dirname);
+
+    let mut entries = Vec::new();
+    let mut total_size = 0u64;
+    let mut file_count = 0u64;
+    let mut walk = filter.walk(dir)?;
+    while let Some(entry) = walk.next().await {
+        let entry = entry?;
+        let rel_path = entry.path.strip_prefix(dir)?;
+        let rel_path_str = rel_path.to_string_lossy().replace('\\', "/");
+        let tar_path = format!("{}/{}", dirname, rel_path_str);
+        trace!(
+            "Scanned entry: {:?} -> tar_path: {} ({} bytes)",
+            entry.path, tar_path, entry.size
+        );
+
+        if !entry.is_dir {
+            file_count += 1;
+            total_size = total_size.saturating_add(entry.size);
+        }
+        entries.push(ScannedEntry {
+            tar_path,
+            rel_path: rel_path.to_path_buf(),
+            size: entry.size,
+            is_dir: entry.is_dir,
+        });
+
+        if let Some(prog) = progress
+            && (entries.len() as u64).is_multiple_of(SCAN_PROGRESS_INTERVAL)
+        {
+            prog.scanned(&dirname, file_count, total_size);
+        }
+    }
+    if let Some(prog) = progress {
+        prog.finished(&dirname, file_count, total_size);
+    }
+
+    debug!(
+        "Directory scan complete: {} entries, {} files, {} bytes total for {:?}",
+        entries.len(),
+        file_count,
+        total_size,
+        dir
+    );
+    Ok(ScannedDirectory {
+        root: dir.to_path_buf(),
+        meta: DirectoryMetadata {
+            dirname,
+            total_size,
+        },
+        entries,
+        file_count,
+    })
+}
+
+/// Turns every input path into the item that will be streamed: folders are scanned through
+/// `filter`, files only measured.
+pub async fn scan_paths(
+    paths: &[PathBuf],
+    filter: &PathFilter,
+    progress: Option<&dyn ScanProgress>,
+) -> Result<Vec<SendItem>> {
+    let mut items = Vec::with_capacity(paths.len());
+    for path in paths {
+        trace!("Preparing item: {:?}", path);
+        if path.is_dir() {
+            items.push(SendItem::Directory(
+                scan_directory(path, filter, progress).await?,
+            ));
+        } else {
+            let meta = create_file_metadata(path).await?;
+            items.push(SendItem::File {
+                path: path.clone(),
+                meta,
+            });
+        }
+    }
+    debug!("Collected {} top-level items", items.len());
+    Ok(items)
+}
@@ -1,29 +1,32 @@
 use {
     crate::metadata::{FileMetadata, PxpMeta, TransferItem},
-    crate::sender::manifest::create_file_metadata,
-    crate::ItemProgress,
+    crate::sender::scan::SendItem,
+    crate::event::{EventSink, Metered, TransferEvent},
     crate::error::{PxpError, Result},
-    async_walkdir::WalkDir,
     bincode::serialize,
-    std::path::PathBuf,
-    tokio::{fs::File, io::AsyncWrite},
-    tokio_stream::StreamExt,
+    std::{path::Path, time::UNIX_EPOCH},
+    tokio::{
+        fs::File,
+        io::{AsyncReadExt, AsyncWrite},
+    },
     tokio_tar::{Builder, EntryType, Header},
     tracing::{debug, info, trace, warn},
 };
 
-/// Appends a file or directory to the provided tar builder
+/// Appends a file, directory or text item to the provided tar builder
 pub async fn send_item<W>(
     builder: &mut Builder<W>,
-    path: PathBuf,
-    item: TransferItem,
-    item_progress: Option<&dyn ItemProgress>,
+    item: SendItem,
+    events: Option<&dyn EventSink>,
 ) -> Result<()>
 where
     W: AsyncWrite + Unpin + Send,
 {
     match item {
-        TransferItem::File(file_meta) => {
+        SendItem::File {
+            path,
+            meta: file_meta,
+        } => {
             trace!(
                 "Streaming file payload '{}' ({} bytes)",
                 file_meta.filename, file_meta.file_size
@@ -33,25 +36,15 @@ where
             trace!("Serialized file metadata size: {} bytes", meta_bytes.len());
             append_raw_meta(builder, meta_bytes).await?;
 
-            trace!("Opening file for reading: {:?}", path);
-            let file = File::open(&path).await?;
-            let mut header = Header::new_gnu();
-            header.set_path(&file_meta.filename)?;
-            header.set_size(file_meta.file_size);
-            header.set_mode(0o644);
-            header.set_cksum();
-
             trace!("Appending file '{}' to tar archive", file_meta.filename);
-            // We use the ItemProgress wrapper to wrap the file reader before handing it off to the tar builder.
-            // As the tar builder pulls bytes from the stream, our wrapper intercepts those reads 
-            // to dynamically update the UI progress bar. This way we don't have to manually chunk the file ourselves.
-            if let Some(prog) = item_progress {
-                let mut reader = prog.wrap_read(Box::new(file));
-                builder.append(&header, &mut *reader).await?;
-            } else {
-                let mut f = file;
-                builder.append(&header, &mut f).await?;
-            }
+            append_file(
+                builder,
+                &path,
+                &file_meta.filename,
+                file_meta.file_size,
+                events,
+            )
+            .await?;
 
             info!(
                 "File '{}' transfer initiated and appended to stream.",
@@ -59,10 +52,13 @@ where
             );
         }
 
-        TransferItem::Directory(dir_meta) => {
+        SendItem::Directory(dir) => {
+            let dir_meta = &dir.meta;
             trace!(
-                "Streaming directory payload '{}' ({} bytes)",
-                dir_meta.dirname, dir_meta.total_size
+                "Streaming directory payload '{}' ({} bytes, {} cached entries)",
+                dir_meta.dirname,
+                dir_meta.total_size,
+                dir.entries.len()
             );
             if dir_meta.total_size == 0 {
                 warn!(
@@ -84,88 +80,153 @@ where
                 "Appending directory node '{}' to tar archive",
                 dir_meta.dirname
             );
-            let mut dir_header = Header::new_gnu();
-            dir_header.set_path(&dir_meta.dirname)?;
-            dir_header.set_entry_type(EntryType::Directory);
-            dir_header.set_mode(0o755);
-            dir_header.set_size(0);
-            dir_header.set_cksum();
-            builder.append(&dir_header, &[][..]).await?;
-
-            debug!("Starting WalkDir for directory: {:?}", path);
-            // We need to flatten the recursive directory structure into a linear series of tar entries.
-            // WalkDir iterates through everything under the path, and for each entry, we strip the 
-            // base path to figure out its relative tar path. This makes sure nested files end up 
-            // in the correct folder structure on the receiver's end.
-            let mut entries = WalkDir::new(&path);
-            while let Some(entry) = entries.next().await {
-                let entry = entry.map_err(|e| PxpError::WalkDir(e.to_string()))?;
-                let file_type = entry.file_type().await?;
-                let local_path = entry.path();
-                let rel_path = local_path.strip_prefix(&path)?;
-                let rel_path_str = rel_path.to_string_lossy().replace('\\', "/");
-                let tar_path = format!("{}/{}", dir_meta.dirname, rel_path_str);
-
+            append_dir(builder, &dir_meta.dirname).await?;
+
+            // The directory was already flattened into a linear list of tar entries by
+            // `scan_directory`, with relative tar paths and sizes captured once. We stream
+            // straight from that list instead of walking (and re-stat'ing) the tree again,
+            // so nested files end up in the right folder structure with the exact sizes
+            // the manifest advertised.
+            for entry in &dir.entries {
+                let local_path = dir.local_path(entry);
                 trace!(
                     "Processing entry: {:?} -> tar_path: {}",
-                    local_path, tar_path
+                    local_path, entry.tar_path
                 );
 
-                if file_type.is_file() {
-                    debug!("Processing nested file: {}", tar_path);
-                    let mut file_meta = create_file_metadata(&local_path).await?;
-                    file_meta.filename = tar_path.clone();
+                if !entry.is_dir {
+                    debug!("Processing nested file: {}", entry.tar_path);
+                    let file_meta = FileMetadata {
+                        filename: entry.tar_path.clone(),
+                        file_size: entry.size,
+                    };
 
-                    trace!("Serializing nested file metadata for: {}", tar_path);
-                    let meta_bytes = serialize(&PxpMeta::NestedFile(file_meta.clone()))?;
+                    trace!("Serializing nested file metadata for: {}", entry.tar_path);
+                    let meta_bytes = serialize(&PxpMeta::NestedFile(file_meta))?;
                     trace!("Nested file metadata size: {} bytes", meta_bytes.len());
                     append_raw_meta(builder, meta_bytes).await?;
 
-                    trace!("Opening nested file: {:?}", local_path);
-                    let file = File::open(&local_path).await?;
-                    let mut header = Header::new_gnu();
-                    header.set_path(&tar_path)?;
-                    header.set_size(file.metadata().await?.len());
-                    header.set_mode(0o644);
-                    header.set_cksum();
-
-                    trace!("Appending nested file '{}' to tar archive", tar_path);
-                    if let Some(prog) = item_progress {
-                        let mut reader = prog.wrap_read(Box::new(file));
-                        builder.append(&header, &mut *reader).await?;
-                    } else {
-                        let mut f = file;
-                        builder.append(&header, &mut f).await?;
-                    }
-
-                    info!("Directory file sent successfully: {}", &tar_path);
-                } else if file_type.is_dir() {
-                    debug!("Processing nested directory: {}", tar_path);
+                    trace!("Appending nested file '{}' to tar archive", entry.tar_path);
+                    append_file(
+                        builder,
+                        &local_path,
+                        &entry.tar_path,
+                        entry.size,
+                        events,
+                    )
+                    .await?;
+
+                    info!("Directory file sent successfully: {}", &entry.tar_path);
+                } else {
+                    debug!("Processing nested directory: {}", entry.tar_path);
                     let sub_dir_meta = FileMetadata {
-                        filename: tar_path.clone(),
+                        filename: entry.tar_path.clone(),
                         file_size: 0,
                     };
 
-                    trace!("Serializing nested directory metadata for: {}", tar_path);
+                    trace!("Serializing nested directory metadata for: {}", entry.tar_path);
                     let meta_bytes = serialize(&PxpMeta::NestedFile(sub_dir_meta))?;
                     trace!("Nested directory metadata size: {} bytes", meta_bytes.len());
                     append_raw_meta(builder, meta_bytes).await?;
 
-                    trace!("Appending subdirectory entry to tar: {}", tar_path);
-                    let mut header = Header::new_gnu();
-                    header.set_path(&tar_path)?;
-                    header.set_entry_type(EntryType::Directory);
-                    header.set_mode(0o755);
-                    header.set_size(0);
-                    header.set_cksum();
-                    builder.append(&header, &[][..]).await?;
+                    trace!("Appending subdirectory entry to tar: {}", entry.tar_path);
+                    append_dir(builder, &entry.tar_path).await?;
                 }
             }
 
             info!("Directory '{}' transfer complete.", dir_meta.dirname);
         }
+
+        SendItem::Text { meta, text } => {
+            trace!("Streaming text payload ({} bytes)", meta.byte_len);
+            let meta_bytes = serialize(&PxpMeta::Item(TransferItem::Text(meta.clone())))?;
+            append_raw_meta(builder, meta_bytes).await?;
+
+            // Text is small and already in memory, so it goes in as one entry and is
+            // reported as one step of progress.
+            let mut header = Header::new_gnu();
+            header.set_path(&meta.name)?;
+            header.set_size(meta.byte_len);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, text.as_bytes()).await?;
+            if let Some(sink) = events {
+                sink.emit(TransferEvent::Progress {
+                    bytes: meta.byte_len,
+                });
+            }
+            info!("Text item appended to stream.");
+        }
+    }
+
+    Ok(())
+}
+
+/// Appends one file's bytes under `tar_path`, using the size captured at scan time for the
+/// header. Only exactly `size` bytes are read, so a file that grew since the scan can't
+/// corrupt the archive; one that shrank is reported instead of producing a short entry.
+async fn append_file<W: AsyncWrite + Unpin + Send>(
+    builder: &mut Builder<W>,
+    path: &Path,
+    tar_path: &str,
+    size: u64,
+    events: Option<&dyn EventSink>,
+) -> Result<()> {
+    trace!("Opening file for reading: {:?}", path);
+    let file = File::open(path).await?;
+    let file_meta = file.metadata().await?;
+    let current_size = file_meta.len();
+    if current_size < size {
+        warn!(
+            "File {:?} shrank from {} to {} bytes since it was scanned",
+            path, size, current_size
+        );
+        return Err(PxpError::WalkDir(format!(
+            "'{}' changed while it was being sent",
+            path.display()
+        )));
     }
 
+    let mut header = Header::new_gnu();
+    header.set_path(tar_path)?;
+    header.set_size(size);
+    header.set_mode(0o644);
+    // The receiver keeps this as the file's modification time, so conflicts can be settled
+    // by which copy is newer.
+    if let Some(since_epoch) = file_meta
+        .modified()
+        .ok()
+        .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+    {
+        header.set_mtime(since_epoch.as_secs());
+    }
+    header.set_cksum();
+
+    // We meter the file reader before handing it off to the tar builder. As the builder
+    // pulls bytes, each read is reported as a progress event, so we don't have to chunk
+    // the file ourselves.
+    if let Some(sink) = events {
+        let mut reader = Metered::new(file.take(size), sink);
+        builder.append(&header, &mut reader).await?;
+    } else {
+        let mut reader = file.take(size);
+        builder.append(&header, &mut reader).await?;
+    }
+    Ok(())
+}
+
+/// Appends an empty directory node under `tar_path`.
+async fn append_dir<W: AsyncWrite + Unpin + Send>(
+    builder: &mut Builder<W>,
+    tar_path: &str,
+) -> Result<()> {
+    let mut header = Header::new_gnu();
+    header.set_path(tar_path)?;
+    header.set_entry_type(EntryType::Directory);
+    header.set_mode(0o755);
+    header.set_size(0);
+    header.set_cksum();
+    builder.append(&header, &[][..]).await?;
     Ok(())
 }
 
@@ -0,0 +1,191 @@
+use {
+    crate::{
+        discovery::{BeaconHandle, DeviceInfo, ReceiverStatus, ShareInfo, spawn_share_beacon},
+        metadata::{PullRequest, PullResponse, RejectReason, ShareListing},
+        receiver::access::SenderFilter,
+    },
+    crate::error::{PxpError, Result, TimeoutPhase},
+    crate::frame::{MAX_PULL_REQUEST_LEN, read_message, write_frame, write_message},
+    crate::net::{Timeouts, enable_keepalive, within},
+    tokio::{
+        io::AsyncWriteExt,
+        net::{TcpListener, TcpStream},
+    },
+    tracing::{debug, error, info, trace, warn},
+    uuid::Uuid,
+};
+
+/// Letters and digits used for share codes, minus the ones that are easy to misread.
+const CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+const CODE_LEN: usize = 6;
+
+/// A fresh random code a getter can type instead of the share name, e.g. `k7p2xq`. Codes
+/// are sent in the clear in every beacon; they save typing, they don't keep anyone out.
+pub fn new_share_code() -> String {
+    Uuid::new_v4()
+        .as_bytes()
+        .iter()
+        .take(CODE_LEN)
+        .map(|b| CODE_ALPHABET[*b as usize % CODE_ALPHABET.len()] as char)
+        .collect()
+}
+
+/// A share that is listening for getters and advertising itself. Dropping it stops both.
+pub struct ShareHost {
+    listener: TcpListener,
+    beacon: BeaconHandle,
+    node_id: String,
+    listing: ShareListing,
+}
+
+/// One getter that has picked what it wants from the listing.
+pub struct PullSession {
+    pub socket: TcpStream,
+    pub peer_addr: String,
+    pub request: PullRequest,
+}
+
+/// Binds `port` and starts advertising `listing` under `share`'s name and code.
+pub async fn open_share(
+    port: u16,
+    username: String,
+    device: DeviceInfo,
+    share: ShareInfo,
+    listing: ShareListing,
+) -> Result<ShareHost> {
+    let node_id = Uuid::new_v4().to_string();
+    debug!("Generated share Node ID: {}", node_id);
+
+    let bind_addr = format!("0.0.0.0:{}", port);
+    let listener = TcpListener::bind(&bind_addr)
+        .await
+        .map_err(|e| PxpError::BindFailed { port, source: e })?;
+    info!("Share '{}' listening on {}", share.name, bind_addr);
+
+    let beacon = spawn_share_beacon(username, node_id.clone(), port, device, share);
+    Ok(ShareHost {
+        listener,
+        beacon,
+        node_id,
+        listing,
+    })
+}
+
+impl ShareHost {
+    pub fn listing(&self) -> &ShareListing {
+        &self.listing
+    }
+
+    /// Waits for the next getter, sends it the listing and reads which items it wants.
+    /// The beacon advertises the host as busy from the moment a getter connects until the
+    /// next call, so other getters wait their turn.
+    ///
+    /// Getters that `filter` doesn't admit, or that ask for items not in the listing, are
+    /// turned away and the wait carries on. Waiting is unbounded; once a getter connects,
+    /// it must send its request within `timeouts.idle`, since its user may be choosing.
+    pub async fn next_request(
+        &mut self,
+        filter: &SenderFilter,
+        timeouts: &Timeouts,
+    ) -> Result<PullSession> {
+        self.beacon.set_status(ReceiverStatus::Idle);
+        loop {
+            let (mut socket, addr) = tokio::select! {
+                err = self.beacon.stopped() => {
+                    error!("Share beacon exited unexpectedly");
+                    return Err(err);
+                }
+                result = self.listener.accept() => result?,
+            };
+            info!("Getter connected from {}", addr);
+
+            if filter.refuses_ip(addr.ip()) {
+                warn!("Dropped connection from refused getter {}", addr);
+                continue;
+            }
+            self.beacon.set_status(ReceiverStatus::Busy);
+            enable_keepalive(&socket);
+
+            let request = match self.read_request(&mut socket, timeouts).await {
+                Ok(request) => request,
+                Err(e) => {
+                    warn!("Getter {} went away before choosing: {}", addr, e);
+                    self.beacon.set_status(ReceiverStatus::Idle);
+                    continue;
+                }
+            };
+
+            if let Some(reason) = self.refusal(&request, addr.ip(), filter) {
+                warn!("Refused pull from {}: {:?}", addr, reason);
+                if let Err(e) = answer_pull(&mut socket, &PullResponse::Refused(reason)).await {
+                    debug!("Could not send refusal to {}: {}", addr, e);
+                }
+                self.beacon.set_status(ReceiverStatus::Idle);
+                continue;
+            }
+
+            return Ok(PullSession {
+                socket,
+                peer_addr: addr.ip().to_string(),
+                request,
+            });
+        }
+    }
+
+    /// Proves who we are, sends the listing, and reads the getter's choice.
+    async fn read_request(
+        &self,
+        socket: &mut TcpStream,
+        timeouts: &Timeouts,
+    ) -> Result<PullRequest> {
+        within(TimeoutPhase::Handshake, timeouts.handshake, async {
+            write_frame(socket, self.node_id.as_bytes()).await?;
+            trace!("Share identity sent to getter.");
+            write_message(socket, &self.listing).await?;
+            socket.flush().await?;
+            Ok(())
+        })
+        .await?;
+        debug!("Listing of {} item(s) sent", self.listing.items.len());
+
+        let request: PullRequest = within(
+            TimeoutPhase::Handshake,
+            timeouts.idle,
+            read_message(socket, MAX_PULL_REQUEST_LEN, "pull request"),
+        )
+        .await?;
+        trace!("Pull request: {:?}", request);
+        Ok(request)
+    }
+
+    fn refusal(
+        &self,
+        request: &PullRequest,
+        ip: std::net::IpAddr,
+        filter: &SenderFilter,
+    ) -> Option<RejectReason> {
+        if !filter.permits(ip, request.username.as_deref()) {
+            return Some(RejectReason::NotAllowed);
+        }
+        if request.items.is_empty() {
+            return Some(RejectReason::Declined("no items were requested".into()));
+        }
+        let offered = self.listing.items.len();
+        if let Some(bad) = request.items.iter().find(|&&i| i as usize >= offered) {
+            return Some(RejectReason::Declined(format!(
+                "item {} is not in this share ({} offered)",
+                bad, offered
+            )));
+        }
+        None
+    }
+}
+
+/// Sends the manifest for the requested items, or a refusal. After a manifest the host
+/// reads the getter's answer with `read_manifest_response`, then streams as a sender would.
+pub async fn answer_pull(socket: &mut TcpStream, response: &PullResponse) -> Result<()> {
+    debug!("Sending pull response: {:?}", response);
+    write_message(socket, response).await?;
+    socket.flush().await?;
+    Ok(())
+}
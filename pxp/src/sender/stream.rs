@@ -1,12 +1,12 @@
 use {
     crate::{
-        metadata::TransferItem,
-        sender::send_item::send_item,
-        TransferProgress,
+        net::{IdleTimeout, Timeouts},
+        retry::is_connection_io_error,
+        sender::{fanout::FanOut, scan::SendItem, send_item::send_item},
+        event::{EventSink, ItemKind, TransferEvent, TransferPhase, TransferWarning, emit},
     },
     crate::error::{PxpError, Result},
     async_compression::tokio::write::GzipEncoder,
-    std::path::PathBuf,
     tokio::{
         io::{AsyncWrite, AsyncWriteExt},
         net::TcpStream,
@@ -17,76 +17,165 @@ use {
 
 async fn stream_items<W: AsyncWrite + Unpin + Send>(
     builder: &mut Builder<W>,
-    items_to_send: Vec<(PathBuf, TransferItem)>,
-    progress: Option<&dyn TransferProgress>,
+    items_to_send: Vec<SendItem>,
+    events: Option<&dyn EventSink>,
 ) -> Result<()> {
     let total = items_to_send.len();
-    for (index, (path, item)) in items_to_send.into_iter().enumerate() {
-        debug!("Processing item {}: {:?}", index + 1, path);
-
-        if let Some(prog) = progress {
-            let (name, bytes, is_dir) = match &item {
-                TransferItem::File(fm) => (fm.filename.clone(), fm.file_size, false),
-                TransferItem::Directory(dm) => (dm.dirname.clone(), dm.total_size, true),
-            };
-
-            prog.set_current_item(index + 1, total);
-
-            if is_dir && bytes == 0 {
-                prog.println(&format!(
-                    "Portal: Note: Directory '{}' is empty. Sending structure only.",
-                    name
-                ));
-            }
-
-            let item_prog = prog.create_item_progress(&name, bytes);
-            send_item(builder, path, item, Some(&*item_prog))
-                .await
-                .map_err(|e| PxpError::Archive(e.to_string()))?;
-            item_prog.finish_and_clear();
-
-            let kind = if is_dir { "Directory" } else { "File" };
-            prog.println(&format!("Portal: {} '{}' sent successfully!", kind, name));
-        } else {
-            send_item(builder, path, item, None)
-                .await
-                .map_err(|e| PxpError::Archive(e.to_string()))?;
+    for (index, item) in items_to_send.into_iter().enumerate() {
+        debug!("Processing item {}: {}", index + 1, item.name());
+
+        let (name, bytes, kind) = (item.name().to_string(), item.bytes(), item.kind());
+        emit(
+            events,
+            TransferEvent::ItemStarted {
+                index: index + 1,
+                total,
+                name: name.clone(),
+                kind,
+                bytes,
+            },
+        );
+        if kind == ItemKind::Directory && bytes == 0 {
+            emit(
+                events,
+                TransferEvent::Warning(TransferWarning::EmptyDirectory { name: name.clone() }),
+            );
         }
+
+        send_item(builder, item, events)
+            .await
+            .map_err(archive_error)?;
+
+        emit(
+            events,
+            TransferEvent::ItemFinished {
+                name,
+                kind,
+                verified: false,
+            },
+        );
     }
     Ok(())
 }
 
+/// Keeps a stall or a dropped connection distinguishable from a genuine packaging failure,
+/// so callers can tell which errors are worth retrying.
+fn archive_error(err: PxpError) -> PxpError {
+    match err.stalled() {
+        err @ PxpError::Timeout { .. } => err,
+        PxpError::Io(io) if is_connection_io_error(io.kind()) => PxpError::Io(io),
+        other => PxpError::Archive(other.to_string()),
+    }
+}
+
+/// Streams every item as one tar archive (optionally gzip-compressed). The socket is wrapped
+/// in an idle timeout, so a receiver that stops reading fails the send with a typed
+/// `PxpError::Timeout` instead of blocking forever.
 pub async fn send_stream(
     stream: TcpStream,
-    items_to_send: Vec<(PathBuf, TransferItem)>,
+    items_to_send: Vec<SendItem>,
     no_compress: bool,
-    progress: Option<&dyn TransferProgress>,
+    timeouts: &Timeouts,
+    events: Option<&dyn EventSink>,
 ) -> Result<()> {
+    let stream = IdleTimeout::new(stream, timeouts.idle);
+    write_stream(stream, items_to_send, no_compress, events)
+        .await
+        .map(|_| ())
+        .map_err(PxpError::stalled)
+}
+
+/// Streams the same items to several receivers at once. The tar stream is built, and every
+/// file read, only once; each byte is copied to all connections. Returns one outcome per
+/// stream, in order: a receiver that drops out or stalls fails on its own while the others
+/// carry on.
+pub async fn send_stream_to_many(
+    streams: Vec<TcpStream>,
+    items_to_send: Vec<SendItem>,
+    no_compress: bool,
+    timeouts: &Timeouts,
+    events: Option<&dyn EventSink>,
+) -> Vec<Result<()>> {
+    let count = streams.len();
+    let sinks = streams
+        .into_iter()
+        .map(|stream| IdleTimeout::new(stream, timeouts.idle))
+        .collect();
+    let (fanout, sink_errors) = FanOut::new(sinks);
+    info!("Fanning the transfer out to {} receivers", count);
+    let outcome = write_stream(fanout, items_to_send, no_compress, events).await;
+
+    let mut sink_errors = sink_errors
+        .lock()
+        .map(|mut errors| std::mem::take(&mut *errors))
+        .unwrap_or_default();
+    sink_errors.resize_with(count, || None);
+    // A failure that wasn't any one receiver's fault (e.g. a file changed while it was
+    // read) applies to every receiver that was still live.
+    let shared_error = outcome.err().map(|e| e.to_string());
+    sink_errors
+        .into_iter()
+        .map(|error| match (error, &shared_error) {
+            (Some(io), _) => Err(PxpError::Io(io).stalled()),
+            (None, Some(message)) => Err(PxpError::Archive(message.clone())),
+            (None, None) => Ok(()),
+        })
+        .collect()
+}
+
+/// Writes the tar stream into `stream` and hands the writer back once it is flushed,
+/// reporting when streaming starts and when it is complete.
+async fn write_stream<W>(
+    stream: W,
+    items_to_send: Vec<SendItem>,
+    no_compress: bool,
+    events: Option<&dyn EventSink>,
+) -> Result<W>
+where
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    emit(events, TransferEvent::Phase(TransferPhase::Streaming));
+    let stream = write_archive(stream, items_to_send, no_compress, events).await?;
+    emit(events, TransferEvent::Phase(TransferPhase::Complete));
+    Ok(stream)
+}
+
+/// Builds the tar archive (gzip-compressed unless `no_compress`) on top of `stream`.
+async fn write_archive<W>(
+    stream: W,
+    items_to_send: Vec<SendItem>,
+    no_compress: bool,
+    events: Option<&dyn EventSink>,
+) -> Result<W>
+where
+    W: AsyncWrite + Unpin + Send + 'static,
+{
     if no_compress {
         debug!("Initializing Tar builder (no compression)...");
         let mut builder = Builder::new(stream);
         info!("Starting TAR stream to network (no compression)...");
-        stream_items(&mut builder, items_to_send, progress).await?;
+        stream_items(&mut builder, items_to_send, events).await?;
 
         debug!("Finalizing Tar archive structure...");
         builder.finish().await?;
 
-        let mut stream: TcpStream = builder.into_inner().await?;
+        let mut stream: W = builder.into_inner().await?;
         trace!("Flushing underlying TCP stream...");
         stream.flush().await?;
         debug!("TCP stream flush complete.");
+        Ok(stream)
     } else {
         debug!("Initializing Gzip encoder and Tar builder...");
         let compressor = GzipEncoder::new(stream);
         let mut builder = Builder::new(compressor);
 
         info!("Starting TAR stream to network...");
-        stream_items(&mut builder, items_to_send, progress).await?;
+        stream_items(&mut builder, items_to_send, events).await?;
 
         debug!("Finalizing Tar archive structure...");
         builder.finish().await?;
 
-        let mut compressor: GzipEncoder<TcpStream> = builder.into_inner().await?;
+        let mut compressor: GzipEncoder<W> = builder.into_inner().await?;
 
         debug!("Shutting down Gzip compressor...");
         compressor
@@ -98,7 +187,6 @@ pub async fn send_stream(
         trace!("Flushing underlying TCP stream...");
         stream.flush().await?;
         debug!("TCP stream flush complete.");
+        Ok(stream)
     }
-
-    Ok(())
 }
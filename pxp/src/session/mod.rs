@@ -0,0 +1,28 @@
+//! Whole sends and receives behind one builder each, for applications that don't need to
+//! drive discovery, the handshake, streaming and reconcile step by step themselves.
+//!
+//! ```no_run
+//! # async fn example() -> pxp::Result<()> {
+//! let outcome = pxp::SendSession::builder()
+//!     .to_user("alice")
+//!     .paths(["notes.txt", "photos"])
+//!     .compression(true)
+//!     .progress(|event: pxp::TransferEvent| println!("{:?}", event))
+//!     .run()
+//!     .await?;
+//! assert!(outcome.succeeded());
+//! # Ok(())
+//! # }
+//! ```
+
+mod receive;
+mod send;
+
+pub use receive::{
+    IncomingTransfer, ReceiveOutcome, ReceiveSession, ReceiveSessionBuilder, ReceivedTransfer,
+    Upload,
+};
+pub use send::{
+    AttemptOutcome, PeerOutcome, SendOutcome, SendSession, SendSessionBuilder, SendTarget,
+    SentItem,
+};
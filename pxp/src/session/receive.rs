@@ -0,0 +1,594 @@
+use {
+    crate::{
+        ConflictResolver, ItemRouter, Timeouts,
+        discovery::{BeaconHandle, DeviceInfo},
+        error::{PxpError, Result},
+        event::{EventSink, ItemKind, Metered, TransferEvent, TransferPhase, emit},
+        metadata::{
+            GlobalTransferManifest, ManifestResponse, ReceiveSummary, ReceivedItem, RejectReason,
+        },
+        receiver::{
+            PlacedItem, ReceivePolicy, SenderFilter, StagedItem, StagedTransfer,
+            check_free_space, discard,
+            handshake::{accept_and_read_manifest, respond_to_manifest},
+            policy::PolicyGuard,
+            receive_item::report_rename,
+            reconcile,
+            sanitize::PathSanitizer,
+            stream::{new_staging_dir, receive_stream},
+        },
+    },
+    std::{
+        collections::HashSet,
+        path::{Path, PathBuf},
+        sync::Arc,
+        time::{Duration, Instant, SystemTime},
+    },
+    tokio::{
+        fs::{File, create_dir_all},
+        io::{AsyncRead, AsyncReadExt, AsyncWriteExt, copy},
+        net::TcpStream,
+    },
+    tracing::{debug, info, warn},
+};
+
+/// Port a receiver listens on unless the builder sets another.
+const DEFAULT_PORT: u16 = 7878;
+
+/// Collects what a receiver needs. Start one with `ReceiveSession::builder()`.
+pub struct ReceiveSessionBuilder {
+    username: Option<String>,
+    port: u16,
+    device: DeviceInfo,
+    filter: SenderFilter,
+    target_dir: Option<PathBuf>,
+    policy: ReceivePolicy,
+    timeouts: Timeouts,
+    resolver: Option<Arc<dyn ConflictResolver>>,
+    events: Option<Arc<dyn EventSink>>,
+}
+
+impl Default for ReceiveSessionBuilder {
+    fn default() -> Self {
+        Self {
+            username: None,
+            port: DEFAULT_PORT,
+            device: DeviceInfo::default(),
+            filter: SenderFilter::default(),
+            target_dir: None,
+            policy: ReceivePolicy::default(),
+            timeouts: Timeouts::default(),
+            resolver: None,
+            events: None,
+        }
+    }
+}
+
+impl ReceiveSessionBuilder {
+    /// The username senders look for. Needed to `listen`.
+    pub fn username(mut self, username: impl Into<String>) -> Self {
+        self.username = Some(username.into());
+        self
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Details advertised in the beacon.
+    pub fn device(mut self, device: DeviceInfo) -> Self {
+        self.device = device;
+        self
+    }
+
+    /// Which senders may connect; others are turned away while the receiver keeps waiting.
+    pub fn sender_filter(mut self, filter: SenderFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Where received items are saved. Required.
+    pub fn target_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.target_dir = Some(dir.into());
+        self
+    }
+
+    pub fn policy(mut self, policy: ReceivePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Asked what to do when an incoming item already exists. Without one, existing items
+    /// are overwritten.
+    pub fn resolver(mut self, resolver: impl ConflictResolver + 'static) -> Self {
+        self.resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    pub fn progress(mut self, events: impl EventSink + 'static) -> Self {
+        self.events = Some(Arc::new(events));
+        self
+    }
+
+    pub fn build(self) -> Result<ReceiveSession> {
+        let target_dir = self
+            .target_dir
+            .ok_or_else(|| PxpError::InvalidSession("no target directory".into()))?;
+        Ok(ReceiveSession {
+            username: self.username,
+            port: self.port,
+            device: self.device,
+            filter: self.filter,
+            target_dir,
+            policy: self.policy,
+            timeouts: self.timeouts,
+            resolver: self.resolver,
+            events: self.events,
+        })
+    }
+}
+
+/// A configured receiver. Each transfer goes through three steps, so the application can
+/// step in between them: an `IncomingTransfer` is accepted or rejected, the accepted
+/// stream lands in staging as a `ReceivedTransfer`, and reconciling that places the items
+/// and gives the `ReceiveOutcome`.
+#[derive(Clone)]
+pub struct ReceiveSession {
+    username: Option<String>,
+    port: u16,
+    device: DeviceInfo,
+    filter: SenderFilter,
+    target_dir: PathBuf,
+    policy: ReceivePolicy,
+    timeouts: Timeouts,
+    resolver: Option<Arc<dyn ConflictResolver>>,
+    events: Option<Arc<dyn EventSink>>,
+}
+
+impl ReceiveSession {
+    pub fn builder() -> ReceiveSessionBuilder {
+        ReceiveSessionBuilder::default()
+    }
+
+    pub fn target_dir(&self) -> &Path {
+        &self.target_dir
+    }
+
+    /// Advertises this receiver and waits, without a time limit, for a sender the filter
+    /// admits. The beacon shows the receiver as busy until the transfer is over.
+    pub async fn listen(&self) -> Result<IncomingTransfer> {
+        let username = self
+            .username
+            .clone()
+            .ok_or_else(|| PxpError::InvalidSession("no username to advertise".into()))?;
+        let handshake = accept_and_read_manifest(
+            self.port,
+            username,
+            self.device.clone(),
+            &self.filter,
+            &self.timeouts,
+        )
+        .await?;
+        let mut incoming = self.incoming(
+            handshake.socket,
+            handshake.manifest,
+            handshake.peer_addr,
+            handshake.peer_username,
+        );
+        incoming.beacon = Some(handshake.beacon);
+        Ok(incoming)
+    }
+
+    /// A transfer whose manifest arrived some other way, e.g. a pull answered by
+    /// `request_items`. It is received exactly like one that was pushed.
+    pub fn incoming(
+        &self,
+        socket: TcpStream,
+        manifest: GlobalTransferManifest,
+        peer_addr: Option<String>,
+        peer_username: Option<String>,
+    ) -> IncomingTransfer {
+        IncomingTransfer {
+            socket,
+            manifest,
+            peer: Peer {
+                addr: peer_addr,
+                username: peer_username,
+                started_at: SystemTime::now(),
+                start: Instant::now(),
+            },
+            beacon: None,
+            session: self.clone(),
+        }
+    }
+
+    /// Starts taking files that reach this receiver some other way than a pxp stream,
+    /// e.g. uploaded from a browser by `peer_addr`.
+    pub async fn upload(&self, peer_addr: Option<String>) -> Upload<'_> {
+        let staging_dir = new_staging_dir(&self.target_dir).await;
+        emit(
+            self.events.as_deref(),
+            TransferEvent::Phase(TransferPhase::Streaming),
+        );
+        Upload {
+            session: self,
+            expected: 0,
+            guard: PolicyGuard::new(&self.policy),
+            sanitizer: PathSanitizer::new(),
+            staged: StagedTransfer {
+                items: Vec::new(),
+                staging_dir,
+                target_dir: self.target_dir.clone(),
+            },
+            summary: ReceiveSummary {
+                items: Vec::new(),
+                total_bytes: 0,
+                renamed: Vec::new(),
+            },
+            peer: Peer {
+                addr: peer_addr,
+                username: None,
+                started_at: SystemTime::now(),
+                start: Instant::now(),
+            },
+        }
+    }
+}
+
+/// Files arriving outside a pxp stream. Each one is named safely, checked against the
+/// receive policy and staged like a streamed file, so `finish` hands back a
+/// `ReceivedTransfer` that is reconciled exactly like a pushed one.
+pub struct Upload<'a> {
+    session: &'a ReceiveSession,
+    expected: usize,
+    guard: PolicyGuard<'a>,
+    sanitizer: PathSanitizer,
+    staged: StagedTransfer,
+    summary: ReceiveSummary,
+    peer: Peer,
+}
+
+impl Upload<'_> {
+    /// How many files the uploader announced, for progress.
+    pub fn expect(&mut self, files: usize) {
+        self.expected = files;
+    }
+
+    /// Stages one file read from `reader`. Only the last component of `name` is kept, and a
+    /// name already used in this upload gets a ` (n)` suffix.
+    /// When the uploader announced the file's `size`, the policy is checked before any of
+    /// it is written and the file must match it; otherwise it is checked once it is in.
+    /// Either way, reading stops one byte past what the size or the policy allows.
+    pub async fn add_file(
+        &mut self,
+        name: &str,
+        size: Option<u64>,
+        reader: impl AsyncRead + Unpin,
+    ) -> Result<()> {
+        let events = self.session.events.as_deref();
+        let name = Path::new(name)
+            .file_name()
+            .map(PathBuf::from)
+            .ok_or_else(|| PxpError::Protocol(format!("'{}' is not a file name", name)))?;
+        let safe_name = self.sanitizer.sanitize_distinct(&name);
+        for renamed in self.sanitizer.take_renamed() {
+            info!("Saving '{}' as '{}'", renamed.original, renamed.renamed);
+            report_rename(events, &renamed);
+            self.summary.renamed.push(renamed);
+        }
+        if let Some(size) = size {
+            self.guard.check_entry(&safe_name, size, false)?;
+        }
+
+        let item_name = safe_name.to_string_lossy().to_string();
+        let index = self.staged.items.len() + 1;
+        emit(
+            events,
+            TransferEvent::ItemStarted {
+                index,
+                total: self.expected.max(index),
+                name: item_name.clone(),
+                kind: ItemKind::File,
+                bytes: size.unwrap_or(0),
+            },
+        );
+
+        let limit = size
+            .or_else(|| self.guard.remaining_bytes())
+            .map_or(u64::MAX, |limit| limit.saturating_add(1));
+        let mut reader = reader.take(limit);
+        let head = self.guard.sniff(&safe_name, &mut reader).await?;
+
+        let staged_path = self.staged.staging_dir.join(&safe_name);
+        create_dir_all(&self.staged.staging_dir).await?;
+        let mut outfile = File::create(&staged_path).await?;
+        if !head.is_empty() {
+            outfile.write_all(&head).await?;
+            emit(events, TransferEvent::Progress { bytes: head.len() as u64 });
+        }
+        let bytes = head.len() as u64
+            + match events {
+                Some(sink) => copy(&mut Metered::new(&mut reader, sink), &mut outfile).await?,
+                None => copy(&mut reader, &mut outfile).await?,
+            };
+        match size {
+            Some(size) if size != bytes => {
+                return Err(PxpError::Protocol(format!(
+                    "'{}' was {} bytes, not the {} announced",
+                    item_name, bytes, size
+                )));
+            }
+            Some(_) => {}
+            None => self.guard.check_entry(&safe_name, bytes, false)?,
+        }
+
+        self.staged.items.push(StagedItem {
+            name: item_name.clone(),
+            staged_path,
+            final_path: self.staged.target_dir.join(&safe_name),
+            is_dir: false,
+            bytes,
+        });
+        self.summary.items.push(ReceivedItem {
+            name: item_name.clone(),
+            bytes,
+            is_directory: false,
+            text: None,
+        });
+        self.summary.total_bytes = self.summary.total_bytes.saturating_add(bytes);
+        debug!("Uploaded file staged: {} ({} bytes)", item_name, bytes);
+        emit(
+            events,
+            TransferEvent::ItemFinished {
+                name: item_name,
+                kind: ItemKind::File,
+                verified: size.is_some(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Ends the upload. `result` says how taking the files went; files staged before a
+    /// failure are kept, like those of a stream that broke off.
+    pub fn finish(self, result: Result<()>) -> ReceivedTransfer {
+        let manifest = GlobalTransferManifest {
+            total_files: self.staged.items.len() as u32,
+            total_directories: 0,
+            total_bytes: self.summary.total_bytes,
+            description: None,
+            sender_username: None,
+            compressed: false,
+        };
+        ReceivedTransfer {
+            staged: self.staged,
+            summary: self.summary,
+            stream_error: result.err(),
+            rejected: HashSet::new(),
+            manifest,
+            peer: self.peer,
+            _beacon: None,
+            session: self.session.clone(),
+        }
+    }
+}
+
+/// Who a transfer comes from and when it started.
+struct Peer {
+    addr: Option<String>,
+    username: Option<String>,
+    started_at: SystemTime,
+    start: Instant,
+}
+
+/// A transfer whose manifest is in, waiting to be accepted or rejected.
+pub struct IncomingTransfer {
+    socket: TcpStream,
+    manifest: GlobalTransferManifest,
+    peer: Peer,
+    /// Keeps the receiver advertised as busy until the transfer is over.
+    beacon: Option<BeaconHandle>,
+    session: ReceiveSession,
+}
+
+impl IncomingTransfer {
+    pub fn manifest(&self) -> &GlobalTransferManifest {
+        &self.manifest
+    }
+
+    pub fn peer_addr(&self) -> Option<&str> {
+        self.peer.addr.as_deref()
+    }
+
+    pub fn peer_username(&self) -> Option<&str> {
+        self.peer.username.as_deref()
+    }
+
+    /// Checks the manifest against the receive policy.
+    pub fn check_policy(&self) -> Result<()> {
+        self.session.policy.check_manifest(&self.manifest)
+    }
+
+    /// Fails with `InsufficientSpace` if the target filesystem can't hold the transfer.
+    pub fn check_space(&self) -> Result<()> {
+        check_free_space(&self.session.target_dir, self.manifest.total_bytes)
+    }
+
+    /// Turns the transfer down, telling the sender why.
+    pub async fn reject(mut self, reason: RejectReason) {
+        info!("Rejecting the transfer: {:?}", reason);
+        let response = ManifestResponse::Reject(reason);
+        if let Err(e) = respond_to_manifest(&mut self.socket, &response).await {
+            warn!("Could not tell the sender about the rejection: {}", e);
+        }
+    }
+
+    /// Accepts the transfer and receives the stream into staging. Only a failure to answer
+    /// the sender is returned as `Err`; a stream that breaks off part-way still hands back
+    /// whatever was staged before it did.
+    pub async fn accept(mut self) -> Result<ReceivedTransfer> {
+        respond_to_manifest(&mut self.socket, &ManifestResponse::Accept).await?;
+        debug!("Transfer accepted");
+
+        let session = &self.session;
+        let total_items = self.manifest.total_files + self.manifest.total_directories;
+        let (result, staged, summary) = receive_stream(
+            self.socket,
+            self.manifest.compressed,
+            &session.target_dir,
+            total_items,
+            &session.policy,
+            &session.timeouts,
+            session.events.as_deref(),
+        )
+        .await;
+        Ok(ReceivedTransfer {
+            staged,
+            summary,
+            stream_error: result.err(),
+            rejected: HashSet::new(),
+            manifest: self.manifest,
+            peer: self.peer,
+            _beacon: self.beacon,
+            session: self.session,
+        })
+    }
+}
+
+/// A transfer that has been streamed into staging, before anything is moved into place.
+pub struct ReceivedTransfer {
+    /// Every file and folder that made it into staging, in stream order.
+    pub staged: StagedTransfer,
+    pub summary: ReceiveSummary,
+    stream_error: Option<PxpError>,
+    rejected: HashSet<String>,
+    manifest: GlobalTransferManifest,
+    peer: Peer,
+    _beacon: Option<BeaconHandle>,
+    session: ReceiveSession,
+}
+
+impl ReceivedTransfer {
+    pub fn manifest(&self) -> &GlobalTransferManifest {
+        &self.manifest
+    }
+
+    pub fn peer_addr(&self) -> Option<&str> {
+        self.peer.addr.as_deref()
+    }
+
+    pub fn peer_username(&self) -> Option<&str> {
+        self.peer.username.as_deref()
+    }
+
+    /// Why the stream ended early, if it did.
+    pub fn stream_error(&self) -> Option<&PxpError> {
+        self.stream_error.as_ref()
+    }
+
+    /// Keeps the staged item `name` out of the target dir; it is cleared with the staging
+    /// dir. Returns false if no such item was staged.
+    pub fn reject_item(&mut self, name: &str) -> bool {
+        let staged = self.staged.items.iter().any(|item| item.name == name);
+        if staged {
+            self.rejected.insert(name.to_string());
+        }
+        staged
+    }
+
+    /// Throws away everything that was staged, e.g. after a policy violation mid-stream.
+    pub async fn discard(self) -> ReceiveOutcome {
+        discard(&self.staged).await;
+        let placed = self
+            .staged
+            .items
+            .iter()
+            .map(|item| PlacedItem {
+                name: item.name.clone(),
+                path: None,
+            })
+            .collect();
+        self.outcome(placed, None)
+    }
+
+    /// Moves every staged item that wasn't rejected into place, asking `router` where each
+    /// one goes and the session's resolver about conflicts. Runs after a broken stream too,
+    /// so whatever arrived is kept.
+    pub async fn reconcile(mut self, router: Option<&dyn ItemRouter>) -> ReceiveOutcome {
+        let order: Vec<String> = self.staged.items.iter().map(|item| item.name.clone()).collect();
+        let rejected = std::mem::take(&mut self.rejected);
+        // Rejected items stay in staging, which reconcile clears once it is done.
+        self.staged.items.retain(|item| !rejected.contains(&item.name));
+
+        let result = reconcile(
+            &self.staged,
+            self.session.resolver.as_deref(),
+            router,
+            self.session.events.as_deref(),
+        )
+        .await;
+        let reconciled = match result {
+            Ok(placed) => placed,
+            Err(e) => return self.outcome(Vec::new(), Some(e)),
+        };
+        let mut reconciled = reconciled.into_iter();
+        let placed = order
+            .into_iter()
+            .map(|name| {
+                if rejected.contains(&name) {
+                    PlacedItem { name, path: None }
+                } else {
+                    reconciled.next().unwrap_or(PlacedItem { name, path: None })
+                }
+            })
+            .collect();
+        self.outcome(placed, None)
+    }
+
+    fn outcome(self, placed: Vec<PlacedItem>, reconcile_error: Option<PxpError>) -> ReceiveOutcome {
+        ReceiveOutcome {
+            peer_addr: self.peer.addr,
+            peer_username: self.peer.username,
+            manifest: self.manifest,
+            target_dir: self.session.target_dir,
+            started_at: self.peer.started_at,
+            duration: self.peer.start.elapsed(),
+            summary: self.summary,
+            placed,
+            stream_error: self.stream_error,
+            reconcile_error,
+        }
+    }
+}
+
+/// How a receive went.
+#[derive(Debug)]
+pub struct ReceiveOutcome {
+    pub peer_addr: Option<String>,
+    pub peer_username: Option<String>,
+    pub manifest: GlobalTransferManifest,
+    pub target_dir: PathBuf,
+    /// When the manifest arrived.
+    pub started_at: SystemTime,
+    pub duration: Duration,
+    pub summary: ReceiveSummary,
+    /// Where each staged file and folder ended up, in stream order. `path` is `None` for
+    /// those that were skipped, rejected or discarded.
+    pub placed: Vec<PlacedItem>,
+    /// Why the stream ended early. Whatever arrived before it did was still placed.
+    pub stream_error: Option<PxpError>,
+    /// Why reconcile failed, in which case nothing was placed.
+    pub reconcile_error: Option<PxpError>,
+}
+
+impl ReceiveOutcome {
+    /// Whether every item arrived and was placed.
+    pub fn succeeded(&self) -> bool {
+        self.stream_error.is_none() && self.reconcile_error.is_none()
+    }
+}
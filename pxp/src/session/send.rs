@@ -0,0 +1,616 @@
+use {
+    crate::{
+        RetryPolicy, Timeouts,
+        error::{PxpError, Result, TimeoutPhase},
+        event::{EventSink, ItemKind, TransferEvent, emit},
+        metadata::GlobalTransferManifest,
+        sender::{
+            PathFilter, ScanProgress, SendItem, connect_to_receiver, count_items,
+            create_global_transfer_manifest, create_text_metadata, discover_receiver,
+            read_manifest_response, scan_paths, send_manifest, send_stream, send_stream_to_many,
+        },
+    },
+    std::{
+        fmt, io,
+        path::PathBuf,
+        sync::Arc,
+        time::{Duration, Instant, SystemTime},
+    },
+    tokio::{
+        net::TcpStream,
+        task::JoinSet,
+        time::{sleep, timeout},
+    },
+    tracing::{debug, info, warn},
+};
+
+/// Port named in the hint shown when discovery finds nothing, unless the builder sets one.
+const DEFAULT_PORT: u16 = 7878;
+
+/// Who a send goes to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SendTarget {
+    /// A receiver found by username through discovery, whose identity is then verified.
+    User(String),
+    /// A receiver at a known address. There is no beacon to verify it against.
+    Address { host: String, port: u16 },
+}
+
+impl fmt::Display for SendTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendTarget::User(username) => write!(f, "{}", username),
+            SendTarget::Address { host, port } => write!(f, "{}:{}", host, port),
+        }
+    }
+}
+
+/// Collects what a send needs. Start one with `SendSession::builder()`.
+///
+/// Items are sent in the order: scanned `paths`, then ready-made `items`, then `text`.
+pub struct SendSessionBuilder {
+    targets: Vec<SendTarget>,
+    fallback_port: u16,
+    paths: Vec<PathBuf>,
+    filter: PathFilter,
+    items: Vec<SendItem>,
+    text: Option<String>,
+    description: Option<String>,
+    sender_username: Option<String>,
+    compress: bool,
+    timeouts: Timeouts,
+    retry: RetryPolicy,
+    events: Option<Arc<dyn EventSink>>,
+    scan_progress: Option<Arc<dyn ScanProgress>>,
+}
+
+impl Default for SendSessionBuilder {
+    fn default() -> Self {
+        Self {
+            targets: Vec::new(),
+            fallback_port: DEFAULT_PORT,
+            paths: Vec::new(),
+            filter: PathFilter::default(),
+            items: Vec::new(),
+            text: None,
+            description: None,
+            sender_username: None,
+            compress: true,
+            timeouts: Timeouts::default(),
+            retry: RetryPolicy::default(),
+            events: None,
+            scan_progress: None,
+        }
+    }
+}
+
+impl SendSessionBuilder {
+    /// Adds a receiver to find by username. With more than one target, the items are read
+    /// once and streamed to all of them together.
+    pub fn to_user(self, username: impl Into<String>) -> Self {
+        self.to(SendTarget::User(username.into()))
+    }
+
+    /// Adds a receiver at a known address, skipping discovery.
+    pub fn to_address(self, host: impl Into<String>, port: u16) -> Self {
+        self.to(SendTarget::Address {
+            host: host.into(),
+            port,
+        })
+    }
+
+    /// Adds a target; the same one twice is only sent to once.
+    pub fn to(mut self, target: SendTarget) -> Self {
+        if !self.targets.contains(&target) {
+            self.targets.push(target);
+        }
+        self
+    }
+
+    /// The port suggested for direct address mode when discovery finds nobody.
+    pub fn fallback_port(mut self, port: u16) -> Self {
+        self.fallback_port = port;
+        self
+    }
+
+    /// Files and folders to send; folders are scanned through `filter` when the session is
+    /// prepared.
+    pub fn paths<I, P>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.paths.extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn filter(mut self, filter: PathFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Items that were already scanned, e.g. by `scan_directory`.
+    pub fn items(mut self, items: impl IntoIterator<Item = SendItem>) -> Self {
+        self.items.extend(items);
+        self
+    }
+
+    /// A text item, sent after every file and folder.
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        self.text = Some(text.into());
+        self
+    }
+
+    pub fn description(mut self, description: Option<String>) -> Self {
+        self.description = description;
+        self
+    }
+
+    /// The username the receiver is told this transfer comes from.
+    pub fn sender_username(mut self, username: Option<String>) -> Self {
+        self.sender_username = username;
+        self
+    }
+
+    /// Whether the stream is gzip-compressed. On by default.
+    pub fn compression(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Where the session reports its events. Shared with the connection tasks of a send to
+    /// several receivers, so it may be called from more than one task at once.
+    pub fn progress(mut self, events: impl EventSink + 'static) -> Self {
+        self.events = Some(Arc::new(events));
+        self
+    }
+
+    /// Told how scanning is going while folders are walked in `prepare`.
+    pub fn scan_progress(mut self, progress: impl ScanProgress + 'static) -> Self {
+        self.scan_progress = Some(Arc::new(progress));
+        self
+    }
+
+    /// Scans the paths and builds the manifest, without touching the network. The session
+    /// can then be inspected (e.g. to show or vet what is about to go out) before `run`.
+    pub async fn prepare(self) -> Result<SendSession> {
+        if self.targets.is_empty() {
+            return Err(PxpError::InvalidSession("no receiver to send to".into()));
+        }
+
+        let mut items =
+            scan_paths(&self.paths, &self.filter, self.scan_progress.as_deref()).await?;
+        items.extend(self.items);
+        if let Some(text) = self.text {
+            let meta = create_text_metadata(&text)?;
+            items.push(SendItem::Text { meta, text });
+        }
+        if items.is_empty() {
+            return Err(PxpError::InvalidSession("nothing to send".into()));
+        }
+        debug!("Session prepared with {} top-level items", items.len());
+
+        let (files, dirs, bytes) = count_items(&items);
+        let manifest = create_global_transfer_manifest(
+            files,
+            dirs,
+            bytes,
+            self.description,
+            self.sender_username,
+            self.compress,
+        )
+        .await?;
+
+        Ok(SendSession {
+            targets: self.targets,
+            items,
+            link: Link {
+                fallback_port: self.fallback_port,
+                manifest,
+                compress: self.compress,
+                timeouts: self.timeouts,
+                retry: self.retry,
+                events: self.events,
+            },
+        })
+    }
+
+    /// Prepares the session and runs it. Only preparation errors are returned as `Err`;
+    /// how each receiver fared is in the outcome.
+    pub async fn run(self) -> Result<SendOutcome> {
+        Ok(self.prepare().await?.run().await)
+    }
+}
+
+/// A send that is ready to go: items scanned, manifest built.
+pub struct SendSession {
+    targets: Vec<SendTarget>,
+    items: Vec<SendItem>,
+    link: Link,
+}
+
+impl SendSession {
+    pub fn builder() -> SendSessionBuilder {
+        SendSessionBuilder::default()
+    }
+
+    pub fn targets(&self) -> &[SendTarget] {
+        &self.targets
+    }
+
+    pub fn items(&self) -> &[SendItem] {
+        &self.items
+    }
+
+    pub fn manifest(&self) -> &GlobalTransferManifest {
+        &self.link.manifest
+    }
+
+    /// Delivers the items. A single receiver is retried as a whole, mid-stream drops
+    /// included. Several receivers are each discovered, connected and asked to accept at
+    /// once, with their own retries, and then share one stream; a receiver that drops out
+    /// of it fails on its own.
+    pub async fn run(self) -> SendOutcome {
+        let items: Vec<SentItem> = self.items.iter().map(SentItem::from).collect();
+        let total_bytes = self.link.manifest.total_bytes;
+        let peers = match <[SendTarget; 1]>::try_from(self.targets) {
+            Ok([target]) => {
+                let mut peer = Peer::new(target);
+                self.link.attempts(&mut peer, Some(&self.items)).await;
+                vec![peer.finish()]
+            }
+            Err(targets) => self.link.send_to_many(targets, self.items).await,
+        };
+        SendOutcome {
+            items,
+            total_bytes,
+            peers,
+        }
+    }
+}
+
+/// How a send went.
+#[derive(Debug)]
+pub struct SendOutcome {
+    /// What was offered, in stream order.
+    pub items: Vec<SentItem>,
+    pub total_bytes: u64,
+    /// One per target, in the order they were added.
+    pub peers: Vec<PeerOutcome>,
+}
+
+impl SendOutcome {
+    /// Whether every receiver got the whole transfer.
+    pub fn succeeded(&self) -> bool {
+        self.peers.iter().all(|peer| peer.error.is_none())
+    }
+}
+
+/// A top-level item of a send.
+#[derive(Debug, Clone)]
+pub struct SentItem {
+    pub name: String,
+    pub kind: ItemKind,
+    pub bytes: u64,
+}
+
+impl From<&SendItem> for SentItem {
+    fn from(item: &SendItem) -> Self {
+        Self {
+            name: item.name().to_string(),
+            kind: item.kind(),
+            bytes: item.bytes(),
+        }
+    }
+}
+
+/// How the send to one receiver went.
+#[derive(Debug)]
+pub struct PeerOutcome {
+    pub target: SendTarget,
+    /// The IP the receiver was last reached at.
+    pub host: Option<String>,
+    /// When the last attempt reached the receiver, or when the session started if none did.
+    pub started_at: SystemTime,
+    pub duration: Duration,
+    pub attempts: Vec<AttemptOutcome>,
+    /// Why this receiver didn't get the transfer.
+    pub error: Option<PxpError>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AttemptOutcome {
+    /// 1-based.
+    pub attempt: u32,
+    pub host: Option<String>,
+    pub duration: Duration,
+    /// Why the attempt failed, with its causes.
+    pub error: Option<String>,
+}
+
+/// A receiver's outcome while the session is still working on it.
+struct Peer {
+    outcome: PeerOutcome,
+    start: Instant,
+}
+
+impl Peer {
+    fn new(target: SendTarget) -> Self {
+        Self {
+            outcome: PeerOutcome {
+                target,
+                host: None,
+                started_at: SystemTime::now(),
+                duration: Duration::ZERO,
+                attempts: Vec::new(),
+                error: None,
+            },
+            start: Instant::now(),
+        }
+    }
+
+    fn label(&self) -> String {
+        self.outcome.target.to_string()
+    }
+
+    fn finish(mut self) -> PeerOutcome {
+        self.outcome.duration = self.start.elapsed();
+        self.outcome
+    }
+}
+
+/// Everything needed to reach a receiver, cloned into each connection task.
+#[derive(Clone)]
+struct Link {
+    fallback_port: u16,
+    manifest: GlobalTransferManifest,
+    compress: bool,
+    timeouts: Timeouts,
+    retry: RetryPolicy,
+    events: Option<Arc<dyn EventSink>>,
+}
+
+impl Link {
+    fn emit(&self, event: TransferEvent) {
+        emit(self.events.as_deref(), event);
+    }
+
+    /// Tries `peer` until an attempt works or the retry policy gives up, noting every
+    /// attempt in its outcome. With `items`, an attempt also streams them, so a drop
+    /// mid-stream is retried too. Without, it ends once the receiver has accepted and the
+    /// connection is handed back.
+    async fn attempts(&self, peer: &mut Peer, items: Option<&[SendItem]>) -> Option<TcpStream> {
+        let label = peer.label();
+        let max_attempts = self.retry.max_attempts();
+        let mut attempt = 0u32;
+        loop {
+            attempt += 1;
+            self.emit(TransferEvent::AttemptStarted {
+                peer: label.clone(),
+                attempt,
+                max_attempts,
+            });
+            let attempt_start = Instant::now();
+            let outcome = self.attempt(peer, items).await;
+            let error = outcome.as_ref().err().map(PxpError::with_causes);
+            peer.outcome.attempts.push(AttemptOutcome {
+                attempt,
+                host: peer.outcome.host.clone(),
+                duration: attempt_start.elapsed(),
+                error: error.clone(),
+            });
+
+            match outcome {
+                Ok(stream) => return stream,
+                Err(e) if self.retry.should_retry(&e, attempt) => {
+                    let wait = self.retry.backoff(attempt + 1);
+                    warn!(
+                        "Attempt {} of {} at '{}' failed: {}",
+                        attempt, max_attempts, label, e
+                    );
+                    self.emit(TransferEvent::AttemptFailed {
+                        peer: label.clone(),
+                        attempt,
+                        max_attempts,
+                        error: error.unwrap_or_default(),
+                        retry_in: wait,
+                    });
+                    sleep(wait).await;
+                }
+                Err(e) => {
+                    warn!("Giving up on '{}': {}", label, e);
+                    peer.outcome.error = Some(e);
+                    return None;
+                }
+            }
+        }
+    }
+
+    async fn attempt(
+        &self,
+        peer: &mut Peer,
+        items: Option<&[SendItem]>,
+    ) -> Result<Option<TcpStream>> {
+        let label = peer.label();
+        let (mut stream, answers) = self.connect(peer).await?;
+
+        peer.outcome.started_at = SystemTime::now();
+        peer.start = Instant::now();
+        send_manifest(&mut stream, &self.manifest).await?;
+        info!("Global manifest delivered to '{}'", label);
+        if answers {
+            self.emit(TransferEvent::AwaitingAcceptance {
+                peer: label.clone(),
+            });
+            read_manifest_response(&mut stream, &self.timeouts).await?;
+            self.emit(TransferEvent::Accepted {
+                peer: label.clone(),
+            });
+        } else {
+            debug!("'{}' predates manifest responses; streaming without one", label);
+        }
+
+        let Some(items) = items else {
+            return Ok(Some(stream));
+        };
+        send_stream(
+            stream,
+            items.to_vec(),
+            !self.compress,
+            &self.timeouts,
+            self.events.as_deref(),
+        )
+        .await?;
+        info!(
+            "SUCCESS: All {} items sent and stream flushed to '{}'",
+            items.len(),
+            label
+        );
+        Ok(None)
+    }
+
+    /// Finds the receiver (unless it is at a known address) and connects to it, verifying
+    /// its identity against the beacon when there is one. Also says whether the receiver
+    /// will answer the manifest: only a beacon can tell, so one at a known address is
+    /// assumed to be current.
+    async fn connect(&self, peer: &mut Peer) -> Result<(TcpStream, bool)> {
+        let label = peer.label();
+        let (host, port, node_id, answers) = match &peer.outcome.target {
+            SendTarget::User(username) => {
+                self.emit(TransferEvent::Discovering {
+                    peer: label.clone(),
+                });
+                let found =
+                    discover_receiver(username, self.fallback_port, self.timeouts.discovery)
+                        .await?;
+                let (host, port) = (found.ip.clone(), found.beacon.port);
+                let node_id = found.beacon.node_id.clone();
+                let answers = found.beacon.answers_manifest();
+                self.emit(TransferEvent::Discovered {
+                    peer: label.clone(),
+                    receiver: Box::new(found),
+                });
+                (host, port, Some(node_id), answers)
+            }
+            SendTarget::Address { host, port } => (host.clone(), *port, None, true),
+        };
+        peer.outcome.host = Some(host.clone());
+
+        self.emit(TransferEvent::Connecting {
+            peer: label.clone(),
+            host: host.clone(),
+            port,
+        });
+        let stream = connect_to_receiver(&host, port, node_id.as_deref(), &self.timeouts).await?;
+        self.emit(TransferEvent::Connected {
+            peer: label,
+            host,
+            verified: node_id.is_some(),
+        });
+        Ok((stream, answers))
+    }
+
+    /// Gets every target to accept at once, then streams the items to all that did.
+    /// Once the first receiver is ready the rest get half of `timeouts.idle` to catch up,
+    /// so one that is offline can't leave the ready ones waiting until they give up.
+    async fn send_to_many(self, targets: Vec<SendTarget>, items: Vec<SendItem>) -> Vec<PeerOutcome> {
+        let mut tasks = JoinSet::new();
+        for (index, target) in targets.iter().cloned().enumerate() {
+            let link = self.clone();
+            tasks.spawn(async move {
+                let mut peer = Peer::new(target);
+                let stream = link.attempts(&mut peer, None).await;
+                (index, peer, stream)
+            });
+        }
+
+        let mut joined: Vec<Option<(Peer, Option<TcpStream>)>> =
+            targets.iter().map(|_| None).collect();
+        let mut deadline: Option<Instant> = None;
+        let mut late = false;
+        loop {
+            let next = match deadline {
+                Some(deadline) => {
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    match timeout(left, tasks.join_next()).await {
+                        Ok(next) => next,
+                        Err(_) => {
+                            warn!(
+                                "Streaming without the {} receiver(s) still connecting",
+                                tasks.len()
+                            );
+                            tasks.abort_all();
+                            late = true;
+                            break;
+                        }
+                    }
+                }
+                None => tasks.join_next().await,
+            };
+            let Some(result) = next else { break };
+            match result {
+                Ok((index, peer, stream)) => {
+                    if stream.is_some() && deadline.is_none() {
+                        deadline = Some(Instant::now() + self.timeouts.idle / 2);
+                    }
+                    joined[index] = Some((peer, stream));
+                }
+                Err(e) => warn!("Connection task ended unexpectedly: {}", e),
+            }
+        }
+        let mut peers: Vec<(Peer, Option<TcpStream>)> = joined
+            .into_iter()
+            .zip(targets)
+            .map(|(peer, target)| {
+                peer.unwrap_or_else(|| {
+                    let mut peer = Peer::new(target);
+                    peer.outcome.error = Some(if late {
+                        PxpError::Timeout {
+                            phase: TimeoutPhase::Connect,
+                        }
+                    } else {
+                        PxpError::Io(io::Error::other("connection task ended unexpectedly"))
+                    });
+                    (peer, None)
+                })
+            })
+            .collect();
+
+        let ready: Vec<usize> = (0..peers.len())
+            .filter(|&i| peers[i].1.is_some())
+            .collect();
+        if !ready.is_empty() {
+            let streams = ready
+                .iter()
+                .filter_map(|&i| peers[i].1.take())
+                .collect();
+            let outcomes = send_stream_to_many(
+                streams,
+                items,
+                !self.compress,
+                &self.timeouts,
+                self.events.as_deref(),
+            )
+            .await;
+            for (&index, outcome) in ready.iter().zip(outcomes) {
+                if let Err(e) = outcome {
+                    let peer = &mut peers[index].0;
+                    warn!("Stream to '{}' failed: {}", peer.label(), e);
+                    peer.outcome.error = Some(e);
+                }
+            }
+        }
+        info!("Fan-out send finished for {} receivers", peers.len());
+        peers.into_iter().map(|(peer, _)| peer.finish()).collect()
+    }
+}